@@ -30,6 +30,74 @@ pub enum MainSubCommands {
     #[clap(arg_required_else_help = true)]
     #[clap(subcommand)]
     Export(ExportSubCommands),
+
+    /// Benchmark import and export on a generated blueprint book
+    Bench(Bench),
+
+    /// Summarize a blueprint string, file or tree without writing any files
+    #[clap(arg_required_else_help = true)]
+    Info(Info),
+
+    /// Count the entities, items and tiles in a blueprint or book
+    #[clap(arg_required_else_help = true)]
+    Bom(Bom),
+
+    /// Extract a single blueprint, book or planner from a book
+    #[clap(arg_required_else_help = true)]
+    Extract(Extract),
+
+    /// Merge two blueprint books into one
+    #[clap(arg_required_else_help = true)]
+    Merge(Merge),
+
+    /// Flatten a nested book, or group its items into sub-books
+    #[clap(arg_required_else_help = true)]
+    Restructure(Restructure),
+
+    /// Search blueprints for entities, circuit signals or text
+    #[clap(arg_required_else_help = true)]
+    Find(Find),
+
+    /// Replace entity and item names in every blueprint of a tree, in place
+    #[clap(arg_required_else_help = true)]
+    Replace(Replace),
+
+    /// Apply an upgrade or deconstruction planner to a blueprint or book
+    #[clap(arg_required_else_help = true)]
+    ApplyPlanner(ApplyPlanner),
+
+    /// Mirror, rotate or move the contents of a blueprint or book
+    #[clap(arg_required_else_help = true)]
+    Transform(Transform),
+
+    /// Draw a blueprint as an SVG image, or a book as a contact sheet
+    #[clap(arg_required_else_help = true)]
+    Render(Render),
+
+    /// Preview a blueprint in the terminal
+    #[clap(arg_required_else_help = true)]
+    Show(Show),
+
+    /// Browse a book in the terminal and copy any item as a blueprint string
+    #[clap(arg_required_else_help = true)]
+    Browse(Browse),
+
+    /// Set the icons of blueprints and books from their contents
+    #[clap(arg_required_else_help = true)]
+    Icons(Icons),
+
+    /// Report files of an imported tree that were modified, deleted or added since the import
+    Check(Check),
+
+    /// Show the settings of the fbpconvert.toml found in this or a parent directory
+    #[clap(arg_required_else_help = true)]
+    #[clap(subcommand)]
+    Config(ConfigSubCommands),
+
+    /// Add, remove, move and reorder the items of a book tree created by import
+    #[clap(arg_required_else_help = true)]
+    #[clap(subcommand)]
+    Book(BookSubCommands),
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -48,17 +116,42 @@ pub enum ImportSubCommands {
 #[derive(Parser, Debug, Clone)]
 pub enum ExportSubCommands {
     /// Export blueprint strings to a file
-    #[clap(arg_required_else_help = true)]
     File(ExportFile),
 
     /// Export blueprint strings to the clipboard
-    #[clap(arg_required_else_help = true)]
     Clipboard(ExportClipboard),
 }
 
+#[derive(Parser, Debug, Clone)]
+pub enum ConfigSubCommands {
+    /// Print the effective settings and where each comes from
+    Show,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub enum BookSubCommands {
+    /// Add a blueprint, book or planner to a book
+    #[clap(arg_required_else_help = true)]
+    Add(BookAdd),
+
+    /// Remove an item and its files from its book
+    #[clap(arg_required_else_help = true)]
+    Rm(BookRm),
+
+    /// Move an item to another slot or into another book
+    #[clap(arg_required_else_help = true)]
+    Mv(BookMv),
+
+    /// Number the items of a book from slot 0, listed items first
+    #[clap(arg_required_else_help = true)]
+    Reorder(BookReorder),
+}
+
 /// Contains CLI flags/arguments for various commands/subcommands
 pub mod commands {
     use super::*;
+    use crate::common::{CompressionLevel, FileFormat, FileNaming};
+    use clap::builder::TypedValueParser;
 
     #[derive(Parser, Debug, Clone)]
     pub struct ImportFile {
@@ -70,6 +163,35 @@ pub mod commands {
         #[clap(short, long)]
         pub destination: Option<String>,
 
+        /// Name files after labels with rich text tags turned into readable tokens.
+        /// Labels in the JSON are kept as they are. Same as "--naming readable"
+        #[clap(long, conflicts_with = "naming")]
+        pub readable_names: bool,
+
+        /// How files are named after labels [default: renamed]
+        #[clap(long, value_enum)]
+        pub naming: Option<FileNaming>,
+
+        /// Layout of the JSON files [default: pretty]
+        #[clap(long, value_enum)]
+        pub file_format: Option<FileFormat>,
+
+        /// Sort keys, entities and tiles so that the same blueprint always gives the same
+        /// files. "--canonical=false" turns it off when fbpconvert.toml turns it on
+        #[clap(
+            long,
+            value_parser,
+            min_values = 0,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        pub canonical: Option<bool>,
+
+        /// Commit the imported tree to its git repository, listing the added, changed
+        /// and removed blueprints. Items no longer in the string are removed from the tree
+        #[clap(long)]
+        pub commit: bool,
+
         /// Inflate blueprint string only. Writes contents to file as "inflated.json".
         #[cfg(debug_assertions)]
         #[clap(long)]
@@ -94,6 +216,35 @@ pub mod commands {
         #[clap(short, long)]
         pub destination: Option<String>,
 
+        /// Name files after labels with rich text tags turned into readable tokens.
+        /// Labels in the JSON are kept as they are. Same as "--naming readable"
+        #[clap(long, conflicts_with = "naming")]
+        pub readable_names: bool,
+
+        /// How files are named after labels [default: renamed]
+        #[clap(long, value_enum)]
+        pub naming: Option<FileNaming>,
+
+        /// Layout of the JSON files [default: pretty]
+        #[clap(long, value_enum)]
+        pub file_format: Option<FileFormat>,
+
+        /// Sort keys, entities and tiles so that the same blueprint always gives the same
+        /// files. "--canonical=false" turns it off when fbpconvert.toml turns it on
+        #[clap(
+            long,
+            value_parser,
+            min_values = 0,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        pub canonical: Option<bool>,
+
+        /// Commit the imported tree to its git repository, listing the added, changed
+        /// and removed blueprints. Items no longer in the string are removed from the tree
+        #[clap(long)]
+        pub commit: bool,
+
         /// Inflate blueprint string only. Writes contents to file as "inflated.json".
         #[cfg(debug_assertions)]
         #[clap(long)]
@@ -102,7 +253,7 @@ pub mod commands {
 
     #[derive(Parser, Debug, Clone)]
     pub struct ExportFile {
-        /// Source directory or single JSON file
+        /// Source directory or single JSON file, defaults to the tree of fbpconvert.toml
         #[clap(value_parser)]
         pub source: Option<String>,
 
@@ -113,12 +264,421 @@ pub mod commands {
         /// Destination directory (optional)
         #[clap(short, long)]
         pub destination: Option<String>,
+
+        /// Compression level of the blueprint string [default: default]
+        #[clap(short, long, value_enum)]
+        pub compression: Option<CompressionLevel>,
     }
 
     #[derive(Parser, Debug, Clone)]
     pub struct ExportClipboard {
-        /// Source directory or single JSON file
+        /// Source directory or single JSON file, defaults to the tree of fbpconvert.toml
+        #[clap(value_parser)]
+        pub source: Option<String>,
+
+        /// Compression level of the blueprint string [default: default]
+        #[clap(short, long, value_enum)]
+        pub compression: Option<CompressionLevel>,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Bench {
+        /// Number of sub-books and blueprints inside each book
+        #[clap(short, long, default_value_t = 4)]
+        pub books: usize,
+
+        /// Nesting depth of the generated book
+        #[clap(short, long, default_value_t = 3)]
+        pub depth: usize,
+
+        /// Number of entities in each generated blueprint
+        #[clap(short, long, default_value_t = 200)]
+        pub entities: usize,
+
+        /// Seed for the blueprint generator
+        #[clap(short, long, default_value_t = 1)]
+        pub seed: u64,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Info {
+        /// Blueprint string, file, JSON tree or "clipboard"
+        #[clap(value_parser)]
+        pub source: String,
+    }
+
+    /// Output format of reports
+    #[derive(clap::ValueEnum, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+    #[serde(rename_all = "lowercase")]
+    pub enum ReportFormat {
+        #[default]
+        Table,
+        Csv,
+        Json,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Bom {
+        /// Blueprint string, file, JSON tree or "clipboard"
+        #[clap(value_parser)]
+        pub source: String,
+
+        /// Output format [default: table]
+        #[clap(short, long, value_enum)]
+        pub format: Option<ReportFormat>,
+    }
+
+    /// Destination of blueprints created by a command.
+    /// The blueprint string is printed to stdout unless another destination is given.
+    #[derive(Parser, Debug, Clone)]
+    pub struct Output {
+        /// Write the blueprint string to a file
+        #[clap(short, long)]
+        pub outfile: Option<String>,
+
+        /// Copy the blueprint string into the clipboard
+        #[clap(long)]
+        pub clipboard: bool,
+
+        /// Write a JSON tree into this directory instead of a blueprint string
+        #[clap(short, long)]
+        pub tree: Option<String>,
+
+        /// Compression level of the blueprint string [default: default]
+        #[clap(short, long, value_enum)]
+        pub compression: Option<CompressionLevel>,
+
+        /// How files of the JSON tree are named after labels [default: renamed]
+        #[clap(long, value_enum)]
+        pub naming: Option<FileNaming>,
+
+        /// Layout of the JSON files [default: pretty]
+        #[clap(long, value_enum)]
+        pub file_format: Option<FileFormat>,
+
+        /// Sort keys, entities and tiles so that the same blueprint always gives the same
+        /// files. "--canonical=false" turns it off when fbpconvert.toml turns it on
+        #[clap(
+            long,
+            value_parser,
+            min_values = 0,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        pub canonical: Option<bool>,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Extract {
+        /// Blueprint string, file, JSON tree or "clipboard"
+        #[clap(value_parser)]
+        pub source: String,
+
+        /// Path to the item, as labels ("Book/Sub book/Blueprint") or slot indices ("3/1/7")
+        #[clap(value_parser)]
+        pub path: String,
+
+        #[clap(flatten)]
+        pub output: Output,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct BookAdd {
+        /// Book directory to add to
+        #[clap(value_parser)]
+        pub book: String,
+
+        /// Blueprint string, file, JSON tree or "clipboard"
+        #[clap(value_parser)]
+        pub source: String,
+
+        /// Slot index, later items move up if it is taken. Defaults to after the last item
+        #[clap(short, long)]
+        pub index: Option<u64>,
+
+        /// How the files of the item are named after labels [default: renamed]
+        #[clap(long, value_enum)]
+        pub naming: Option<FileNaming>,
+
+        /// Layout of the JSON files [default: pretty]
+        #[clap(long, value_enum)]
+        pub file_format: Option<FileFormat>,
+
+        /// Sort keys, entities and tiles so that the same blueprint always gives the same
+        /// files. "--canonical=false" turns it off when fbpconvert.toml turns it on
+        #[clap(
+            long,
+            value_parser,
+            min_values = 0,
+            require_equals = true,
+            default_missing_value = "true"
+        )]
+        pub canonical: Option<bool>,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct BookRm {
+        /// JSON file or book directory to remove
+        #[clap(value_parser)]
+        pub item: String,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct BookMv {
+        /// JSON file or book directory to move
+        #[clap(value_parser)]
+        pub item: String,
+
+        /// Book directory to move into, may be the item's own book
+        #[clap(value_parser)]
+        pub book: String,
+
+        /// Slot index, later items move up if it is taken. Defaults to after the last item
+        #[clap(short, long)]
+        pub index: Option<u64>,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct BookReorder {
+        /// Book directory to reorder
+        #[clap(value_parser)]
+        pub book: String,
+
+        /// Items to place first, by slot index, label or file name
+        #[clap(value_parser)]
+        pub items: Vec<String>,
+
+        /// Sort the remaining items by label instead of keeping their order
+        #[clap(long)]
+        pub by_label: bool,
+    }
+
+    /// What to keep when both books contain a blueprint or planner with the same label
+    #[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum LabelPolicy {
+        /// Keep both items
+        KeepBoth,
+        /// Keep the item of the left book
+        Left,
+        /// Keep the item saved with the newer game version, the left one if equal
+        Newer,
+    }
+
+    /// How sub-books with the same label in both books are combined
+    #[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum BookPolicy {
+        /// Merge their contents with the same policies
+        Merge,
+        /// Place the right sub-book inside the left one
+        Nest,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Merge {
+        /// Left book: blueprint string, file, JSON tree or "clipboard"
+        #[clap(value_parser)]
+        pub left: String,
+
+        /// Right book, merged into the left one
+        #[clap(value_parser)]
+        pub right: String,
+
+        /// Items with the same label
+        #[clap(long, value_enum, default_value = "keep-both")]
+        pub same_label: LabelPolicy,
+
+        /// Sub-books with the same label
+        #[clap(long, value_enum, default_value = "merge")]
+        pub sub_books: BookPolicy,
+
+        #[clap(flatten)]
+        pub output: Output,
+    }
+
+    /// How the items of a book are grouped into sub-books
+    #[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum GroupBy {
+        /// Text of the label before the separator
+        Prefix,
+        /// Most common entity of each blueprint
+        Entity,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Restructure {
+        /// Blueprint string, file, JSON tree or "clipboard"
+        #[clap(value_parser)]
+        pub source: String,
+
+        /// Move every item into the top-level book, prefixing labels with their sub-book labels
+        #[clap(long)]
+        pub flatten: bool,
+
+        /// Group the items of the flattened book into sub-books
+        #[clap(short, long, value_enum)]
+        pub group_by: Option<GroupBy>,
+
+        /// Separates sub-book labels in the prefix of flattened labels
+        #[clap(long, default_value = "/")]
+        pub separator: String,
+
+        /// Maximum nesting of sub-books created when grouping by prefix
+        #[clap(long)]
+        pub depth: Option<usize>,
+
+        #[clap(flatten)]
+        pub output: Output,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Find {
+        /// Blueprint string, file, JSON tree or "clipboard", defaults to the tree of fbpconvert.toml
         #[clap(value_parser)]
         pub source: Option<String>,
+
+        /// Entity or recipe name, may be given more than once
+        #[clap(short, long)]
+        pub entity: Vec<String>,
+
+        /// Signal name used in circuit settings, may be given more than once
+        #[clap(short, long)]
+        pub signal: Vec<String>,
+
+        /// Text in the labels or description, may be given more than once
+        #[clap(short, long)]
+        pub text: Vec<String>,
+
+        /// List blueprints matching any of the filters instead of all of them
+        #[clap(long)]
+        pub any: bool,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Replace {
+        /// JSON tree or single JSON file created by import, defaults to the tree of fbpconvert.toml
+        #[clap(value_parser)]
+        pub tree: Option<String>,
+
+        /// Names to replace as from=to, separated by commas
+        #[clap(short, long, required = true, value_delimiter = ',')]
+        pub map: Vec<String>,
+
+        /// Count the changes without writing any files
+        #[clap(long)]
+        pub dry_run: bool,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct ApplyPlanner {
+        /// Planner: blueprint string, JSON file or "clipboard"
+        #[clap(value_parser)]
+        pub planner: String,
+
+        /// Blueprint or book to apply the planner to
+        #[clap(value_parser)]
+        pub target: String,
+
+        #[clap(flatten)]
+        pub output: Output,
+    }
+
+    /// Axis a blueprint is mirrored along
+    #[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum MirrorAxis {
+        /// Flip horizontally, left becomes right
+        X,
+        /// Flip vertically, top becomes bottom
+        Y,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Transform {
+        /// Blueprint string, JSON file or "clipboard"
+        #[clap(value_parser)]
+        pub source: String,
+
+        /// Mirror along an axis, applied first
+        #[clap(short, long, value_enum)]
+        pub mirror: Option<MirrorAxis>,
+
+        /// Rotate clockwise by 90, 180 or 270 degrees, applied after mirroring
+        #[clap(
+            short,
+            long,
+            value_parser = clap::builder::PossibleValuesParser::new(["90", "180", "270"])
+                .map(|degrees| degrees.parse::<u16>().unwrap())
+        )]
+        pub rotate: Option<u16>,
+
+        /// Move by whole tiles, given as dx,dy
+        #[clap(long, allow_hyphen_values = true)]
+        pub offset: Option<String>,
+
+        #[clap(flatten)]
+        pub output: Output,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Render {
+        /// Blueprint string, JSON file, book directory or "clipboard"
+        #[clap(value_parser)]
+        pub source: String,
+
+        /// SVG file to write, printed to stdout if not given
+        #[clap(short, long, value_parser)]
+        pub outfile: Option<String>,
+
+        /// Pixels per tile of a single blueprint
+        #[clap(short, long, default_value_t = 16.0)]
+        pub scale: f64,
+
+        /// Blueprints per row of a book's contact sheet
+        #[clap(long, default_value_t = 4)]
+        pub columns: usize,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Show {
+        /// Blueprint string, JSON file or "clipboard"
+        #[clap(value_parser)]
+        pub source: String,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Browse {
+        /// Blueprint string, JSON file, book directory or "clipboard"
+        #[clap(value_parser)]
+        pub source: String,
+
+        /// Compression level of copied blueprint strings [default: default]
+        #[clap(short, long, value_enum)]
+        pub compression: Option<CompressionLevel>,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Icons {
+        /// JSON tree or single JSON file created by import, defaults to the tree of fbpconvert.toml
+        #[clap(value_parser)]
+        pub tree: Option<String>,
+
+        /// Pick icons from the most significant entities, books from their items
+        #[clap(long)]
+        pub auto: bool,
+
+        /// Weights as name=weight, separated by commas. Names are entities or kinds:
+        /// belt, inserter, production, mining, power, logistics, fluid, rail, circuit, defense, other
+        #[clap(short, long, value_delimiter = ',')]
+        pub weight: Vec<String>,
+
+        /// Replace existing icons
+        #[clap(long)]
+        pub overwrite: bool,
+    }
+
+    #[derive(Parser, Debug, Clone)]
+    pub struct Check {
+        /// Tree root holding fbpconvert.lock, defaults to the tree of fbpconvert.toml or "."
+        #[clap(value_parser)]
+        pub tree: Option<String>,
     }
 }
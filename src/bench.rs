@@ -0,0 +1,343 @@
+//! Benchmarks the import and export workers on a generated blueprint book.
+//!
+//! The book is generated from a seed, so the same arguments always produce the same book
+//! and timings can be compared between builds.
+
+use std::fs;
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+use crossterm::style::Stylize;
+use rayon::prelude::*;
+use serde_json::{json, Value};
+
+use crate::common::BlueprintType;
+use crate::progress::{self, CommandType};
+use crate::{args, common, export, factorio_structs, import};
+
+/// Version number stamped on generated blueprints (factorio 1.1)
+const BENCH_VERSION: u64 = 281479275675648;
+
+/// Label of the top-level generated book
+const BENCH_BOOK_LABEL: &str = "bench";
+
+/// Entities placed by the generator, with their recipe (if any)
+const BENCH_ENTITIES: &[(&str, Option<&str>)] = &[
+    ("transport-belt", None),
+    ("fast-transport-belt", None),
+    ("inserter", None),
+    ("fast-inserter", None),
+    ("assembling-machine-2", Some("iron-gear-wheel")),
+    ("assembling-machine-2", Some("electronic-circuit")),
+    ("stone-furnace", None),
+    ("medium-electric-pole", None),
+    ("pipe", None),
+    ("steel-chest", None),
+];
+
+pub struct Worker {
+    pub bench_args: args::Bench,
+}
+
+/// Number of items created by the generator
+#[derive(Default)]
+struct GeneratedStats {
+    books: usize,
+    blueprints: usize,
+    entities: usize,
+}
+
+/// Deterministic blueprint book generator (xorshift64)
+struct Generator {
+    state: u64,
+    stats: GeneratedStats,
+}
+
+impl Generator {
+    fn new(seed: u64) -> Generator {
+        Generator {
+            // xorshift state must never be zero
+            state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1,
+            stats: GeneratedStats::default(),
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Returns a number in the range `0..upper`
+    fn below(&mut self, upper: u64) -> u64 {
+        self.next() % upper.max(1)
+    }
+
+    /// Generates a book with `books` blueprints and, above the last level, `books` sub-books
+    fn book(&mut self, label: String, depth: usize, bench_args: &args::Bench) -> Value {
+        self.stats.books += 1;
+
+        let mut children = Vec::new();
+        for idx in 0..bench_args.books {
+            let mut blueprint =
+                self.blueprint(format!("{} bp {}", label, idx), bench_args.entities);
+            blueprint["index"] = json!(children.len());
+            children.push(blueprint);
+        }
+        if depth > 1 {
+            for idx in 0..bench_args.books {
+                let mut book = self.book(format!("{} book {}", label, idx), depth - 1, bench_args);
+                book["index"] = json!(children.len());
+                children.push(book);
+            }
+        }
+
+        json!({
+            factorio_structs::FACTORIO_BP_BOOK_KEY: {
+                "item": "blueprint-book",
+                "label": label,
+                "active_index": 0,
+                "version": BENCH_VERSION,
+                "blueprints": children,
+            }
+        })
+    }
+
+    /// Generates a blueprint with a square-ish cloud of entities
+    fn blueprint(&mut self, label: String, entities: usize) -> Value {
+        self.stats.blueprints += 1;
+        self.stats.entities += entities;
+
+        let side = ((entities as f64).sqrt().ceil() as u64 * 2).max(1);
+        let entity_list: Vec<Value> = (0..entities)
+            .map(|idx| {
+                let (name, recipe) =
+                    BENCH_ENTITIES[self.below(BENCH_ENTITIES.len() as u64) as usize];
+                let mut entity = json!({
+                    "entity_number": idx + 1,
+                    "name": name,
+                    "position": {
+                        "x": self.below(side) as f64 + 0.5,
+                        "y": self.below(side) as f64 + 0.5,
+                    },
+                    "direction": self.below(4) * 2,
+                });
+                if let Some(recipe) = recipe {
+                    entity["recipe"] = json!(recipe);
+                }
+                entity
+            })
+            .collect();
+
+        json!({
+            factorio_structs::FACTORIO_BP_KEY: {
+                "icons": [{"signal": {"type": "item", "name": "assembling-machine-2"}, "index": 1}],
+                "item": "blueprint",
+                "label": label,
+                "version": BENCH_VERSION,
+                "entities": entity_list,
+            }
+        })
+    }
+}
+
+impl Worker {
+    pub fn from(bench_args: &args::Bench) -> Worker {
+        Worker {
+            bench_args: bench_args.clone(),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let progress_tracker = progress::Tracker::new_quiet_sync(CommandType::Bench);
+        let bench_dir =
+            std::env::temp_dir().join(format!("fbpconvert-bench-{}", std::process::id()));
+        // exit skips destructors, the benchmark directory is removed before it
+        let bench_error = |err_msg: String| -> ! {
+            let _ = fs::remove_dir_all(&bench_dir);
+            progress_tracker.lock().unwrap().error_additional(err_msg);
+            exit(1);
+        };
+
+        // setup, not timed
+        let mut generator = Generator::new(self.bench_args.seed);
+        let book = generator.book(
+            BENCH_BOOK_LABEL.to_string(),
+            self.bench_args.depth.max(1),
+            &self.bench_args,
+        );
+        let json_size = serde_json::to_vec(&book).map(|v| v.len()).unwrap_or(0);
+        let bp_string = match common::factorio_deflate(&book, common::CompressionLevel::Default) {
+            Ok(_string) => _string,
+            Err(err_msg) => bench_error(err_msg),
+        };
+        drop(book);
+
+        let _ = fs::remove_dir_all(&bench_dir);
+        if fs::create_dir_all(&bench_dir).is_err() {
+            bench_error("failed to create benchmark directory".to_string());
+        }
+
+        let mut timings: Vec<(&str, Duration)> = vec![];
+
+        // inflate
+        let start = Instant::now();
+        let inflated = match common::factorio_inflate(&bp_string) {
+            Ok(_inflated) => _inflated,
+            Err(err_msg) => bench_error(err_msg),
+        };
+        let book: Value = match serde_json::from_str(&inflated) {
+            Ok(_book) => _book,
+            Err(_) => bench_error("json parse error".to_string()),
+        };
+        drop(inflated);
+        timings.push(("inflate", start.elapsed()));
+
+        // classify
+        let start = Instant::now();
+        let classified = Worker::classify_recursive(&book);
+        timings.push(("classify", start.elapsed()));
+
+        // write
+        let start = Instant::now();
+        if let Err(err_msg) = import::Worker::recursive_book_write(
+            &progress_tracker,
+            book,
+            &bench_dir,
+            common::TreeStyle::default(),
+        ) {
+            bench_error(err_msg);
+        }
+        timings.push(("write", start.elapsed()));
+
+        // read
+        let start = Instant::now();
+        let book = match export::Worker::read_book_recursive(
+            &progress_tracker,
+            &bench_dir.join(BENCH_BOOK_LABEL),
+        ) {
+            Ok(_book) => _book,
+            Err(err_msg) => bench_error(err_msg),
+        };
+        timings.push(("read", start.elapsed()));
+
+        // deflate
+        let start = Instant::now();
+        if let Err(err_msg) = common::factorio_deflate(&book, common::CompressionLevel::Default) {
+            bench_error(err_msg);
+        }
+        timings.push(("deflate", start.elapsed()));
+
+        let _ = fs::remove_dir_all(&bench_dir);
+
+        let errors = progress_tracker.lock().unwrap().errors;
+        Worker::report(
+            &generator.stats,
+            classified,
+            json_size,
+            bp_string.len(),
+            &timings,
+            errors,
+        );
+    }
+
+    /// Classifies every node in the book, returning the number of valid nodes
+    fn classify_recursive(unknown_bp: &Value) -> usize {
+        let classified = match BlueprintType::classify(unknown_bp) {
+            BlueprintType::Invalid => return 0,
+            _ => 1,
+        };
+
+        match unknown_bp
+            .get(factorio_structs::FACTORIO_BP_BOOK_KEY)
+            .and_then(|book| book.get("blueprints"))
+        {
+            Some(Value::Array(bp_arr)) => {
+                classified
+                    + bp_arr
+                        .par_iter()
+                        .map(Worker::classify_recursive)
+                        .sum::<usize>()
+            }
+            _ => classified,
+        }
+    }
+
+    /// Prints the benchmark results
+    fn report(
+        stats: &GeneratedStats,
+        classified: usize,
+        json_size: usize,
+        string_size: usize,
+        timings: &[(&str, Duration)],
+        errors: u16,
+    ) {
+        let label = |name: &str| format!("{:<12}", name).green().bold();
+
+        println!(
+            "{}{} books, {} blueprints, {} entities",
+            label("generated"),
+            stats.books,
+            stats.blueprints,
+            stats.entities
+        );
+        println!("{}{} nodes", label("classified"), classified);
+        println!("{}{} bytes", label("json"), json_size);
+        println!("{}{} bytes", label("string"), string_size);
+
+        for (phase, elapsed) in timings {
+            println!(
+                "{}{:>10.3} ms",
+                label(phase),
+                elapsed.as_secs_f64() * 1000.0
+            );
+        }
+        let total: Duration = timings.iter().map(|(_, elapsed)| *elapsed).sum();
+        println!(
+            "{}{:>10.3} ms",
+            label("total"),
+            total.as_secs_f64() * 1000.0
+        );
+
+        if errors > 0 {
+            println!("{}{}", format!("{:<12}", "errors").red().bold(), errors);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bench_args() -> args::Bench {
+        args::Bench {
+            books: 2,
+            depth: 2,
+            entities: 5,
+            seed: 7,
+        }
+    }
+
+    #[test]
+    fn test_generator_deterministic() {
+        let first = Generator::new(7).book("bench".to_string(), 2, &bench_args());
+        let second = Generator::new(7).book("bench".to_string(), 2, &bench_args());
+        assert_eq!(first, second);
+
+        let other_seed = Generator::new(8).book("bench".to_string(), 2, &bench_args());
+        assert_ne!(first, other_seed);
+    }
+
+    #[test]
+    fn test_generator_shape() {
+        let mut generator = Generator::new(1);
+        let book = generator.book("bench".to_string(), 2, &bench_args());
+
+        assert_eq!(generator.stats.books, 3);
+        assert_eq!(generator.stats.blueprints, 6);
+        assert_eq!(generator.stats.entities, 30);
+        assert_eq!(Worker::classify_recursive(&book), 9);
+    }
+}
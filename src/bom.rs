@@ -0,0 +1,317 @@
+//! Bill of materials: totals the entities, requested items and tiles of blueprints.
+
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal};
+use std::process::exit;
+
+use crossterm::style::Stylize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::args::{self, ReportFormat};
+use crate::common;
+use crate::progress::{self, CommandType};
+use crate::source::Source;
+
+pub struct Worker {
+    pub source: Source,
+    pub format: ReportFormat,
+}
+
+/// Item counts of one or more blueprints, sorted by item name
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct BillOfMaterials {
+    /// Placed entities
+    pub entities: BTreeMap<String, u64>,
+    /// Items requested for entities, such as modules
+    pub items: BTreeMap<String, u64>,
+    /// Placed tiles
+    pub tiles: BTreeMap<String, u64>,
+}
+
+/// Bill of materials of a single blueprint inside the source
+#[derive(Serialize, Debug, Clone)]
+pub struct BlueprintMaterials {
+    /// Labels of the enclosing books and the blueprint, separated by "/"
+    pub path: String,
+    #[serde(flatten)]
+    pub materials: BillOfMaterials,
+}
+
+/// Full report: a breakdown per blueprint and the grand total
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub blueprints: Vec<BlueprintMaterials>,
+    pub total: BillOfMaterials,
+}
+
+impl BillOfMaterials {
+    /// Counts the contents of a blueprint object (the value under the "blueprint" key)
+    pub fn from(blueprint: &Value) -> BillOfMaterials {
+        let mut bom = BillOfMaterials::default();
+
+        for entity in blueprint
+            .get("entities")
+            .and_then(|entities| entities.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(name) = entity.get("name").and_then(|name| name.as_str()) {
+                *bom.entities.entry(name.to_string()).or_default() += 1;
+            }
+
+            match entity.get("items") {
+                // 1.1: {"speed-module": 2}
+                Some(Value::Object(requests)) => {
+                    for (name, count) in requests {
+                        *bom.items.entry(name.clone()).or_default() += count.as_u64().unwrap_or(0);
+                    }
+                }
+                // 2.0: [{"id": {"name": "speed-module"}, "items": {"in_inventory": [...]}}]
+                Some(Value::Array(requests)) => {
+                    for request in requests {
+                        let name = match request.pointer("/id/name").and_then(|n| n.as_str()) {
+                            Some(_name) => _name,
+                            None => continue,
+                        };
+                        let count: u64 = request
+                            .pointer("/items/in_inventory")
+                            .and_then(|slots| slots.as_array())
+                            .into_iter()
+                            .flatten()
+                            .map(|slot| slot.get("count").and_then(|c| c.as_u64()).unwrap_or(1))
+                            .sum();
+                        *bom.items.entry(name.to_string()).or_default() += count;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        for tile in blueprint
+            .get("tiles")
+            .and_then(|tiles| tiles.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(name) = tile.get("name").and_then(|name| name.as_str()) {
+                *bom.tiles.entry(name.to_string()).or_default() += 1;
+            }
+        }
+
+        bom
+    }
+
+    /// Adds the counts of another bill of materials to this one
+    pub fn add(&mut self, other: &BillOfMaterials) {
+        for (totals, counts) in [
+            (&mut self.entities, &other.entities),
+            (&mut self.items, &other.items),
+            (&mut self.tiles, &other.tiles),
+        ] {
+            for (name, count) in counts {
+                *totals.entry(name.clone()).or_default() += count;
+            }
+        }
+    }
+
+    /// Iterates over all counts as (category, name, count)
+    pub fn rows(&self) -> impl Iterator<Item = (&'static str, &String, &u64)> {
+        let entities = self.entities.iter().map(|(n, c)| ("entity", n, c));
+        let items = self.items.iter().map(|(n, c)| ("item", n, c));
+        let tiles = self.tiles.iter().map(|(n, c)| ("tile", n, c));
+        entities.chain(items).chain(tiles)
+    }
+}
+
+impl Report {
+    /// Builds the report for a blueprint or book
+    pub fn from(unknown_bp: &Value) -> Report {
+        let mut report = Report {
+            blueprints: vec![],
+            total: BillOfMaterials::default(),
+        };
+
+        common::visit_blueprints(unknown_bp, &mut |path, blueprint| {
+            let materials = BillOfMaterials::from(blueprint);
+            report.total.add(&materials);
+            report.blueprints.push(BlueprintMaterials {
+                path: path.join("/"),
+                materials,
+            });
+        });
+
+        report
+    }
+
+    /// Plain text table, with styled titles for a terminal
+    pub fn to_table(&self, styled: bool) -> String {
+        let mut table = String::new();
+        let mut push_section = |title: String, materials: &BillOfMaterials| {
+            table.push_str(&format!("{}\n", title));
+            for (category, name, count) in materials.rows() {
+                table.push_str(&format!("  {:<8}{:<40}{:>8}\n", category, name, count));
+            }
+        };
+
+        let title = |title: &str| match styled {
+            true => title.green().bold().to_string(),
+            false => title.to_string(),
+        };
+        for blueprint in &self.blueprints {
+            push_section(title(&blueprint.path), &blueprint.materials);
+        }
+        push_section(title("total"), &self.total);
+
+        table
+    }
+
+    /// CSV with one row per count; the grand total rows have the "total" scope and no path
+    pub fn to_csv(&self) -> String {
+        let mut csv = "scope,path,category,name,count\n".to_string();
+        let scoped = self
+            .blueprints
+            .iter()
+            .map(|bp| ("blueprint", bp.path.as_str(), &bp.materials))
+            .chain(std::iter::once(("total", "", &self.total)));
+
+        for (scope, path, materials) in scoped {
+            for (category, name, count) in materials.rows() {
+                csv.push_str(&format!(
+                    "{},{},{},{},{}\n",
+                    scope,
+                    csv_escape(path),
+                    category,
+                    csv_escape(name),
+                    count
+                ));
+            }
+        }
+
+        csv
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl Worker {
+    pub fn from(bom_args: &args::Bom) -> Worker {
+        Worker {
+            source: Source::from(&bom_args.source),
+            format: bom_args.format.unwrap_or_default(),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let blueprint_obj = match self.source.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => {
+                progress::Tracker::new_quiet_sync(CommandType::Bom)
+                    .lock()
+                    .unwrap()
+                    .error_additional(err_msg);
+                exit(1);
+            }
+        };
+
+        let report = Report::from(&blueprint_obj);
+        match self.format {
+            ReportFormat::Table => print!("{}", report.to_table(io::stdout().is_terminal())),
+            ReportFormat::Csv => print!("{}", report.to_csv()),
+            ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn book() -> Value {
+        json!({
+            "blueprint_book": {
+                "label": "factory",
+                "active_index": 0,
+                "version": 1,
+                "blueprints": [
+                    {"index": 0, "blueprint": {
+                        "label": "gears",
+                        "version": 1,
+                        "entities": [
+                            {"entity_number": 1, "name": "assembling-machine-2", "position": {"x": 0, "y": 0},
+                             "items": {"speed-module": 2}},
+                            {"entity_number": 2, "name": "inserter", "position": {"x": 2, "y": 0}},
+                            {"entity_number": 3, "name": "inserter", "position": {"x": 3, "y": 0}}
+                        ],
+                        "tiles": [{"name": "landfill", "position": {"x": 0, "y": 0}}]
+                    }},
+                    {"index": 1, "blueprint": {
+                        "label": "beacons, 2.0",
+                        "version": 1,
+                        "entities": [
+                            {"entity_number": 1, "name": "beacon", "position": {"x": 0, "y": 0},
+                             "items": [{"id": {"name": "speed-module"},
+                                        "items": {"in_inventory": [{"inventory": 1, "stack": 0, "count": 1},
+                                                                   {"inventory": 1, "stack": 1}]}}]}
+                        ]
+                    }}
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn test_report_totals() {
+        let report = Report::from(&book());
+
+        assert_eq!(report.blueprints.len(), 2);
+        assert_eq!(report.blueprints[0].path, "factory/gears");
+        assert_eq!(report.blueprints[0].materials.entities["inserter"], 2);
+        assert_eq!(report.blueprints[1].materials.items["speed-module"], 2);
+
+        assert_eq!(report.total.entities.len(), 3);
+        assert_eq!(report.total.items["speed-module"], 4);
+        assert_eq!(report.total.tiles["landfill"], 1);
+    }
+
+    #[test]
+    fn test_report_csv() {
+        let csv = Report::from(&book()).to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], "scope,path,category,name,count");
+        assert!(lines.contains(&"blueprint,factory/gears,entity,inserter,2"));
+        assert!(lines.contains(&"blueprint,\"factory/beacons, 2.0\",item,speed-module,2"));
+        assert!(lines.contains(&"total,,item,speed-module,4"));
+    }
+
+    #[test]
+    fn test_report_table() {
+        let report = Report::from(&book());
+        let table = report.to_table(false);
+
+        // piped tables carry no escape codes
+        assert!(!table.contains('\x1b'));
+        assert_eq!(table.lines().next(), Some("factory/gears"));
+        assert!(table.lines().any(|line| line == "total"));
+        assert!(report.to_table(true).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_report_json() {
+        let report = serde_json::to_value(Report::from(&book())).unwrap();
+
+        assert_eq!(report["blueprints"][0]["path"], "factory/gears");
+        assert_eq!(report["blueprints"][0]["entities"]["inserter"], 2);
+        assert_eq!(report["total"]["tiles"]["landfill"], 1);
+    }
+}
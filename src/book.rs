@@ -0,0 +1,844 @@
+//! Edits book trees created by import in place.
+//!
+//! Each book directory holds a dotfile whose `order` array lists the book's items
+//! by label and slot index. Every edit keeps the dotfile, the slot index stored in each
+//! item's own file and the book's `active_index` in agreement with the files on disk.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::args::{self, BookSubCommands};
+use crate::common::{self, BlueprintType};
+use crate::factorio_structs::{self, UnknownBlueprintType};
+use crate::progress::{self, CommandType};
+use crate::source::Source;
+use crate::{import, manifest};
+
+pub struct Worker {
+    pub command: BookSubCommands,
+}
+
+/// Book directory created by import, with its dotfile loaded
+#[derive(Debug, Clone)]
+pub struct TreeBook {
+    pub dir: PathBuf,
+    /// Contents of the dotfile
+    dot_file: Value,
+    /// Layout of the dotfile, kept when it is written back
+    style: common::TreeStyle,
+}
+
+impl TreeBook {
+    /// Loads the dotfile of a book directory
+    pub fn open(book_dir: &Path) -> Result<TreeBook, String> {
+        if !book_dir.is_dir() {
+            return Err(format!("{:?}: not a book directory", book_dir));
+        }
+        let dot_file_path = TreeBook::dot_file_path(book_dir)?;
+
+        let (dot_file, style) = match common::json_read_tree(&dot_file_path) {
+            Some(_val) => _val,
+            None => return Err(format!("{:?}: failed to read dotfile", dot_file_path)),
+        };
+        if dot_file
+            .get(factorio_structs::FACTORIO_BP_BOOK_KEY)
+            .is_none_or(|book| !book.is_object())
+        {
+            return Err(format!("{:?}: not a book dotfile", dot_file_path));
+        }
+
+        Ok(TreeBook {
+            dir: book_dir.to_path_buf(),
+            dot_file,
+            style,
+        })
+    }
+
+    /// Writes the dotfile back to disk
+    pub fn save(&mut self) -> Result<(), String> {
+        self.order_mut().sort_by_key(common::slot_index);
+
+        let dot_file_path = TreeBook::dot_file_path(&self.dir)?;
+        match File::create(&dot_file_path)
+            .and_then(|dot_file| self.style.write(dot_file, &self.dot_file))
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("{:?}: error writing to dotfile", dot_file_path)),
+        }
+    }
+
+    /// Path of the dotfile inside a book directory
+    fn dot_file_path(book_dir: &Path) -> Result<PathBuf, String> {
+        match book_dir.file_name().and_then(|name| name.to_str()) {
+            Some(book_name) => Ok(book_dir.join(format!(".{}.json", book_name))),
+            None => Err(format!("{:?}: invalid book directory name", book_dir)),
+        }
+    }
+
+    /// Order entries of the book's items
+    pub fn order(&self) -> &[Value] {
+        self.dot_file[factorio_structs::FACTORIO_BP_BOOK_KEY]
+            .get("order")
+            .and_then(|order| order.as_array())
+            .map(|order| order.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Order entries of the book's items, created if missing
+    pub fn order_mut(&mut self) -> &mut Vec<Value> {
+        let book = self.dot_file[factorio_structs::FACTORIO_BP_BOOK_KEY]
+            .as_object_mut()
+            .unwrap();
+        let order = book.entry("order").or_insert_with(|| Value::Array(vec![]));
+        if !order.is_array() {
+            *order = Value::Array(vec![]);
+        }
+        order.as_array_mut().unwrap()
+    }
+
+    /// Slot index of the book's active item
+    pub fn active_index(&self) -> u64 {
+        self.dot_file[factorio_structs::FACTORIO_BP_BOOK_KEY]["active_index"]
+            .as_u64()
+            .unwrap_or(0)
+    }
+
+    fn set_active_index(&mut self, index: u64) {
+        self.dot_file[factorio_structs::FACTORIO_BP_BOOK_KEY]["active_index"] = index.into();
+    }
+
+    /// Position in the order of the active item, if it exists
+    fn active_position(&self) -> Option<usize> {
+        let active_index = self.active_index();
+        self.order()
+            .iter()
+            .position(|entry| common::slot_index(entry) == Some(active_index))
+    }
+
+    /// Points the active index to an existing item, the first one if the active item is gone
+    fn fix_active_index(&mut self) {
+        if self.active_position().is_none() {
+            let first = self.order().iter().filter_map(common::slot_index).min();
+            self.set_active_index(first.unwrap_or(0));
+        }
+    }
+
+    /// Slot index after the last item of the book
+    pub fn next_index(&self) -> u64 {
+        self.order()
+            .iter()
+            .filter_map(common::slot_index)
+            .max()
+            .map_or(0, |index| index + 1)
+    }
+
+    /// Finds an item by its label or file name
+    pub fn find_label(&self, name: &str) -> Option<usize> {
+        let name = name.strip_suffix(".json").unwrap_or(name);
+        self.order().iter().position(|entry| {
+            entry_label(entry).is_some_and(|label| {
+                label == name || common::file_rename(label.to_string()) == name
+            }) || common::entry_file_name(entry).is_some_and(|file_name| file_name == name)
+        })
+    }
+
+    /// Finds an item by its slot index, label or file name.
+    /// Numeric names are matched against slot indices first
+    pub fn find(&self, name: &str) -> Result<usize, String> {
+        let by_index = name.parse::<u64>().ok().and_then(|index| {
+            self.order()
+                .iter()
+                .position(|entry| common::slot_index(entry) == Some(index))
+        });
+
+        match by_index.or_else(|| self.find_label(name)) {
+            Some(_pos) => Ok(_pos),
+            None => Err(format!("no item \"{}\" in {:?}", name, self.dir)),
+        }
+    }
+
+    /// The book's own settings in the dotfile, such as its label and icons
+    pub fn head(&self) -> &Value {
+        &self.dot_file[factorio_structs::FACTORIO_BP_BOOK_KEY]
+    }
+
+    pub fn head_mut(&mut self) -> &mut Value {
+        &mut self.dot_file[factorio_structs::FACTORIO_BP_BOOK_KEY]
+    }
+
+    /// File or directory of the item an order entry refers to
+    pub fn entry_path(&self, entry: &Value) -> Result<PathBuf, String> {
+        entry_path(&self.dir, entry)
+    }
+
+    /// Sets the slot index of an item, both in the order and in the item's own file
+    fn set_index(&mut self, pos: usize, index: u64) -> Result<(), String> {
+        let entry_path = self.entry_path(&self.order()[pos])?;
+        write_item_index(&entry_path, index)?;
+        self.order_mut()[pos]["index"] = index.into();
+        Ok(())
+    }
+
+    /// Frees the slot index by moving the items at and after it up by one slot,
+    /// if it is taken. The active index follows its item
+    fn make_room(&mut self, index: u64) -> Result<(), String> {
+        let taken = self
+            .order()
+            .iter()
+            .any(|entry| common::slot_index(entry) == Some(index));
+        if !taken {
+            return Ok(());
+        }
+
+        let active_index = self.active_index();
+        for pos in 0..self.order().len() {
+            match common::slot_index(&self.order()[pos]) {
+                Some(_index) if _index >= index => self.set_index(pos, _index + 1)?,
+                _ => (),
+            }
+        }
+        if active_index >= index {
+            self.set_active_index(active_index + 1);
+        }
+
+        Ok(())
+    }
+
+    /// Puts the book back in an earlier state, rewriting the slot index of every item file
+    /// changed since. Undoes the slot shifts of an edit that failed half way
+    fn roll_back(&mut self, before: TreeBook) -> Result<(), String> {
+        for entry in before.order() {
+            let entry_path = before.entry_path(entry)?;
+            let unchanged = self.order().iter().any(|current| {
+                self.entry_path(current).ok().as_ref() == Some(&entry_path)
+                    && common::slot_index(current) == common::slot_index(entry)
+            });
+            if let (false, Some(index)) = (unchanged, common::slot_index(entry)) {
+                write_item_index(&entry_path, index)?;
+            }
+        }
+        *self = before;
+        Ok(())
+    }
+}
+
+/// Label of the item an order entry refers to
+fn entry_label(entry: &Value) -> Option<String> {
+    match BlueprintType::classify(entry) {
+        BlueprintType::Book(label)
+        | BlueprintType::Blueprint(label)
+        | BlueprintType::UpgradePlanner(label)
+        | BlueprintType::DeconPlanner(label) => Some(label),
+        BlueprintType::Invalid => None,
+    }
+}
+
+/// File or directory of the item an order entry refers to, named the same way as import does
+fn entry_path(book_dir: &Path, entry: &Value) -> Result<PathBuf, String> {
+    let mut item_path = book_dir.to_path_buf();
+    let file_name = common::entry_file_name(entry).unwrap_or_default();
+    match BlueprintType::classify(entry) {
+        BlueprintType::Book(_) => item_path.push(file_name),
+        BlueprintType::Blueprint(_)
+        | BlueprintType::UpgradePlanner(_)
+        | BlueprintType::DeconPlanner(_) => {
+            item_path.push(file_name);
+            item_path.set_extension("json");
+        }
+        BlueprintType::Invalid => {
+            return Err(format!("{:?}: invalid entry in book order", book_dir))
+        }
+    }
+    Ok(item_path)
+}
+
+/// Rewrites the slot index stored in an item's file, or a book's dotfile
+fn write_item_index(item_path: &Path, index: u64) -> Result<(), String> {
+    let file_path = match item_path.is_dir() {
+        true => TreeBook::dot_file_path(item_path)?,
+        false => item_path.to_path_buf(),
+    };
+
+    let (mut item, style) = match common::json_read_tree(&file_path) {
+        Some(_val) => _val,
+        None => return Err(format!("{:?}: failed to read item", file_path)),
+    };
+    item["index"] = index.into();
+
+    match File::create(&file_path).and_then(|file| style.write(file, &item)) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("{:?}: error writing item", file_path)),
+    }
+}
+
+/// Splits an item path into its book directory and file name
+fn split_item_path(item_path: &Path) -> Result<(&Path, &str), String> {
+    match (
+        item_path.parent(),
+        item_path.file_name().and_then(|name| name.to_str()),
+    ) {
+        (Some(book_dir), Some(name)) => Ok((book_dir, name)),
+        _ => Err(format!("{:?}: not an item inside a book", item_path)),
+    }
+}
+
+impl Worker {
+    pub fn from(book_args: &BookSubCommands) -> Worker {
+        Worker {
+            command: book_args.clone(),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let prog_tracker = progress::Tracker::new_quiet_sync(CommandType::Book);
+        match self.run() {
+            Ok(ok_msg) => prog_tracker.lock().unwrap().msg(ok_msg),
+            Err(err_msg) => {
+                prog_tracker.lock().unwrap().error_additional(err_msg);
+                exit(1);
+            }
+        }
+    }
+
+    /// Runs the command, then records the books it edited in the manifest of their tree
+    fn run(&self) -> Result<String, String> {
+        let edited_books: Vec<PathBuf> = match &self.command {
+            BookSubCommands::Add(_args) => vec![PathBuf::from(&_args.book)],
+            BookSubCommands::Rm(_args) => {
+                vec![Path::new(&_args.item)
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .to_path_buf()]
+            }
+            BookSubCommands::Mv(_args) => vec![
+                Path::new(&_args.item)
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .to_path_buf(),
+                PathBuf::from(&_args.book),
+            ],
+            BookSubCommands::Reorder(_args) => vec![PathBuf::from(&_args.book)],
+        };
+        let refreshes = edited_books
+            .iter()
+            .map(|book_dir| manifest::Refresh::prepare(book_dir))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let ok_msg = match &self.command {
+            BookSubCommands::Add(_args) => Worker::add(_args),
+            BookSubCommands::Rm(_args) => Worker::rm(Path::new(&_args.item)),
+            BookSubCommands::Mv(_args) => {
+                Worker::mv(Path::new(&_args.item), Path::new(&_args.book), _args.index)
+            }
+            BookSubCommands::Reorder(_args) => {
+                Worker::reorder(Path::new(&_args.book), &_args.items, _args.by_label)
+            }
+        }?;
+        for refresh in refreshes.iter().flatten() {
+            refresh.finish()?;
+        }
+        Ok(ok_msg)
+    }
+
+    /// Adds a blueprint, book or planner to a book, in the given slot or after the last item
+    fn add(add_args: &args::BookAdd) -> Result<String, String> {
+        let mut book = TreeBook::open(Path::new(&add_args.book))?;
+        let mut item = Source::from(&add_args.source).read()?;
+
+        let mut order_entry = match UnknownBlueprintType::deserialize(&item) {
+            Ok(_entry) => _entry,
+            Err(_) => return Err("invalid blueprint!".to_string()),
+        };
+        // the item is laid out like the book unless the flags say otherwise
+        let style = common::TreeStyle {
+            naming: add_args.naming.unwrap_or_default(),
+            format: add_args.file_format.unwrap_or(book.style.format),
+            canonical: add_args.canonical.unwrap_or(book.style.canonical),
+        };
+        match style.naming {
+            common::FileNaming::Renamed => import::Worker::order_entry_rename(&mut order_entry),
+            common::FileNaming::Readable => import::Worker::order_entry_readable(&mut order_entry),
+        }
+        let mut order_entry = serde_json::to_value(order_entry).unwrap();
+        let label = match entry_label(&order_entry) {
+            Some(_label) => _label,
+            None => return Err("invalid blueprint!".to_string()),
+        };
+        if book.entry_path(&order_entry)?.exists() || book.find_label(&label).is_some() {
+            return Err(format!("\"{}\" already exists in {:?}", label, book.dir));
+        }
+
+        let before = book.clone();
+        let index = match add_args.index {
+            Some(_index) => {
+                if let Err(err_msg) = book.make_room(_index) {
+                    let _ = book.roll_back(before);
+                    return Err(err_msg);
+                }
+                _index
+            }
+            None => book.next_index(),
+        };
+        item["index"] = index.into();
+        order_entry["index"] = index.into();
+
+        let prog_tracker = progress::Tracker::new_quiet_sync(CommandType::Book);
+        import::Worker::unknown_write(&prog_tracker, item, &book.dir, style);
+        if prog_tracker.lock().unwrap().errors != 0 {
+            let _ = book.roll_back(before);
+            return Err(format!("error writing \"{}\"", label));
+        }
+
+        book.order_mut().push(order_entry);
+        book.fix_active_index();
+        book.save()?;
+
+        Ok(format!(
+            "added \"{}\" to {:?} at slot {}",
+            label, book.dir, index
+        ))
+    }
+
+    /// Removes an item and its files from its book
+    fn rm(item_path: &Path) -> Result<String, String> {
+        let (book_dir, name) = split_item_path(item_path)?;
+        let mut book = TreeBook::open(book_dir)?;
+        let pos = match book.find_label(name) {
+            Some(_pos) => _pos,
+            None => return Err(format!("{:?}: not an item of {:?}", item_path, book_dir)),
+        };
+
+        let entry_path = book.entry_path(&book.order()[pos])?;
+        let removed = match entry_path.is_dir() {
+            true => fs::remove_dir_all(&entry_path),
+            false => fs::remove_file(&entry_path),
+        };
+        if removed.is_err() {
+            return Err(format!("{:?}: unable to remove", entry_path));
+        }
+
+        book.order_mut().remove(pos);
+        book.fix_active_index();
+        book.save()?;
+
+        Ok(format!("removed {:?}", entry_path))
+    }
+
+    /// Moves an item to another slot of its book, or into another book
+    fn mv(item_path: &Path, to_book_dir: &Path, index: Option<u64>) -> Result<String, String> {
+        let (from_book_dir, name) = split_item_path(item_path)?;
+        let mut from_book = TreeBook::open(from_book_dir)?;
+        let pos = match from_book.find_label(name) {
+            Some(_pos) => _pos,
+            None => {
+                return Err(format!(
+                    "{:?}: not an item of {:?}",
+                    item_path, from_book_dir
+                ))
+            }
+        };
+        let entry_path = from_book.entry_path(&from_book.order()[pos])?;
+
+        let canonical = |path: &Path| match path.canonicalize() {
+            Ok(_path) => Ok(_path),
+            Err(_) => Err(format!("{:?}: no such directory", path)),
+        };
+        let (from_dir, to_dir) = (canonical(from_book_dir)?, canonical(to_book_dir)?);
+
+        // moving inside the same book only changes the slot
+        if from_dir == to_dir {
+            let index = match index {
+                Some(_index) => _index,
+                None => return Err("item is already in this book, give a slot index".to_string()),
+            };
+            let was_active = from_book.active_position() == Some(pos);
+            let before = from_book.clone();
+            let entry = from_book.order_mut().remove(pos);
+            let moved = from_book.make_room(index).and_then(|_| {
+                from_book.order_mut().push(entry);
+                let new_pos = from_book.order().len() - 1;
+                from_book.set_index(new_pos, index)
+            });
+            if let Err(err_msg) = moved {
+                let _ = from_book.roll_back(before);
+                return Err(err_msg);
+            }
+            if was_active {
+                from_book.set_active_index(index);
+            }
+            from_book.fix_active_index();
+            from_book.save()?;
+
+            return Ok(format!("moved {:?} to slot {}", entry_path, index));
+        }
+
+        if to_dir.starts_with(canonical(&entry_path)?) {
+            return Err("a book cannot be moved into itself".to_string());
+        }
+
+        let mut to_book = TreeBook::open(to_book_dir)?;
+        let mut entry = from_book.order_mut().remove(pos);
+        let label = entry_label(&entry).unwrap_or_default();
+        let new_path = to_book.entry_path(&entry)?;
+        if new_path.exists() || to_book.find_label(&label).is_some() {
+            return Err(format!("\"{}\" already exists in {:?}", label, to_book.dir));
+        }
+
+        // moved before any slot index changes, a failed move leaves both books untouched
+        if fs::rename(&entry_path, &new_path).is_err() {
+            return Err(format!("{:?}: unable to move", entry_path));
+        }
+
+        let before = to_book.clone();
+        let moved = match index {
+            Some(_index) => to_book.make_room(_index).map(|_| _index),
+            None => Ok(to_book.next_index()),
+        }
+        .and_then(|index| write_item_index(&new_path, index).map(|_| index));
+        let index = match moved {
+            Ok(_index) => _index,
+            Err(err_msg) => {
+                // the dotfiles are not written yet, only the files need to be put back
+                let _ = to_book.roll_back(before);
+                let _ = fs::rename(&new_path, &entry_path);
+                return Err(err_msg);
+            }
+        };
+        entry["index"] = index.into();
+        to_book.order_mut().push(entry);
+
+        // dotfiles last, once every item file is in place
+        from_book.fix_active_index();
+        to_book.fix_active_index();
+        from_book.save()?;
+        to_book.save()?;
+
+        Ok(format!(
+            "moved {:?} to {:?} at slot {}",
+            entry_path, new_path, index
+        ))
+    }
+
+    /// Numbers the items of a book from slot 0 without gaps.
+    /// The given items come first, the others keep their order or are sorted by label
+    fn reorder(book_dir: &Path, items: &[String], by_label: bool) -> Result<String, String> {
+        let mut book = TreeBook::open(book_dir)?;
+
+        let mut positions: Vec<usize> = vec![];
+        for item in items {
+            let pos = book.find(item)?;
+            if positions.contains(&pos) {
+                return Err(format!("\"{}\" given more than once", item));
+            }
+            positions.push(pos);
+        }
+
+        let mut rest: Vec<usize> = (0..book.order().len())
+            .filter(|pos| !positions.contains(pos))
+            .collect();
+        if by_label {
+            rest.sort_by_key(|pos| entry_label(&book.order()[*pos]).unwrap_or_default());
+        } else {
+            rest.sort_by_key(|pos| common::slot_index(&book.order()[*pos]));
+        }
+        positions.extend(rest);
+
+        let active_pos = book.active_position();
+        let before = book.clone();
+        for (index, pos) in positions.iter().enumerate() {
+            if let Err(err_msg) = book.set_index(*pos, index as u64) {
+                let _ = book.roll_back(before);
+                return Err(err_msg);
+            }
+            if active_pos == Some(*pos) {
+                book.set_active_index(index as u64);
+            }
+        }
+        book.fix_active_index();
+        book.save()?;
+
+        Ok(format!(
+            "reordered {} items in {:?}",
+            positions.len(),
+            book.dir
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    /// Imports a test book into a fresh temporary directory, returning the directory
+    /// and the book directory inside it
+    fn import_book() -> (common::TestDir, PathBuf) {
+        let dest = common::TestDir::new("book");
+
+        let book = json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "label": "outer",
+                "active_index": 1,
+                "version": 1,
+                "blueprints": [
+                    {"index": 0, "blueprint": {"item": "blueprint", "label": "a", "version": 1}},
+                    {"index": 1, "blueprint": {"item": "blueprint", "label": "b", "version": 1}},
+                    {"index": 4, "blueprint": {"item": "blueprint", "label": "c", "version": 1}},
+                    {"index": 5, "blueprint_book": {
+                        "item": "blueprint-book",
+                        "label": "inner",
+                        "active_index": 0,
+                        "version": 1,
+                        "blueprints": [
+                            {"index": 0, "blueprint": {"item": "blueprint", "label": "d", "version": 1}}
+                        ]
+                    }}
+                ]
+            }
+        });
+        let tracker = progress::Tracker::new_quiet_sync(CommandType::Book);
+        import::Worker::unknown_write(&tracker, book, &dest, common::TreeStyle::default());
+        let book_dir = dest.join("outer");
+        (dest, book_dir)
+    }
+
+    /// Labels and slot indices of the order, checked against the index in each item's file
+    fn slots(book_dir: &Path) -> Vec<(String, u64)> {
+        let book = TreeBook::open(book_dir).unwrap();
+        book.order()
+            .iter()
+            .map(|entry| {
+                let index = common::slot_index(entry).unwrap();
+                let path = book.entry_path(entry).unwrap();
+                let file_path = match path.is_dir() {
+                    true => TreeBook::dot_file_path(&path).unwrap(),
+                    false => path,
+                };
+                let item: Value =
+                    serde_json::from_str(&fs::read_to_string(file_path).unwrap()).unwrap();
+                assert_eq!(item["index"], index);
+                (entry_label(entry).unwrap(), index)
+            })
+            .collect()
+    }
+
+    fn labels_and_slots(expected: &[(&str, u64)]) -> Vec<(String, u64)> {
+        expected
+            .iter()
+            .map(|(label, index)| (label.to_string(), *index))
+            .collect()
+    }
+
+    #[test]
+    fn test_add_into_taken_slot() {
+        let (_dest, book_dir) = import_book();
+        let bp_string = common::factorio_deflate(
+            &json!({"blueprint": {"item": "blueprint", "label": "new bp", "version": 1}}),
+            common::CompressionLevel::Default,
+        )
+        .unwrap();
+
+        let add_args = args::BookAdd {
+            book: book_dir.to_string_lossy().to_string(),
+            source: bp_string,
+            index: Some(1),
+            naming: None,
+            file_format: None,
+            canonical: None,
+        };
+        Worker::add(&add_args).unwrap();
+
+        assert_eq!(
+            slots(&book_dir),
+            labels_and_slots(&[("a", 0), ("new_bp", 1), ("b", 2), ("c", 5), ("inner", 6)])
+        );
+        // the active item moved along with its slot
+        assert_eq!(TreeBook::open(&book_dir).unwrap().active_index(), 2);
+        // adding the same label twice is refused
+        assert!(Worker::add(&add_args).is_err());
+    }
+
+    #[test]
+    fn test_add_with_readable_names() {
+        let (_dest, book_dir) = import_book();
+        let bp_string = common::factorio_deflate(
+            &json!({"blueprint": {"item": "blueprint", "label": "[item=iron-plate] plates", "version": 1}}),
+            common::CompressionLevel::Default,
+        )
+        .unwrap();
+
+        let add_args = args::BookAdd {
+            book: book_dir.to_string_lossy().to_string(),
+            source: bp_string,
+            index: None,
+            naming: Some(common::FileNaming::Readable),
+            file_format: None,
+            canonical: None,
+        };
+        Worker::add(&add_args).unwrap();
+
+        assert!(book_dir.join("iron-plate plates.json").is_file());
+        let exported = Source::Tree(book_dir.clone()).read().unwrap();
+        assert_eq!(
+            exported["blueprint_book"]["blueprints"][4]["blueprint"]["label"],
+            "[item=iron-plate] plates"
+        );
+    }
+
+    #[test]
+    fn test_rm_and_mv_between_books() {
+        let (_dest, book_dir) = import_book();
+
+        Worker::rm(&book_dir.join("b.json")).unwrap();
+        assert!(!book_dir.join("b.json").exists());
+        // the active item was removed, the first item becomes active
+        assert_eq!(TreeBook::open(&book_dir).unwrap().active_index(), 0);
+
+        Worker::mv(&book_dir.join("c.json"), &book_dir.join("inner"), Some(0)).unwrap();
+        assert!(book_dir.join("inner").join("c.json").is_file());
+        assert_eq!(
+            slots(&book_dir),
+            labels_and_slots(&[("a", 0), ("inner", 5)])
+        );
+        assert_eq!(
+            slots(&book_dir.join("inner")),
+            labels_and_slots(&[("c", 0), ("d", 1)])
+        );
+
+        assert!(Worker::mv(&book_dir.join("inner"), &book_dir.join("inner"), None).is_err());
+
+        let exported = Source::Tree(book_dir.clone()).read().unwrap();
+        assert_eq!(
+            exported["blueprint_book"]["blueprints"][1]["blueprint_book"]["blueprints"][0]
+                ["blueprint"]["label"],
+            "c"
+        );
+    }
+
+    #[test]
+    fn test_failed_mv_rolls_back() {
+        let (_dest, book_dir) = import_book();
+        let inner_dir = book_dir.join("inner");
+        let bp_string = common::factorio_deflate(
+            &json!({"blueprint": {"item": "blueprint", "label": "e", "version": 1}}),
+            common::CompressionLevel::Default,
+        )
+        .unwrap();
+        let add_args = args::BookAdd {
+            book: inner_dir.to_string_lossy().to_string(),
+            source: bp_string,
+            index: None,
+            naming: None,
+            file_format: None,
+            canonical: None,
+        };
+        Worker::add(&add_args).unwrap();
+        let inner_dot_file = fs::read_to_string(inner_dir.join(".inner.json")).unwrap();
+
+        // "d" moves up a slot before "e" fails to be read
+        fs::write(inner_dir.join("e.json"), "not json").unwrap();
+        assert!(Worker::mv(&book_dir.join("a.json"), &inner_dir, Some(0)).is_err());
+
+        assert!(book_dir.join("a.json").is_file());
+        assert!(!inner_dir.join("a.json").exists());
+        assert_eq!(
+            slots(&book_dir),
+            labels_and_slots(&[("a", 0), ("b", 1), ("c", 4), ("inner", 5)])
+        );
+        let d_item: Value =
+            serde_json::from_str(&fs::read_to_string(inner_dir.join("d.json")).unwrap()).unwrap();
+        assert_eq!(d_item["index"], 0);
+        assert_eq!(
+            fs::read_to_string(inner_dir.join(".inner.json")).unwrap(),
+            inner_dot_file
+        );
+    }
+
+    #[test]
+    fn test_failed_reorder_rolls_back() {
+        let (_dest, book_dir) = import_book();
+        let dot_file = fs::read_to_string(book_dir.join(".outer.json")).unwrap();
+
+        // "c" and "a" are renumbered before "b" fails to be read
+        fs::write(book_dir.join("b.json"), "not json").unwrap();
+        assert!(Worker::reorder(&book_dir, &["c".to_string()], false).is_err());
+
+        let item_index = |file_name: &str| -> Value {
+            let item: Value =
+                serde_json::from_str(&fs::read_to_string(book_dir.join(file_name)).unwrap())
+                    .unwrap();
+            item["index"].clone()
+        };
+        assert_eq!(item_index("a.json"), 0);
+        assert_eq!(item_index("c.json"), 4);
+        assert_eq!(
+            fs::read_to_string(book_dir.join(".outer.json")).unwrap(),
+            dot_file
+        );
+    }
+
+    #[test]
+    fn test_mv_within_book_and_reorder() {
+        let (_dest, book_dir) = import_book();
+
+        Worker::mv(&book_dir.join("inner"), &book_dir, Some(0)).unwrap();
+        assert_eq!(
+            slots(&book_dir),
+            labels_and_slots(&[("inner", 0), ("a", 1), ("b", 2), ("c", 5)])
+        );
+
+        Worker::reorder(&book_dir, &["c".to_string()], false).unwrap();
+        assert_eq!(
+            slots(&book_dir),
+            labels_and_slots(&[("c", 0), ("inner", 1), ("a", 2), ("b", 3)])
+        );
+        assert_eq!(TreeBook::open(&book_dir).unwrap().active_index(), 3);
+
+        Worker::reorder(&book_dir, &[], true).unwrap();
+        assert_eq!(
+            slots(&book_dir),
+            labels_and_slots(&[("a", 0), ("b", 1), ("c", 2), ("inner", 3)])
+        );
+    }
+
+    #[test]
+    fn test_mv_keeps_manifest_current() {
+        let (dest, book_dir) = import_book();
+        manifest::update(&dest, "outer").unwrap();
+        let changes = || {
+            let manifest = manifest::Manifest::read(&dest).unwrap().unwrap();
+            manifest.changes(&manifest::Manifest::build(&dest).unwrap())
+        };
+        let mv = |item: &str, book: &Path, index: u64| {
+            let worker = Worker::from(&BookSubCommands::Mv(args::BookMv {
+                item: book_dir.join(item).to_string_lossy().to_string(),
+                book: book.to_string_lossy().to_string(),
+                index: Some(index),
+            }));
+            worker.run().unwrap();
+        };
+
+        mv("a.json", &book_dir.join("inner"), 0);
+        assert!(changes().is_empty());
+        let manifest = manifest::Manifest::read(&dest).unwrap().unwrap();
+        assert_eq!(manifest.files["outer/inner/a.json"].slot, "5/0");
+        assert_eq!(manifest.files["outer/inner/d.json"].slot, "5/1");
+
+        // an edit made by hand is still reported after the book is edited around it
+        let c_path = book_dir.join("c.json");
+        let edited = fs::read_to_string(&c_path)
+            .unwrap()
+            .replace("\"c\"", "\"edited\"");
+        fs::write(&c_path, edited).unwrap();
+        mv("b.json", &book_dir, 9);
+        let changes = changes();
+        assert_eq!(changes.modified, vec!["outer/c.json"]);
+        assert!(changes.deleted.is_empty() && changes.added.is_empty());
+    }
+}
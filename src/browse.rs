@@ -0,0 +1,415 @@
+//! Browses a book in the terminal like a file manager.
+//!
+//! The items of the open book are listed on the left, the selected item's details and a
+//! preview on the right. Any item can be copied to the clipboard as a blueprint string.
+
+use std::io::{self, Write};
+use std::process::exit;
+
+use crossterm::event::{self, Event, KeyCode};
+use crossterm::style::{self, Stylize};
+use crossterm::{cursor, terminal, QueueableCommand};
+use serde_json::Value;
+
+use crate::args;
+use crate::common::{self, BlueprintType, CompressionLevel};
+use crate::destination::Destination;
+use crate::factorio_structs;
+use crate::info::BlueprintSummary;
+use crate::progress::{self, CommandType, Screen};
+use crate::show::{Grid, View};
+use crate::source::Source;
+
+/// Widest the list of items gets, in columns
+const LIST_WIDTH: u16 = 40;
+
+pub struct Worker {
+    pub source: Source,
+    pub compression: CompressionLevel,
+}
+
+/// Position in a book and the selected item
+pub struct Browser {
+    root: Value,
+    /// Positions of the opened books, starting from the list holding only the root
+    path: Vec<usize>,
+    /// Position of the selected item in the open book
+    selected: usize,
+}
+
+impl Browser {
+    pub fn new(root: Value) -> Browser {
+        Browser {
+            root,
+            path: vec![],
+            selected: 0,
+        }
+    }
+
+    /// Items of the open book in slot order. The top level lists the root itself
+    pub fn items(&self) -> Vec<&Value> {
+        let mut items = vec![&self.root];
+        for pos in &self.path {
+            items = common::sorted_book_children(items[*pos]);
+        }
+        items
+    }
+
+    pub fn selected(&self) -> Option<&Value> {
+        self.items().get(self.selected).copied()
+    }
+
+    pub fn selected_pos(&self) -> usize {
+        self.selected
+    }
+
+    pub fn down(&mut self) {
+        if self.selected + 1 < self.items().len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Opens the selected item if it is a book
+    pub fn open(&mut self) {
+        if let Some(BlueprintType::Book(_)) = self.selected().map(BlueprintType::classify) {
+            self.path.push(self.selected);
+            self.selected = 0;
+        }
+    }
+
+    /// Goes back to the enclosing book, selecting the book that was open
+    pub fn back(&mut self) {
+        if let Some(pos) = self.path.pop() {
+            self.selected = pos;
+        }
+    }
+
+    /// Labels of the open books, separated by "/"
+    pub fn breadcrumbs(&self) -> String {
+        let mut labels = vec![];
+        let mut items = vec![&self.root];
+        for pos in &self.path {
+            labels.push(label(items[*pos]));
+            items = common::sorted_book_children(items[*pos]);
+        }
+        format!("/{}", labels.join("/"))
+    }
+
+    /// The selected item on its own, without its slot in the book
+    pub fn selected_node(&self) -> Option<Value> {
+        let mut node = self.selected()?.clone();
+        if let Some(head) = node.as_object_mut() {
+            head.remove("index");
+        }
+        Some(node)
+    }
+}
+
+fn label(unknown_bp: &Value) -> String {
+    match BlueprintType::classify(unknown_bp) {
+        BlueprintType::Invalid => "invalid".to_string(),
+        BlueprintType::Book(label)
+        | BlueprintType::Blueprint(label)
+        | BlueprintType::UpgradePlanner(label)
+        | BlueprintType::DeconPlanner(label) => label,
+    }
+}
+
+/// Line of an item in the list: slot index, label, and a "/" after books
+pub fn list_line(unknown_bp: &Value) -> String {
+    let slot = match common::slot_index(unknown_bp) {
+        Some(index) => format!("{:>3} ", index),
+        None => "    ".to_string(),
+    };
+    match BlueprintType::classify(unknown_bp) {
+        BlueprintType::Book(label) => format!("{}{}/", slot, label),
+        _ => format!("{}{}", slot, label(unknown_bp)),
+    }
+}
+
+/// Details of an item: type, label, version, contents, icons and description
+pub fn metadata(unknown_bp: &Value) -> Vec<String> {
+    let bp_type = BlueprintType::classify(unknown_bp);
+    let inner = bp_type
+        .key()
+        .and_then(|key| unknown_bp.get(key))
+        .unwrap_or(&Value::Null);
+    let type_name = match bp_type {
+        BlueprintType::Invalid => "invalid",
+        BlueprintType::Book(_) => "book",
+        BlueprintType::Blueprint(_) => "blueprint",
+        BlueprintType::UpgradePlanner(_) => "upgrade planner",
+        BlueprintType::DeconPlanner(_) => "deconstruction planner",
+    };
+
+    let mut lines = vec![format!("{}  {}", type_name, label(unknown_bp))];
+    if let Some(version) = inner.get("version").and_then(|version| version.as_u64()) {
+        lines.push(format!("version   {}", common::version_string(version)));
+    }
+    match bp_type {
+        BlueprintType::Book(_) => {
+            lines.push(format!(
+                "items     {}",
+                common::sorted_book_children(unknown_bp).len()
+            ));
+        }
+        BlueprintType::Blueprint(_) => {
+            let summary = BlueprintSummary::from(inner);
+            lines.push(format!("entities  {}", summary.entities));
+            lines.push(format!("tiles     {}", summary.tiles));
+            if let Some(bounding_box) = summary.bounding_box {
+                lines.push(format!(
+                    "size      {}x{}",
+                    bounding_box.width(),
+                    bounding_box.height()
+                ));
+            }
+        }
+        _ => (),
+    }
+
+    let icons: Vec<&str> = inner
+        .get("icons")
+        .and_then(|icons| icons.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|icon| icon.pointer("/signal/name").and_then(|name| name.as_str()))
+        .collect();
+    if !icons.is_empty() {
+        lines.push(format!("icons     {}", icons.join(", ")));
+    }
+
+    if let Some(description) = inner.get("description").and_then(|d| d.as_str()) {
+        lines.push(String::new());
+        lines.extend(description.lines().map(String::from));
+    }
+    lines
+}
+
+/// Shortens a line to a number of columns
+fn fit(line: &str, cols: usize) -> String {
+    line.chars().take(cols).collect()
+}
+
+impl Worker {
+    pub fn from(browse_args: &args::Browse) -> Worker {
+        Worker {
+            source: Source::from(&browse_args.source),
+            compression: browse_args.compression.unwrap_or_default(),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let browse_error = |err_msg: String| -> ! {
+            progress::Tracker::new_quiet_sync(CommandType::Browse)
+                .lock()
+                .unwrap()
+                .error_additional(err_msg);
+            exit(1);
+        };
+
+        let blueprint_obj = match self.source.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => browse_error(err_msg),
+        };
+        if let BlueprintType::Invalid = BlueprintType::classify(&blueprint_obj) {
+            browse_error("invalid blueprint!".to_string());
+        }
+
+        let mut browser = Browser::new(blueprint_obj);
+        if let Err(err) = self.interactive(&mut browser) {
+            browse_error(format!("terminal error: {}", err));
+        }
+    }
+
+    /// Shows the browser until the user quits
+    fn interactive(&self, browser: &mut Browser) -> io::Result<()> {
+        let mut screen = Screen::enter()?;
+        let mut message = String::new();
+
+        loop {
+            Worker::draw(screen.out(), browser, &message)?;
+            message.clear();
+
+            if let Event::Key(key) = event::read()? {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Down | KeyCode::Char('j') => browser.down(),
+                    KeyCode::Up | KeyCode::Char('k') => browser.up(),
+                    KeyCode::Right | KeyCode::Enter | KeyCode::Char('l') => browser.open(),
+                    KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => browser.back(),
+                    KeyCode::Char('c') | KeyCode::Char('y') => {
+                        message = match browser.selected_node() {
+                            Some(node) => {
+                                let copied = label(&node);
+                                match Destination::Clipboard.write(node, self.compression) {
+                                    Ok(_) => format!("copied \"{}\" to the clipboard", copied),
+                                    Err(err_msg) => err_msg,
+                                }
+                            }
+                            None => "nothing selected".to_string(),
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws the list, details and preview of the selected item, and a status line
+    fn draw<W: Write>(out: &mut W, browser: &Browser, message: &str) -> io::Result<()> {
+        let (cols, rows) = Screen::size();
+        let list_width = LIST_WIDTH.min(cols / 3);
+        let body_rows = rows.saturating_sub(2);
+        let (detail_left, detail_cols) = (list_width + 2, cols.saturating_sub(list_width + 2));
+
+        out.queue(terminal::Clear(terminal::ClearType::All))?;
+        out.queue(cursor::MoveTo(0, 0))?;
+        out.queue(style::PrintStyledContent(
+            fit(&browser.breadcrumbs(), cols as usize).bold(),
+        ))?;
+
+        // the list scrolls to keep the selected item in view
+        let items = browser.items();
+        let first = (browser.selected_pos() + 1).saturating_sub(body_rows as usize);
+        for (row, (pos, item)) in items
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(body_rows as usize)
+            .enumerate()
+        {
+            out.queue(cursor::MoveTo(0, row as u16 + 1))?;
+            let line = format!(
+                "{:width$}",
+                fit(&list_line(item), list_width as usize),
+                width = list_width as usize
+            );
+            match pos == browser.selected_pos() {
+                true => out.queue(style::PrintStyledContent(line.reverse()))?,
+                false => out.queue(style::Print(line))?,
+            };
+        }
+        for row in 1..=body_rows {
+            out.queue(cursor::MoveTo(list_width, row))?;
+            out.queue(style::PrintStyledContent("│".dim()))?;
+        }
+
+        if let Some(selected) = browser.selected() {
+            let details = metadata(selected);
+            for (row, line) in details.iter().take(body_rows as usize).enumerate() {
+                out.queue(cursor::MoveTo(detail_left, row as u16 + 1))?;
+                let line = fit(line, detail_cols as usize);
+                match row {
+                    0 => out.queue(style::PrintStyledContent(line.green().bold()))?,
+                    _ => out.queue(style::Print(line))?,
+                };
+            }
+
+            // the preview fills the space below the details
+            let preview_top = details.len() as u16 + 2;
+            if let Some(blueprint) = selected.get(factorio_structs::FACTORIO_BP_KEY) {
+                if preview_top < body_rows {
+                    let preview_rows = body_rows + 1 - preview_top;
+                    let grid = Grid::from(blueprint);
+                    let view = View {
+                        x: 0,
+                        y: 0,
+                        zoom: grid.fit_zoom(detail_cols as usize, preview_rows as usize),
+                    };
+                    view.queue(
+                        out,
+                        &grid,
+                        (detail_left, preview_top, detail_cols, preview_rows),
+                    )?;
+                }
+            }
+        }
+
+        out.queue(cursor::MoveTo(0, rows.saturating_sub(1)))?;
+        let status = match message.is_empty() {
+            true => "↑↓ select  → open  ← back  c copy string  q quit",
+            false => message,
+        };
+        out.queue(style::PrintStyledContent(
+            fit(status, cols as usize).reverse(),
+        ))?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn book() -> Value {
+        json!({"blueprint_book": {"item": "blueprint-book", "label": "Top", "active_index": 0,
+        "version": 281479275675648u64, "blueprints": [
+            {"index": 1, "blueprint": {"item": "blueprint", "label": "Second", "version": 1}},
+            {"index": 0, "blueprint_book": {"item": "blueprint-book", "label": "Sub",
+                "active_index": 0, "version": 1, "blueprints": [
+                    {"index": 0, "blueprint": {"item": "blueprint", "label": "Deep",
+                     "version": 1, "description": "first line\nsecond line",
+                     "icons": [{"index": 1, "signal": {"type": "item", "name": "pipe"}}],
+                     "entities": [{"entity_number": 1, "name": "pipe",
+                                   "position": {"x": 0.5, "y": 0.5}}]}}
+                ]}}
+        ]}})
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut browser = Browser::new(book());
+        assert_eq!(browser.items().len(), 1);
+        assert_eq!(browser.breadcrumbs(), "/");
+
+        browser.open();
+        assert_eq!(browser.breadcrumbs(), "/Top");
+        let lines: Vec<String> = browser.items().into_iter().map(list_line).collect();
+        assert_eq!(lines, ["  0 Sub/", "  1 Second"]);
+
+        // blueprints do not open
+        browser.down();
+        browser.down();
+        browser.open();
+        assert_eq!(browser.breadcrumbs(), "/Top");
+
+        browser.up();
+        browser.open();
+        assert_eq!(browser.breadcrumbs(), "/Top/Sub");
+        assert_eq!(label(browser.selected().unwrap()), "Deep");
+
+        browser.back();
+        assert_eq!(browser.selected_pos(), 0);
+        let node = browser.selected_node().unwrap();
+        assert!(node.get("index").is_none());
+        assert_eq!(label(&node), "Sub");
+    }
+
+    #[test]
+    fn test_metadata() {
+        let deep = &book()["blueprint_book"]["blueprints"][1]["blueprint_book"]["blueprints"][0];
+        assert_eq!(
+            metadata(deep),
+            [
+                "blueprint  Deep",
+                "version   0.0.0",
+                "entities  1",
+                "tiles     0",
+                "size      1x1",
+                "icons     pipe",
+                "",
+                "first line",
+                "second line"
+            ]
+        );
+        assert_eq!(metadata(&book())[2], "items     2");
+    }
+}
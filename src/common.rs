@@ -1,5 +1,13 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
 use base64::Engine;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use unicode_normalization::UnicodeNormalization;
 
 use crate::factorio_structs;
 
@@ -16,18 +24,14 @@ pub enum BlueprintType {
 }
 
 impl BlueprintType {
-    /// Determines the blueprint type, returning an enum with the enclosing blueprint's name
+    /// Determines the blueprint type, returning an enum with the enclosing blueprint's name.
+    /// The value is only borrowed: child blueprints and entities are skipped, not copied.
     pub fn classify(given_bp: &Value) -> BlueprintType {
-        let unknown_bp_type: factorio_structs::UnknownBlueprintType;
-        match serde_json::from_value(given_bp.clone()) {
-            Ok(_val) => {
-                unknown_bp_type = _val;
-            }
-            Err(e) => {
-                println!("Error: {}", e);
-                return BlueprintType::Invalid;
-            }
-        }
+        // callers report invalid values, printing here would corrupt their output
+        let unknown_bp_type = match factorio_structs::UnknownBlueprintType::deserialize(given_bp) {
+            Ok(_val) => _val,
+            Err(_) => return BlueprintType::Invalid,
+        };
 
         if let Some(_book) = unknown_bp_type.blueprint_book {
             return BlueprintType::Book(_book.label);
@@ -45,51 +49,450 @@ impl BlueprintType {
             return BlueprintType::UpgradePlanner(_upgrade.label);
         }
 
-        return BlueprintType::Invalid;
+        BlueprintType::Invalid
+    }
+
+    /// Key of the object enclosed in the blueprint JSON
+    pub fn key(&self) -> Option<&'static str> {
+        match self {
+            BlueprintType::Invalid => None,
+            BlueprintType::Book(_) => Some(factorio_structs::FACTORIO_BP_BOOK_KEY),
+            BlueprintType::Blueprint(_) => Some(factorio_structs::FACTORIO_BP_KEY),
+            BlueprintType::UpgradePlanner(_) => Some(factorio_structs::FACTORIO_UP_PLANNER_KEY),
+            BlueprintType::DeconPlanner(_) => Some(factorio_structs::FACTORIO_DECON_PLANNER_KEY),
+        }
     }
 }
 
-/// Inflate the blueprint string according to factorio spec
-pub fn factorio_inflate(bp_string: &str) -> Result<String, &str> {
-    // skip first byte, then base64 decode
-    let base64_engine = base64::engine::general_purpose::STANDARD;
-    let decoded = base64_engine.decode(bp_string[1..].as_bytes());
+/// Returns the child blueprints/books/planners of a book, or None if the value is not a book
+pub fn book_children(bp_book: &Value) -> Option<&Vec<Value>> {
+    bp_book
+        .get(factorio_structs::FACTORIO_BP_BOOK_KEY)
+        .and_then(|book| book.get("blueprints"))
+        .and_then(|blueprints| blueprints.as_array())
+}
 
-    let pre_inflate;
-    match decoded {
-        Err(_) => return Err(&"Base64 decode error!"),
-        _ => {
-            pre_inflate = decoded.unwrap();
+/// Children of a book in slot order, empty if the value is not a book
+pub fn sorted_book_children(bp_book: &Value) -> Vec<&Value> {
+    let mut children: Vec<&Value> = book_children(bp_book).into_iter().flatten().collect();
+    children.sort_by_key(|child| slot_index(child));
+    children
+}
+
+/// Detaches the children of a book, sorted by slot index.
+/// Also returns the position of the book's active item among them
+pub fn take_book_children(bp_book: &mut Value) -> (Vec<Value>, Option<usize>) {
+    let mut children = match bp_book
+        .get_mut(factorio_structs::FACTORIO_BP_BOOK_KEY)
+        .and_then(|book| book.get_mut("blueprints"))
+        .map(Value::take)
+    {
+        Some(Value::Array(_children)) => _children,
+        _ => vec![],
+    };
+    children.sort_by_key(slot_index);
+
+    let active_index = bp_book[factorio_structs::FACTORIO_BP_BOOK_KEY]["active_index"].as_u64();
+    let active_pos = children
+        .iter()
+        .position(|child| slot_index(child) == active_index);
+    (children, active_pos)
+}
+
+/// Attaches the children to a book, numbering their slots from 0
+pub fn set_book_children(bp_book: &mut Value, mut children: Vec<Value>, active_pos: Option<usize>) {
+    for (index, child) in children.iter_mut().enumerate() {
+        child["index"] = index.into();
+    }
+
+    let book = &mut bp_book[factorio_structs::FACTORIO_BP_BOOK_KEY];
+    book["active_index"] = active_pos.unwrap_or(0).into();
+    book["blueprints"] = Value::Array(children);
+}
+
+/// Calls `visit` on every blueprint inside the value (a blueprint or book), depth first in slot order.
+/// The visitor receives the labels of the enclosing books and the blueprint itself,
+/// along with the blueprint object (the value under the "blueprint" key).
+pub fn visit_blueprints<'a, F>(unknown_bp: &'a Value, visit: &mut F)
+where
+    F: FnMut(&[String], &'a Value),
+{
+    fn visit_recursive<'a, F>(unknown_bp: &'a Value, path: &mut Vec<String>, visit: &mut F)
+    where
+        F: FnMut(&[String], &'a Value),
+    {
+        match BlueprintType::classify(unknown_bp) {
+            BlueprintType::Blueprint(label) => {
+                path.push(label);
+                visit(path, &unknown_bp[factorio_structs::FACTORIO_BP_KEY]);
+                path.pop();
+            }
+            BlueprintType::Book(label) => {
+                let mut children: Vec<&Value> =
+                    book_children(unknown_bp).into_iter().flatten().collect();
+                children.sort_by_key(|child| slot_index(child));
+
+                path.push(label);
+                for child in children {
+                    visit_recursive(child, path, visit);
+                }
+                path.pop();
+            }
+            _ => (),
         }
     }
 
-    let inflated = inflate::inflate_bytes_zlib(&pre_inflate);
+    visit_recursive(unknown_bp, &mut vec![], visit);
+}
+
+/// Calls `visit` on every blueprint object inside the value (a blueprint or book),
+/// allowing the blueprints to be modified in place. Blueprints are visited in storage order
+pub fn visit_blueprints_mut<F>(unknown_bp: &mut Value, visit: &mut F)
+where
+    F: FnMut(&mut Value),
+{
+    if let Some(blueprint) = unknown_bp.get_mut(factorio_structs::FACTORIO_BP_KEY) {
+        visit(blueprint);
+    }
 
-    match inflated {
-        Err(_) => {
-            return Err(&"zlib inflate error!");
+    if let Some(children) = unknown_bp
+        .get_mut(factorio_structs::FACTORIO_BP_BOOK_KEY)
+        .and_then(|book| book.get_mut("blueprints"))
+        .and_then(|children| children.as_array_mut())
+    {
+        for child in children {
+            visit_blueprints_mut(child, visit);
         }
-        _ => (),
     }
+}
 
-    return Ok(std::str::from_utf8(&inflated.unwrap()).unwrap().to_string());
+/// Returns the slot index of an item inside a book
+pub fn slot_index(unknown_bp: &Value) -> Option<u64> {
+    unknown_bp.get("index").and_then(|index| index.as_u64())
 }
 
-/// Deflate the blueprint string according to factorio spec
-pub fn factorio_deflate(bp_string_json: &str) -> String {
-    // compress string
-    let deflated = deflate::deflate_bytes_zlib(bp_string_json.as_bytes());
+/// Array under a key of an object, empty if there is none
+pub fn array<'a>(object: &'a Value, key: &str) -> &'a [Value] {
+    object
+        .get(key)
+        .and_then(|values| values.as_array())
+        .map(|values| values.as_slice())
+        .unwrap_or_default()
+}
+
+/// Position of an entity or tile
+pub fn position(object: &Value) -> Option<(f64, f64)> {
+    let position = object.get("position")?;
+    Some((position.get("x")?.as_f64()?, position.get("y")?.as_f64()?))
+}
+
+/// Whether a blueprint, book or planner object was made by game version 2.0 or later
+pub fn is_version_2(object: &Value) -> bool {
+    object
+        .get("version")
+        .and_then(|version| version.as_u64())
+        .is_some_and(|version| version >> 48 >= 2)
+}
+
+/// Number of directions in the game version of a blueprint object
+pub fn direction_count(blueprint: &Value) -> u64 {
+    // 2.0 doubled the number of directions
+    match is_version_2(blueprint) {
+        true => 16,
+        false => 8,
+    }
+}
+
+/// Formats the version number of a blueprint as the game displays it.
+/// The version packs four 16-bit numbers: major, minor, patch and build.
+pub fn version_string(version: u64) -> String {
+    format!(
+        "{}.{}.{}",
+        version >> 48,
+        (version >> 32) & 0xffff,
+        (version >> 16) & 0xffff
+    )
+}
+
+/// Version byte expected at the start of every blueprint string
+pub const BLUEPRINT_STRING_VERSION: char = '0';
+
+/// Normalizes a blueprint string as it may be pasted or read from a file.
+/// Surrounding whitespace and any embedded line breaks or spaces are removed.
+pub fn normalize_blueprint_string(bp_string: &str) -> String {
+    bp_string
+        .chars()
+        .filter(|character| !character.is_whitespace())
+        .collect()
+}
+
+/// Inflate the blueprint string according to factorio spec.
+/// The string is normalized first, then the version byte is checked before decoding.
+pub fn factorio_inflate(bp_string: &str) -> Result<String, String> {
+    let normalized = normalize_blueprint_string(bp_string);
+
+    // check and skip the version byte, then base64 decode
+    let mut characters = normalized.chars();
+    match characters.next() {
+        None => return Err("blueprint string is empty".to_string()),
+        Some(BLUEPRINT_STRING_VERSION) => (),
+        Some(version) => {
+            return Err(format!(
+                "unsupported blueprint string version {:?}, expected {:?}",
+                version, BLUEPRINT_STRING_VERSION
+            ))
+        }
+    }
+
     let base64_engine = base64::engine::general_purpose::STANDARD;
-    let encoded = base64_engine.encode(&deflated);
+    let pre_inflate = match base64_engine.decode(characters.as_str()) {
+        Ok(_decoded) => _decoded,
+        Err(e) => return Err(format!("Base64 decode error! {}", e)),
+    };
+
+    let inflated = match inflate::inflate_bytes_zlib(&pre_inflate) {
+        Ok(_inflated) => _inflated,
+        Err(e) => return Err(format!("zlib inflate error! {}", e)),
+    };
+
+    match String::from_utf8(inflated) {
+        Ok(_inflated) => Ok(_inflated),
+        Err(e) => Err(format!("inflated blueprint is not valid UTF-8: {}", e)),
+    }
+}
 
-    // append a 0
-    let mut result = "0".to_string();
-    result.push_str(&encoded);
+/// Compression level used when deflating blueprint strings
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionLevel {
+    /// Fastest compression, largest strings
+    Fast,
+    /// Balanced compression (default)
+    #[default]
+    Default,
+    /// Best compression the regular deflate encoder offers
+    Best,
+    /// Exhaustive zopfli compression, slow but produces the smallest strings
+    Max,
+}
+
+impl From<CompressionLevel> for deflate::Compression {
+    fn from(level: CompressionLevel) -> deflate::Compression {
+        match level {
+            CompressionLevel::Fast => deflate::Compression::Fast,
+            CompressionLevel::Default => deflate::Compression::Default,
+            CompressionLevel::Best | CompressionLevel::Max => deflate::Compression::Best,
+        }
+    }
+}
+
+/// Sizes in bytes of each stage of a deflated blueprint string
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DeflateStats {
+    /// Serialized JSON
+    pub raw: u64,
+    /// zlib stream
+    pub compressed: u64,
+    /// Final blueprint string, including the version byte
+    pub encoded: u64,
+}
 
-    return result;
+/// Passes writes through to the inner writer, counting the bytes written
+struct ByteCounter<W: Write> {
+    inner: W,
+    count: u64,
 }
 
-/// Replaces all invalid characters in file names with underscores
+impl<W: Write> ByteCounter<W> {
+    fn new(inner: W) -> ByteCounter<W> {
+        ByteCounter { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for ByteCounter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Deflate the blueprint according to factorio spec, returning the blueprint string
+pub fn factorio_deflate<T: Serialize + ?Sized>(
+    blueprint: &T,
+    level: CompressionLevel,
+) -> Result<String, String> {
+    let encoded = match factorio_deflate_to_writer(blueprint, level, Vec::new()) {
+        Ok((_encoded, _)) => _encoded,
+        Err(_) => return Err("failed to deflate blueprint".to_string()),
+    };
+
+    // base64 output is always valid ascii
+    Ok(String::from_utf8(encoded).unwrap())
+}
+
+/// Deflate the blueprint according to factorio spec, streaming the blueprint string into a writer.
+///
+/// The JSON is serialized straight into the zlib encoder, which feeds the base64 encoder,
+/// so neither the JSON text nor the compressed bytes are held in memory in full.
+/// Returns the writer once everything has been written to it, along with the size of each stage.
+pub fn factorio_deflate_to_writer<T: Serialize + ?Sized, W: Write>(
+    blueprint: &T,
+    level: CompressionLevel,
+    writer: W,
+) -> std::io::Result<(W, DeflateStats)> {
+    let mut encoded_counter = ByteCounter::new(writer);
+
+    // version byte
+    encoded_counter.write_all(b"0")?;
+
+    let base64_engine = base64::engine::general_purpose::STANDARD;
+    let mut base64_writer = base64::write::EncoderWriter::new(encoded_counter, &base64_engine);
+    let compressed_counter = ByteCounter::new(&mut base64_writer);
+
+    let (compressed_counter, raw) = match level {
+        CompressionLevel::Max => {
+            let zlib_writer = zopfli::ZlibEncoder::new_buffered(
+                zopfli::Options::default(),
+                zopfli::BlockType::Dynamic,
+                compressed_counter,
+            )?;
+            let (zlib_writer, raw) = json_write_counted(zlib_writer, blueprint)?;
+            let zlib_writer = zlib_writer.into_inner().map_err(|e| e.into_error())?;
+            (zlib_writer.finish()?, raw)
+        }
+        _ => {
+            let zlib_writer = deflate::write::ZlibEncoder::new(
+                compressed_counter,
+                deflate::Compression::from(level),
+            );
+            let (zlib_writer, raw) = json_write_counted(zlib_writer, blueprint)?;
+            (zlib_writer.finish()?, raw)
+        }
+    };
+    let compressed = compressed_counter.count;
+
+    let encoded_counter = base64_writer.finish()?;
+    let stats = DeflateStats {
+        raw,
+        compressed,
+        encoded: encoded_counter.count,
+    };
+
+    Ok((encoded_counter.inner, stats))
+}
+
+/// Serializes the value as compact JSON into the writer.
+/// Returns the writer and the number of bytes written.
+fn json_write_counted<T: Serialize + ?Sized, W: Write>(
+    writer: W,
+    value: &T,
+) -> std::io::Result<(W, u64)> {
+    let mut counter = ByteCounter::new(writer);
+    serde_json::to_writer(&mut counter, value)?;
+    Ok((counter.inner, counter.count))
+}
+
+/// Serializes the value as pretty JSON directly into the file.
+/// Writes go through a buffer and are flushed before returning.
+pub fn json_write_pretty<T: Serialize + ?Sized>(file: File, value: &T) -> std::io::Result<()> {
+    let mut buf_writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut buf_writer, value)?;
+    buf_writer.flush()
+}
+
+/// Reads a JSON file of a tree, along with the style it is written in.
+/// Edits in place write the file back in the same style
+pub fn json_read_tree(file_path: &Path) -> Option<(Value, TreeStyle)> {
+    let contents = fs::read_to_string(file_path).ok()?;
+    let value: Value = serde_json::from_str(&contents).ok()?;
+    let style = TreeStyle {
+        naming: FileNaming::default(),
+        format: match contents.trim_end().contains('\n') {
+            true => FileFormat::Pretty,
+            false => FileFormat::Compact,
+        },
+        canonical: is_canonical(&value),
+    };
+    Some((value, style))
+}
+
+/// Sorts the keys of every object, blueprint entities by entity number and tiles by
+/// position, so that the same blueprint always serializes the same way
+pub fn canonicalize(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<(String, Value)> = std::mem::take(object).into_iter().collect();
+            entries.sort_by(|(left, _), (right, _)| left.cmp(right));
+            for (key, mut entry) in entries {
+                canonicalize(&mut entry);
+                if let Value::Array(items) = &mut entry {
+                    match key.as_str() {
+                        "entities" => items.sort_by_key(|entity| entity["entity_number"].as_u64()),
+                        "tiles" => items.sort_by(|left, right| {
+                            let (left_x, left_y) = position(left).unwrap_or_default();
+                            let (right_x, right_y) = position(right).unwrap_or_default();
+                            left_y.total_cmp(&right_y).then(left_x.total_cmp(&right_x))
+                        }),
+                        _ => (),
+                    }
+                }
+                object.insert(key, entry);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(canonicalize),
+        _ => (),
+    }
+}
+
+/// Whether the keys of every object are sorted, as canonicalize leaves them
+pub fn is_canonical(value: &Value) -> bool {
+    match value {
+        Value::Object(object) => {
+            object
+                .keys()
+                .zip(object.keys().skip(1))
+                .all(|(left, right)| left < right)
+                && object.values().all(is_canonical)
+        }
+        Value::Array(items) => items.iter().all(is_canonical),
+        _ => true,
+    }
+}
+
+/// Lists the JSON files of a tree created by import, including book dotfiles, sorted by path.
+/// A single JSON file is listed on its own
+pub fn tree_json_files(tree_path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        for dir_entry in fs::read_dir(dir)? {
+            let entry_path = dir_entry?.path();
+            if entry_path.is_dir() {
+                collect(&entry_path, files)?;
+            } else if entry_path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+            {
+                files.push(entry_path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = vec![];
+    match tree_path.is_dir() {
+        true => collect(tree_path, &mut files)?,
+        false => files.push(tree_path.to_path_buf()),
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Replaces all invalid characters in file names with underscores,
+/// then sanitizes the name for other filesystems
 pub fn file_rename(file_name: String) -> String {
     let mut new_file_name: String = String::new();
 
@@ -102,7 +505,292 @@ pub fn file_rename(file_name: String) -> String {
         }
     }
 
-    return new_file_name;
+    sanitize_file_name(&new_file_name)
+}
+
+/// Device names reserved by Windows, also when followed by an extension.
+/// Superscript digits count as digits for the serial and parallel ports
+const RESERVED_NAMES: [&str; 30] = [
+    "CON",
+    "PRN",
+    "AUX",
+    "NUL",
+    "CONIN$",
+    "CONOUT$",
+    "COM1",
+    "COM2",
+    "COM3",
+    "COM4",
+    "COM5",
+    "COM6",
+    "COM7",
+    "COM8",
+    "COM9",
+    "COM\u{b9}",
+    "COM\u{b2}",
+    "COM\u{b3}",
+    "LPT1",
+    "LPT2",
+    "LPT3",
+    "LPT4",
+    "LPT5",
+    "LPT6",
+    "LPT7",
+    "LPT8",
+    "LPT9",
+    "LPT\u{b9}",
+    "LPT\u{b2}",
+    "LPT\u{b3}",
+];
+
+/// Longest file name in bytes on mainstream filesystems
+const MAX_FILE_NAME_BYTES: usize = 255;
+
+/// Bytes added to a name on disk, at most: the dot and extension of a book dotfile
+const FILE_NAME_AFFIX_BYTES: usize = ".".len() + ".json".len();
+
+/// Makes a name valid on every mainstream filesystem, so that a tree can be shared through git:
+/// - composed to Unicode NFC, as macOS would otherwise store some characters decomposed
+/// - control characters replaced with underscores
+/// - trailing dots and spaces replaced with underscores, Windows drops them
+/// - Windows reserved names such as "CON" or "com1.x" suffixed with an underscore
+/// - cut at a character boundary so that the book dotfile fits in 255 bytes, last so that
+///   the underscores above are counted
+pub fn sanitize_file_name(name: &str) -> String {
+    fn replace_trailing(name: &mut String) {
+        let kept_len = name.trim_end_matches(['.', ' ']).len();
+        let trailing = name.len() - kept_len;
+        name.truncate(kept_len);
+        name.push_str(&"_".repeat(trailing));
+    }
+
+    let mut sanitized: String = name
+        .nfc()
+        .map(|c| match c.is_control() {
+            true => '_',
+            false => c,
+        })
+        .collect();
+
+    replace_trailing(&mut sanitized);
+
+    let stem = sanitized.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.contains(&stem.trim_end().to_ascii_uppercase().as_str()) {
+        sanitized.insert(stem.len(), '_');
+    }
+
+    let max_len = MAX_FILE_NAME_BYTES - FILE_NAME_AFFIX_BYTES;
+    if sanitized.len() > max_len {
+        let mut end = max_len;
+        while !sanitized.is_char_boundary(end) {
+            end -= 1;
+        }
+        sanitized.truncate(end);
+        // the cut may end on a dot or space
+        replace_trailing(&mut sanitized);
+    }
+
+    match sanitized.is_empty() {
+        true => "_".to_string(),
+        false => sanitized,
+    }
+}
+
+/// Pairs of names that differ only in case. They would share one file
+/// on case-insensitive filesystems, the default on Windows and macOS
+pub fn case_collisions(names: &[String]) -> Vec<(String, String)> {
+    let mut seen: HashMap<String, &String> = HashMap::new();
+    let mut collisions = vec![];
+    for name in names {
+        match seen.entry(name.to_lowercase()) {
+            Entry::Occupied(_first) => {
+                if *_first.get() != name {
+                    collisions.push((_first.get().to_string(), name.clone()));
+                }
+            }
+            Entry::Vacant(_slot) => {
+                _slot.insert(name);
+            }
+        }
+    }
+    collisions
+}
+
+/// Order entry key holding the name of an item's file or directory,
+/// set when import named it differently from its label
+pub const ORDER_FILE_KEY: &str = "file";
+
+/// Formatting tags dropped from readable file names, they carry no text
+const FORMATTING_TAGS: [&str; 2] = ["color", "font"];
+
+/// How import names the files and directories of a tree
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FileNaming {
+    /// Invalid characters replaced with underscores. The label in the JSON is renamed too
+    #[default]
+    Renamed,
+    /// Rich text tags turned into readable tokens. The label in the JSON is kept as it is
+    Readable,
+}
+
+impl FileNaming {
+    /// File or directory name of an item with a label
+    pub fn file_name(&self, label: &str) -> String {
+        match self {
+            FileNaming::Renamed => file_rename(label.to_string()),
+            FileNaming::Readable => readable_file_name(label),
+        }
+    }
+}
+
+/// Layout of the JSON files of a tree
+#[derive(clap::ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    /// Indented, one value per line
+    #[default]
+    Pretty,
+    /// Everything on a single line
+    Compact,
+}
+
+/// How the files of a written tree are named and laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TreeStyle {
+    pub naming: FileNaming,
+    pub format: FileFormat,
+    /// Files are canonicalized before they are written
+    pub canonical: bool,
+}
+
+impl TreeStyle {
+    /// Serializes the value into the file in this style.
+    /// Writes go through a buffer and are flushed before returning.
+    pub fn write<T: Serialize + ?Sized>(&self, file: File, value: &T) -> std::io::Result<()> {
+        let mut buf_writer = BufWriter::new(file);
+        match self.canonical {
+            true => {
+                let mut value = serde_json::to_value(value)?;
+                canonicalize(&mut value);
+                self.write_value(&mut buf_writer, &value)?;
+            }
+            false => self.write_value(&mut buf_writer, value)?,
+        }
+        buf_writer.flush()
+    }
+
+    fn write_value<T: Serialize + ?Sized, W: Write>(
+        &self,
+        writer: W,
+        value: &T,
+    ) -> serde_json::Result<()> {
+        match self.format {
+            FileFormat::Pretty => serde_json::to_writer_pretty(writer, value),
+            FileFormat::Compact => serde_json::to_writer(writer, value),
+        }
+    }
+}
+
+/// Turns the rich text tags of a label into their names, then replaces invalid characters
+/// except spaces: "[item=iron-plate] Smelting [virtual-signal=signal-A]" becomes
+/// "iron-plate Smelting signal-A"
+pub fn readable_file_name(label: &str) -> String {
+    let mut tokens = String::new();
+    let mut rest = label;
+    while let Some(start) = rest.find('[') {
+        tokens.push_str(&rest[..start]);
+        let tag = &rest[start + 1..];
+        let end = match tag.find(']') {
+            Some(_end) => _end,
+            None => {
+                tokens.push_str(&rest[start..]);
+                rest = "";
+                break;
+            }
+        };
+        let (name, value) = tag[..end].split_once('=').unwrap_or((&tag[..end], ""));
+        if !(name.starts_with('/') || FORMATTING_TAGS.contains(&name)) {
+            // [item=iron-plate,quality=rare] and [img=item/iron-plate] are named after the item
+            let value = value.split(",quality=").next().unwrap_or_default();
+            let value = value.rsplit('/').next().unwrap_or_default();
+            tokens.push(' ');
+            tokens.push_str(if value.is_empty() { name } else { value });
+            tokens.push(' ');
+        }
+        rest = &tag[end + 1..];
+    }
+    tokens.push_str(rest);
+
+    let readable = tokens
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .map(|c| match c != ' ' && INVALID_CHARS.contains(c) {
+            true => '_',
+            false => c,
+        })
+        .collect::<String>();
+    match readable.is_empty() {
+        true => file_rename(label.to_string()),
+        false => sanitize_file_name(&readable),
+    }
+}
+
+/// File or directory name of the item an order entry refers to
+pub fn entry_file_name(entry: &Value) -> Option<String> {
+    if let Some(file_name) = entry.get(ORDER_FILE_KEY).and_then(|file| file.as_str()) {
+        return Some(file_name.to_string());
+    }
+    match BlueprintType::classify(entry) {
+        BlueprintType::Book(label)
+        | BlueprintType::Blueprint(label)
+        | BlueprintType::UpgradePlanner(label)
+        | BlueprintType::DeconPlanner(label) => Some(label),
+        BlueprintType::Invalid => None,
+    }
+}
+
+/// Empty directory for a test, removed when dropped so that a failed assertion
+/// does not leave it behind
+#[cfg(test)]
+pub struct TestDir(PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    /// Creates the directory in the temporary directory, named after the test,
+    /// the process and a counter so that tests running in parallel never share one
+    pub fn new(name: &str) -> TestDir {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static TEST_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "fbpconvert-{}-{}-{}",
+            name,
+            std::process::id(),
+            TEST_DIR_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TestDir(dir)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
 }
 
 #[cfg(test)]
@@ -157,4 +845,264 @@ mod test {
             BlueprintType::Book(_)
         ));
     }
+
+    #[test]
+    fn test_deflate_inflate_roundtrip() {
+        let blueprint = json!({
+            "blueprint": {
+                "item": "blueprint",
+                "label": "roundtrip",
+                "version": 1234567890
+            }
+        });
+
+        for level in [
+            CompressionLevel::Fast,
+            CompressionLevel::Default,
+            CompressionLevel::Best,
+            CompressionLevel::Max,
+        ] {
+            let bp_string = factorio_deflate(&blueprint, level).unwrap();
+            assert!(bp_string.starts_with('0'));
+
+            let inflated: Value =
+                serde_json::from_str(&factorio_inflate(&bp_string).unwrap()).unwrap();
+            assert_eq!(inflated, blueprint);
+        }
+    }
+
+    #[test]
+    fn test_deflate_to_writer_matches_string() {
+        let blueprint = json!({"blueprint": {"label": "writer", "version": 1}});
+
+        let (written, stats) =
+            factorio_deflate_to_writer(&blueprint, CompressionLevel::Default, Vec::new()).unwrap();
+        assert_eq!(
+            String::from_utf8(written.clone()).unwrap(),
+            factorio_deflate(&blueprint, CompressionLevel::Default).unwrap()
+        );
+
+        assert_eq!(
+            stats.raw,
+            serde_json::to_vec(&blueprint).unwrap().len() as u64
+        );
+        assert_eq!(stats.encoded, written.len() as u64);
+        // base64 encodes every 3 bytes as 4 characters, plus the version byte
+        assert_eq!(stats.encoded, stats.compressed.div_ceil(3) * 4 + 1);
+    }
+
+    #[test]
+    fn test_deflate_max_not_larger() {
+        let entities: Vec<Value> = (0..50)
+            .map(|idx| {
+                json!({
+                    "entity_number": idx,
+                    "name": "transport-belt",
+                    "position": {"x": idx, "y": 0}
+                })
+            })
+            .collect();
+        let blueprint =
+            json!({"blueprint": {"label": "belts", "version": 1, "entities": entities}});
+
+        let (_, default_stats) =
+            factorio_deflate_to_writer(&blueprint, CompressionLevel::Default, std::io::sink())
+                .unwrap();
+        let (_, max_stats) =
+            factorio_deflate_to_writer(&blueprint, CompressionLevel::Max, std::io::sink()).unwrap();
+
+        assert_eq!(default_stats.raw, max_stats.raw);
+        assert!(max_stats.compressed <= default_stats.compressed);
+    }
+
+    #[test]
+    fn test_inflate_trims_whitespace_and_line_breaks() {
+        let blueprint = json!({"blueprint": {"label": "trimmed", "version": 1}});
+        let bp_string = factorio_deflate(&blueprint, CompressionLevel::Default).unwrap();
+
+        let (head, tail) = bp_string.split_at(bp_string.len() / 2);
+        let messy = format!("  \t{}\r\n{}\n\n", head, tail);
+
+        let inflated: Value = serde_json::from_str(&factorio_inflate(&messy).unwrap()).unwrap();
+        assert_eq!(inflated, blueprint);
+    }
+
+    #[test]
+    fn test_inflate_errors_instead_of_panicking() {
+        assert!(factorio_inflate("").is_err());
+        assert!(factorio_inflate(" \n ").is_err());
+        // multi-byte first character
+        assert!(factorio_inflate("é0eNqrVg")
+            .unwrap_err()
+            .contains("version"));
+        assert!(factorio_inflate("1eNqrVg").unwrap_err().contains("version"));
+        assert!(factorio_inflate("0not base64!").is_err());
+        // valid base64, not a zlib stream
+        assert!(factorio_inflate("0aGVsbG8=").is_err());
+
+        // valid zlib stream, not UTF-8
+        let base64_engine = base64::engine::general_purpose::STANDARD;
+        let not_utf8 = format!(
+            "0{}",
+            base64_engine.encode(deflate::deflate_bytes_zlib(&[0xff, 0xfe, 0xfd]))
+        );
+        assert!(factorio_inflate(&not_utf8).unwrap_err().contains("UTF-8"));
+    }
+
+    #[test]
+    fn test_visit_blueprints_in_slot_order() {
+        let book = json!({
+            "blueprint_book": {
+                "label": "outer",
+                "active_index": 0,
+                "version": 1,
+                "blueprints": [
+                    {"index": 1, "blueprint": {"label": "second", "version": 1}},
+                    {"index": 0, "blueprint_book": {
+                        "label": "inner",
+                        "active_index": 0,
+                        "version": 1,
+                        "blueprints": [{"index": 0, "blueprint": {"label": "first", "version": 1}}]
+                    }},
+                    {"index": 2, "upgrade_planner": {"label": "skipped", "settings": {}}}
+                ]
+            }
+        });
+
+        let mut visited: Vec<String> = vec![];
+        visit_blueprints(&book, &mut |path, blueprint| {
+            assert_eq!(path.last().unwrap(), blueprint["label"].as_str().unwrap());
+            visited.push(path.join("/"));
+        });
+        assert_eq!(visited, vec!["outer/inner/first", "outer/second"]);
+    }
+
+    #[test]
+    fn test_readable_file_name() {
+        assert_eq!(
+            readable_file_name("[item=iron-plate] Smelting [virtual-signal=signal-A]"),
+            "iron-plate Smelting signal-A"
+        );
+        assert_eq!(
+            readable_file_name("[color=red]Red[/color] [img=item/copper-plate] v1.2"),
+            "Red copper-plate v1_2"
+        );
+        assert_eq!(
+            readable_file_name("[item=iron-gear-wheel,quality=rare]Gears"),
+            "iron-gear-wheel Gears"
+        );
+        assert_eq!(readable_file_name("[item=a/b"), "[item=a_b");
+        assert_eq!(
+            readable_file_name("[color=red][/color]"),
+            "[color=red][_color]"
+        );
+        assert_eq!(FileNaming::Renamed.file_name("a b"), "a_b");
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        // control characters, trailing dots and spaces
+        assert_eq!(sanitize_file_name("a\tb\u{7}"), "a_b_");
+        assert_eq!(sanitize_file_name("name. "), "name__");
+        assert_eq!(file_rename("version 1.".to_string()), "version_1_");
+
+        // reserved names, with or without extension and in any case
+        assert_eq!(sanitize_file_name("CON"), "CON_");
+        assert_eq!(sanitize_file_name("com1.belts"), "com1_.belts");
+        assert_eq!(sanitize_file_name("nul "), "nul_");
+        assert_eq!(sanitize_file_name("CONSOLE"), "CONSOLE");
+        assert_eq!(sanitize_file_name("conin$"), "conin$_");
+        assert_eq!(sanitize_file_name("CONOUT$.txt"), "CONOUT$_.txt");
+        assert_eq!(sanitize_file_name("COM\u{b9}"), "COM\u{b9}_");
+        assert_eq!(sanitize_file_name("lpt\u{b3}.x"), "lpt\u{b3}_.x");
+        assert_eq!(sanitize_file_name(""), "_");
+
+        // decomposed and composed forms give the same name
+        assert_eq!(sanitize_file_name("e\u{301}t\u{e9}"), "\u{e9}t\u{e9}");
+
+        // long names are cut at a character boundary
+        let long = sanitize_file_name(&"\u{e9}".repeat(200));
+        assert!(long.len() + FILE_NAME_AFFIX_BYTES <= MAX_FILE_NAME_BYTES);
+        assert_eq!(long, "\u{e9}".repeat(124));
+
+        // the reserved name underscore is counted before cutting
+        let long = sanitize_file_name(&format!("CON.{}", "a".repeat(250)));
+        assert_eq!(long.len() + FILE_NAME_AFFIX_BYTES, MAX_FILE_NAME_BYTES);
+        assert!(long.starts_with("CON_.aaa"));
+
+        // a cut ending on a dot or space is fixed as well
+        let long = sanitize_file_name(&format!("{}. b", "a".repeat(247)));
+        assert_eq!(long, format!("{}__", "a".repeat(247)));
+    }
+
+    #[test]
+    fn test_case_collisions() {
+        let names: Vec<String> = [
+            "Belts.json",
+            "belts.json",
+            "Belts",
+            "Belts.json",
+            "BELTS.json",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+        assert_eq!(
+            case_collisions(&names),
+            vec![
+                ("Belts.json".to_string(), "belts.json".to_string()),
+                ("Belts.json".to_string(), "BELTS.json".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_canonical_tree_style() {
+        let dir = TestDir::new("canonical");
+        let blueprint = json!({
+            "blueprint": {
+                "label": "b",
+                "item": "blueprint",
+                "entities": [
+                    {"name": "b", "entity_number": 2, "position": {"y": 0.5, "x": 0.5}},
+                    {"name": "a", "entity_number": 1, "position": {"y": 0.5, "x": 1.5}}
+                ],
+                "tiles": [
+                    {"name": "concrete", "position": {"x": 1, "y": 1}},
+                    {"name": "concrete", "position": {"x": 0, "y": 1}},
+                    {"name": "concrete", "position": {"x": 5, "y": 0}}
+                ]
+            }
+        });
+        assert!(!is_canonical(&blueprint));
+
+        let style = TreeStyle {
+            format: FileFormat::Compact,
+            canonical: true,
+            ..TreeStyle::default()
+        };
+        let file_path = dir.join("b.json");
+        style
+            .write(File::create(&file_path).unwrap(), &blueprint)
+            .unwrap();
+
+        let contents = fs::read_to_string(&file_path).unwrap();
+        assert!(!contents.contains('\n'));
+        assert!(contents.starts_with(r#"{"blueprint":{"entities":[{"entity_number":1,"name":"a""#));
+        let (written, read_style) = json_read_tree(&file_path).unwrap();
+        assert_eq!(read_style, style);
+        let tiles: Vec<(f64, f64)> = array(&written["blueprint"], "tiles")
+            .iter()
+            .filter_map(position)
+            .collect();
+        assert_eq!(tiles, vec![(5.0, 0.0), (0.0, 1.0), (1.0, 1.0)]);
+
+        // the same value comes out of a pretty file, laid out as it was
+        TreeStyle::default()
+            .write(File::create(&file_path).unwrap(), &blueprint)
+            .unwrap();
+        let (read_back, read_style) = json_read_tree(&file_path).unwrap();
+        assert_eq!(read_back, blueprint);
+        assert_eq!(read_style, TreeStyle::default());
+    }
 }
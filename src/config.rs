@@ -0,0 +1,399 @@
+//! Project configuration read from an fbpconvert.toml in the current or a parent directory.
+//! Its settings stand in for flags that are not given on the command line.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::args::{
+    self, BookSubCommands, ConfigSubCommands, ExportSubCommands, ImportSubCommands, MainSubCommands,
+};
+use crate::common::{CompressionLevel, FileFormat, FileNaming};
+use crate::progress::{self, CommandType};
+
+/// Name of the configuration file
+pub const CONFIG_FILE_NAME: &str = "fbpconvert.toml";
+
+/// Settings of an fbpconvert.toml. Every setting is optional
+#[derive(Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Tree root: where import writes, what export, find, replace and icons read,
+    /// and where book commands look up paths not found in the current directory
+    pub tree: Option<String>,
+    /// Directory export writes blueprint string files into
+    pub output: Option<String>,
+    /// How files of written trees are named after labels
+    pub naming: Option<FileNaming>,
+    /// Layout of the JSON files of written trees
+    pub file_format: Option<FileFormat>,
+    /// Whether the JSON files of written trees are canonicalized
+    pub canonical: Option<bool>,
+    /// Compression level of blueprint strings
+    pub compression: Option<CompressionLevel>,
+    /// Output format of the bom report
+    pub format: Option<args::ReportFormat>,
+
+    /// File the settings were read from
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Reads the fbpconvert.toml in the directory or its closest parent.
+    /// Returns the empty configuration if there is none
+    pub fn discover(dir: &Path) -> Result<Config, String> {
+        match dir
+            .ancestors()
+            .map(|ancestor| ancestor.join(CONFIG_FILE_NAME))
+            .find(|config_path| config_path.is_file())
+        {
+            Some(_path) => Config::read(&_path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Reads a configuration file. Relative paths in it are resolved against its directory
+    pub fn read(config_path: &Path) -> Result<Config, String> {
+        let contents = match fs::read_to_string(config_path) {
+            Ok(_contents) => _contents,
+            Err(_) => return Err(format!("failed to read {:?}", config_path)),
+        };
+        let mut config: Config = match toml::from_str(&contents) {
+            Ok(_config) => _config,
+            Err(e) => return Err(format!("{:?}: {}", config_path, e)),
+        };
+
+        let config_dir = config_path.parent().unwrap_or(Path::new("."));
+        let resolve = |dir: &String| config_dir.join(dir).to_string_lossy().to_string();
+        config.tree = config.tree.as_ref().map(resolve);
+        config.output = config.output.as_ref().map(resolve);
+        config.path = Some(config_path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Fills in the flags of a command that were not given on the command line
+    pub fn apply(&self, command: &mut MainSubCommands) {
+        fn fill<T: Clone>(flag: &mut Option<T>, setting: &Option<T>) {
+            if flag.is_none() {
+                *flag = setting.clone();
+            }
+        }
+        fn in_tree(path: &mut String, tree: &Option<String>) {
+            if let Some(_tree) = tree {
+                let tree_path = Path::new(_tree).join(&path);
+                if !Path::new(path).exists() && tree_path.exists() {
+                    *path = tree_path.to_string_lossy().to_string();
+                }
+            }
+        }
+
+        match command {
+            MainSubCommands::Import(ImportSubCommands::File(_file)) => {
+                fill(&mut _file.destination, &self.tree);
+                if !_file.readable_names {
+                    fill(&mut _file.naming, &self.naming);
+                }
+                fill(&mut _file.file_format, &self.file_format);
+                fill(&mut _file.canonical, &self.canonical);
+            }
+            MainSubCommands::Import(ImportSubCommands::Clipboard(_copy)) => {
+                fill(&mut _copy.destination, &self.tree);
+                if !_copy.readable_names {
+                    fill(&mut _copy.naming, &self.naming);
+                }
+                fill(&mut _copy.file_format, &self.file_format);
+                fill(&mut _copy.canonical, &self.canonical);
+            }
+            MainSubCommands::Export(ExportSubCommands::File(_file)) => {
+                fill(&mut _file.source, &self.tree);
+                fill(&mut _file.destination, &self.output);
+                fill(&mut _file.compression, &self.compression);
+            }
+            MainSubCommands::Export(ExportSubCommands::Clipboard(_copy)) => {
+                fill(&mut _copy.source, &self.tree);
+                fill(&mut _copy.compression, &self.compression);
+            }
+            MainSubCommands::Check(_check) => fill(&mut _check.tree, &self.tree),
+            MainSubCommands::Find(_find) => fill(&mut _find.source, &self.tree),
+            MainSubCommands::Replace(_replace) => fill(&mut _replace.tree, &self.tree),
+            MainSubCommands::Icons(_icons) => fill(&mut _icons.tree, &self.tree),
+            MainSubCommands::Bom(_bom) => fill(&mut _bom.format, &self.format),
+            MainSubCommands::Browse(_browse) => fill(&mut _browse.compression, &self.compression),
+            MainSubCommands::Book(BookSubCommands::Add(_add)) => {
+                in_tree(&mut _add.book, &self.tree);
+                fill(&mut _add.naming, &self.naming);
+                fill(&mut _add.file_format, &self.file_format);
+                fill(&mut _add.canonical, &self.canonical);
+            }
+            MainSubCommands::Book(BookSubCommands::Rm(_rm)) => in_tree(&mut _rm.item, &self.tree),
+            MainSubCommands::Book(BookSubCommands::Mv(_mv)) => {
+                in_tree(&mut _mv.item, &self.tree);
+                in_tree(&mut _mv.book, &self.tree);
+            }
+            MainSubCommands::Book(BookSubCommands::Reorder(_reorder)) => {
+                in_tree(&mut _reorder.book, &self.tree)
+            }
+            MainSubCommands::Extract(args::Extract { output, .. })
+            | MainSubCommands::Merge(args::Merge { output, .. })
+            | MainSubCommands::Restructure(args::Restructure { output, .. })
+            | MainSubCommands::ApplyPlanner(args::ApplyPlanner { output, .. })
+            | MainSubCommands::Transform(args::Transform { output, .. }) => {
+                fill(&mut output.compression, &self.compression);
+                fill(&mut output.naming, &self.naming);
+                fill(&mut output.file_format, &self.file_format);
+                fill(&mut output.canonical, &self.canonical);
+            }
+            _ => (),
+        }
+    }
+
+    /// Effective settings as lines of name, value and origin
+    pub fn lines(&self) -> Vec<String> {
+        fn setting(name: &str, value: &Option<String>, default: &str) -> String {
+            match value {
+                Some(_value) => format!("{:<12}{:<24}{}", name, _value, CONFIG_FILE_NAME),
+                None => format!("{:<12}{:<24}default", name, default),
+            }
+        }
+        fn value_name<T: ValueEnum>(value: &Option<T>) -> Option<String> {
+            value
+                .as_ref()
+                .and_then(|_value| _value.to_possible_value())
+                .map(|possible_value| possible_value.get_name().to_string())
+        }
+
+        vec![
+            format!(
+                "{:<12}{}",
+                "config",
+                match &self.path {
+                    Some(_path) => _path.to_string_lossy().to_string(),
+                    None => format!("no {} found", CONFIG_FILE_NAME),
+                }
+            ),
+            setting("tree", &self.tree, "."),
+            setting("output", &self.output, "."),
+            setting("naming", &value_name(&self.naming), "renamed"),
+            setting("file_format", &value_name(&self.file_format), "pretty"),
+            setting(
+                "canonical",
+                &self.canonical.map(|canonical| canonical.to_string()),
+                "false",
+            ),
+            setting("compression", &value_name(&self.compression), "default"),
+            setting("format", &value_name(&self.format), "table"),
+        ]
+    }
+}
+
+pub struct Worker {
+    pub config_cmd: ConfigSubCommands,
+}
+
+impl Worker {
+    pub fn from(config_cmd: &ConfigSubCommands) -> Worker {
+        Worker {
+            config_cmd: config_cmd.clone(),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let config = match std::env::current_dir()
+            .map_err(|_| "failed to read the current directory".to_string())
+            .and_then(|current_dir| Config::discover(&current_dir))
+        {
+            Ok(_config) => _config,
+            Err(err_msg) => {
+                progress::Tracker::new_quiet_sync(CommandType::Config)
+                    .lock()
+                    .unwrap()
+                    .error_additional(err_msg);
+                exit(1);
+            }
+        };
+
+        match self.config_cmd {
+            ConfigSubCommands::Show => {
+                for line in config.lines() {
+                    println!("{}", line);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::common;
+    use clap::Parser;
+
+    #[test]
+    fn test_discover_in_parent_directory() {
+        let root = common::TestDir::new("config");
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            root.join(CONFIG_FILE_NAME),
+            "tree = \"blueprints\"\nnaming = \"readable\"\ncompression = \"best\"\n",
+        )
+        .unwrap();
+
+        let config = Config::discover(&nested).unwrap();
+        assert_eq!(
+            config.tree,
+            Some(root.join("blueprints").to_string_lossy().to_string())
+        );
+        assert_eq!(config.naming, Some(FileNaming::Readable));
+        assert_eq!(config.compression, Some(CompressionLevel::Best));
+        assert_eq!(config.output, None);
+        assert_eq!(config.path, Some(root.join(CONFIG_FILE_NAME)));
+
+        // unknown settings are refused instead of being ignored
+        fs::write(root.join(CONFIG_FILE_NAME), "compresion = \"best\"\n").unwrap();
+        assert!(Config::discover(&nested).is_err());
+    }
+
+    #[test]
+    fn test_flags_override_config() {
+        let config = Config {
+            tree: Some("tree".to_string()),
+            output: Some("out".to_string()),
+            compression: Some(CompressionLevel::Max),
+            ..Config::default()
+        };
+
+        let mut main_args =
+            args::MainCliArgs::parse_from(["fbpconvert", "export", "file", "-c", "fast"]);
+        config.apply(&mut main_args.command);
+        match main_args.command {
+            MainSubCommands::Export(ExportSubCommands::File(_file)) => {
+                assert_eq!(_file.source.as_deref(), Some("tree"));
+                assert_eq!(_file.destination.as_deref(), Some("out"));
+                assert_eq!(_file.compression, Some(CompressionLevel::Fast));
+            }
+            _ => panic!("expected export file"),
+        }
+
+        let mut main_args =
+            args::MainCliArgs::parse_from(["fbpconvert", "import", "file", "bp.txt", "-d", "dir"]);
+        config.apply(&mut main_args.command);
+        match main_args.command {
+            MainSubCommands::Import(ImportSubCommands::File(_file)) => {
+                assert_eq!(_file.destination.as_deref(), Some("dir"));
+                assert_eq!(_file.naming, None);
+            }
+            _ => panic!("expected import file"),
+        }
+
+        // the naming policy reaches every command writing a tree
+        let config = Config {
+            naming: Some(FileNaming::Readable),
+            ..Config::default()
+        };
+        let mut main_args =
+            args::MainCliArgs::parse_from(["fbpconvert", "transform", "bp.txt", "-t", "dir"]);
+        config.apply(&mut main_args.command);
+        match main_args.command {
+            MainSubCommands::Transform(_transform) => {
+                assert_eq!(_transform.output.naming, Some(FileNaming::Readable));
+            }
+            _ => panic!("expected transform"),
+        }
+
+        let mut main_args = args::MainCliArgs::parse_from([
+            "fbpconvert",
+            "book",
+            "add",
+            "book",
+            "bp.txt",
+            "--naming",
+            "renamed",
+        ]);
+        config.apply(&mut main_args.command);
+        match main_args.command {
+            MainSubCommands::Book(BookSubCommands::Add(_add)) => {
+                assert_eq!(_add.naming, Some(FileNaming::Renamed));
+            }
+            _ => panic!("expected book add"),
+        }
+    }
+
+    #[test]
+    fn test_tree_layout_and_tree_default() {
+        let root = common::TestDir::new("config-tree");
+        fs::create_dir_all(root.join("tree").join("Book")).unwrap();
+        let config = Config {
+            tree: Some(root.join("tree").to_string_lossy().to_string()),
+            file_format: Some(FileFormat::Compact),
+            canonical: Some(true),
+            ..Config::default()
+        };
+
+        let mut main_args = args::MainCliArgs::parse_from([
+            "fbpconvert",
+            "import",
+            "clipboard",
+            "--canonical=false",
+        ]);
+        config.apply(&mut main_args.command);
+        match main_args.command {
+            MainSubCommands::Import(ImportSubCommands::Clipboard(_copy)) => {
+                assert_eq!(_copy.file_format, Some(FileFormat::Compact));
+                assert_eq!(_copy.canonical, Some(false));
+            }
+            _ => panic!("expected import clipboard"),
+        }
+
+        let mut main_args =
+            args::MainCliArgs::parse_from(["fbpconvert", "extract", "bp.txt", "1", "-t", "dir"]);
+        config.apply(&mut main_args.command);
+        match main_args.command {
+            MainSubCommands::Extract(_extract) => {
+                assert_eq!(_extract.output.file_format, Some(FileFormat::Compact));
+                assert_eq!(_extract.output.canonical, Some(true));
+            }
+            _ => panic!("expected extract"),
+        }
+
+        // commands reading a tree default to it
+        let mut main_args = args::MainCliArgs::parse_from(["fbpconvert", "icons", "--auto"]);
+        config.apply(&mut main_args.command);
+        match main_args.command {
+            MainSubCommands::Icons(_icons) => assert_eq!(_icons.tree, config.tree),
+            _ => panic!("expected icons"),
+        }
+        let mut main_args = args::MainCliArgs::parse_from(["fbpconvert", "find", "-e", "beacon"]);
+        config.apply(&mut main_args.command);
+        match main_args.command {
+            MainSubCommands::Find(_find) => assert_eq!(_find.source, config.tree),
+            _ => panic!("expected find"),
+        }
+
+        // book paths not found in the current directory are looked up in the tree
+        let mut main_args =
+            args::MainCliArgs::parse_from(["fbpconvert", "book", "reorder", "Book", "--by-label"]);
+        config.apply(&mut main_args.command);
+        match main_args.command {
+            MainSubCommands::Book(BookSubCommands::Reorder(_reorder)) => assert_eq!(
+                _reorder.book,
+                root.join("tree").join("Book").to_string_lossy()
+            ),
+            _ => panic!("expected book reorder"),
+        }
+        let mut main_args =
+            args::MainCliArgs::parse_from(["fbpconvert", "book", "rm", "Missing/a.json"]);
+        config.apply(&mut main_args.command);
+        match main_args.command {
+            MainSubCommands::Book(BookSubCommands::Rm(_rm)) => {
+                assert_eq!(_rm.item, "Missing/a.json")
+            }
+            _ => panic!("expected book rm"),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Writes the blueprint produced by a command to its destination.
+//!
+//! The counterpart of [`crate::source`]: a blueprint can be written as a blueprint string
+//! to stdout, a file or the clipboard, or as a JSON tree like import does.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use copypasta::{ClipboardContext, ClipboardProvider};
+use serde_json::Value;
+
+use crate::common::{self, BlueprintType, CompressionLevel, TreeStyle};
+use crate::progress::{self, CommandType};
+use crate::{args, import, manifest};
+
+/// Where a blueprint is written to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    /// Blueprint string printed to stdout
+    Stdout,
+    /// Blueprint string written to a file
+    File(PathBuf),
+    /// Blueprint string copied into the clipboard
+    Clipboard,
+    /// JSON tree written into a directory, files named and laid out as given
+    Tree(PathBuf, TreeStyle),
+}
+
+impl Destination {
+    pub fn from(output_args: &args::Output) -> Destination {
+        if let Some(_file) = &output_args.outfile {
+            Destination::File(PathBuf::from(_file))
+        } else if let Some(_dir) = &output_args.tree {
+            Destination::Tree(
+                PathBuf::from(_dir),
+                TreeStyle {
+                    naming: output_args.naming.unwrap_or_default(),
+                    format: output_args.file_format.unwrap_or_default(),
+                    canonical: output_args.canonical.unwrap_or_default(),
+                },
+            )
+        } else if output_args.clipboard {
+            Destination::Clipboard
+        } else {
+            Destination::Stdout
+        }
+    }
+
+    /// Writes the blueprint to the destination.
+    /// Returns an error message if an error occurs
+    pub fn write(&self, blueprint_obj: Value, compression: CompressionLevel) -> Result<(), String> {
+        match self {
+            Destination::Stdout => {
+                println!("{}", common::factorio_deflate(&blueprint_obj, compression)?);
+                Ok(())
+            }
+            Destination::File(out_file) => {
+                let out_file = match File::create(out_file) {
+                    Ok(_file) => _file,
+                    Err(_) => return Err("file creation error".to_string()),
+                };
+                match common::factorio_deflate_to_writer(
+                    &blueprint_obj,
+                    compression,
+                    BufWriter::new(out_file),
+                )
+                .and_then(|(mut buf_writer, _)| buf_writer.flush())
+                {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err("file write error".to_string()),
+                }
+            }
+            Destination::Clipboard => {
+                let bp_string = common::factorio_deflate(&blueprint_obj, compression)?;
+                let mut clipboard = match ClipboardContext::new() {
+                    Ok(_clipboard) => _clipboard,
+                    Err(_) => return Err("clipboard unavailable".to_string()),
+                };
+                match clipboard.set_contents(bp_string) {
+                    Ok(_) => {
+                        // same pause as export, the contents are not copied over without it
+                        std::thread::sleep(std::time::Duration::from_millis(100));
+                        Ok(())
+                    }
+                    Err(_) => Err("failed to copy blueprint string to clipboard".to_string()),
+                }
+            }
+            Destination::Tree(tree_dir, style) => {
+                if let BlueprintType::Invalid = BlueprintType::classify(&blueprint_obj) {
+                    return Err("invalid blueprint!".to_string());
+                }
+                if fs::create_dir_all(tree_dir).is_err() {
+                    return Err("Error creating destination directory!".to_string());
+                }
+
+                // a tree written inside an imported tree is recorded in its manifest
+                let refresh = manifest::Refresh::prepare(tree_dir)?;
+                let prog_tracker = progress::Tracker::new_quiet_sync(CommandType::Import);
+                import::Worker::unknown_write(&prog_tracker, blueprint_obj, tree_dir, *style);
+                if let Some(_refresh) = refresh {
+                    _refresh.finish()?;
+                }
+
+                let errors = prog_tracker.lock().unwrap().errors;
+                match errors {
+                    0 => Ok(()),
+                    _ => Err(format!("{} errors writing the tree", errors)),
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use std::fs;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::sync::{Arc, Mutex};
 
@@ -10,6 +11,7 @@ use serde_json::Value;
 use crate::args;
 use crate::common;
 use crate::factorio_structs::{self, exportable};
+use crate::manifest;
 use crate::progress::{self, ProgressType};
 
 /// Prefix for exported blueprints
@@ -20,6 +22,7 @@ pub struct Worker {
     pub source: String,
     pub out_file: Option<String>,
     pub dest: Option<String>,
+    pub compression: common::CompressionLevel,
 }
 
 impl Worker {
@@ -27,17 +30,20 @@ impl Worker {
         let source: String;
         let out_file: Option<String>;
         let dest: Option<String>;
+        let compression: common::CompressionLevel;
 
         match &export_file {
             args::ExportSubCommands::File(_file) => {
-                source = _file.source.clone().unwrap();
+                source = _file.source.clone().unwrap_or_default();
                 out_file = _file.outfile.clone();
                 dest = _file.destination.clone();
+                compression = _file.compression.unwrap_or_default();
             }
             args::ExportSubCommands::Clipboard(_copy) => {
-                source = _copy.source.clone().unwrap();
+                source = _copy.source.clone().unwrap_or_default();
                 out_file = None;
                 dest = None;
+                compression = _copy.compression.unwrap_or_default();
             }
         }
 
@@ -46,14 +52,27 @@ impl Worker {
             source,
             out_file,
             dest,
+            compression,
         }
     }
 
     /// Main calling method for struct
     pub fn exec(&self) {
-        let mut progress_tracker = progress::Tracker::new_sync(progress::CommandType::Export);
+        let progress_tracker = progress::Tracker::new_sync(progress::CommandType::Export);
+
+        if self.source.is_empty() {
+            let mut unlocked = progress_tracker.lock().unwrap();
+            unlocked.error_additional(
+                "no source given, and no tree set in fbpconvert.toml".to_string(),
+            );
+            unlocked.complete();
+            exit(1);
+        }
 
         let source_path = PathBuf::from(&self.source);
+        for warning in manifest::export_warnings(&source_path) {
+            progress_tracker.lock().unwrap().msg(warning);
+        }
         let mut read_json_value = serde_json::json!({});
 
         match source_path.extension() {
@@ -116,7 +135,7 @@ impl Worker {
             None => {
                 // read blueprint book (recursive)
                 progress_tracker.lock().unwrap().read_books += 1;
-                match Worker::read_book_recursive(&mut progress_tracker, &source_path) {
+                match Worker::read_book_recursive(&progress_tracker, &source_path) {
                     Ok(json_object) => {
                         // remove unnecessary "index" key-val at head of bp
                         let strip_index = |json_object: Value| -> Result<Value, String> {
@@ -175,7 +194,7 @@ impl Worker {
         match &self.export_type {
             args::ExportSubCommands::File(_) => {
                 match self.write_blueprint_to_file(&read_json_value) {
-                    Ok(()) => (),
+                    Ok(deflate_stats) => Worker::report_sizes(&progress_tracker, &deflate_stats),
                     Err(err_msg) => {
                         progress_tracker.lock().unwrap().error_additional(err_msg);
                     }
@@ -184,17 +203,20 @@ impl Worker {
 
             args::ExportSubCommands::Clipboard(_) => {
                 let mut clipboard = ClipboardContext::new().unwrap();
-                match serde_json::to_string(&read_json_value) {
-                    Ok(blueprint_string) => {
-                        let blueprint_string_deflated =
-                            common::factorio_deflate(blueprint_string.as_ref());
-                        match clipboard.set_contents(blueprint_string_deflated.clone()) {
+                match common::factorio_deflate_to_writer(&read_json_value, self.compression, vec![])
+                {
+                    Ok((blueprint_string_deflated, deflate_stats)) => {
+                        // base64 output is always valid ascii
+                        match clipboard
+                            .set_contents(String::from_utf8(blueprint_string_deflated).unwrap())
+                        {
                             Ok(_) => {
                                 // for some reason there needs to be a small pause here
                                 // if not the clipboard contents are not copied over
                                 std::thread::sleep(std::time::Duration::from_millis(100));
                                 // progress::Tracker::pause(
                                 //     format!("Blueprint copied into clipboard. Paste the string before exiting."))
+                                Worker::report_sizes(&progress_tracker, &deflate_stats);
                             }
                             Err(_) => progress_tracker.lock().unwrap().error_additional(
                                 "failed to copy blueprint string to clipboard".to_string(),
@@ -204,7 +226,7 @@ impl Worker {
                     Err(_) => progress_tracker
                         .lock()
                         .unwrap()
-                        .error_additional("serde_json serialize error".to_string()),
+                        .error_additional("failed to deflate blueprint".to_string()),
                 }
             }
         }
@@ -215,7 +237,7 @@ impl Worker {
     /// Returns the complete blueprint JSON, given a file name.
     /// Returns an error message if an error occurs.
     /// This returns a generic Value data structure, so all types (books, planners) can be read through.
-    fn read_blueprint(bp_file_path: &PathBuf) -> Result<Value, String> {
+    pub(crate) fn read_blueprint(bp_file_path: &Path) -> Result<Value, String> {
         if !bp_file_path.is_file() {
             // println!("{:?}", bp_file_path);
             return Err(format!("{:?}: not a file", bp_file_path));
@@ -235,40 +257,32 @@ impl Worker {
         let bp_file = fs::read_to_string(bp_file_path);
 
         match bp_file {
-            Ok(file_contents) => {
-                let json_contents: serde_json::Value;
-                match serde_json::from_str(&file_contents.as_str()) {
-                    Ok(_contents) => json_contents = _contents,
-                    Err(_) => return Err("failed to serialize blueprint data".to_string()),
-                }
-
-                return Ok(json_contents);
-            }
-            Err(_) => {
-                return Err(bp_file_path.to_str().unwrap().to_string());
-            }
+            Ok(file_contents) => match serde_json::from_str(file_contents.as_str()) {
+                Ok(_contents) => Ok(_contents),
+                Err(_) => Err("failed to serialize blueprint data".to_string()),
+            },
+            Err(_) => Err(bp_file_path.to_str().unwrap().to_string()),
         }
     }
 
     /// Recursively searches the directory to rebuild the blueprint book
     /// Returns an error message if an error occurs
-    fn read_book_recursive(
-        prog_tracker: &mut Arc<Mutex<progress::Tracker>>,
-        bp_book_dir_path: &PathBuf,
+    pub(crate) fn read_book_recursive(
+        prog_tracker: &Arc<Mutex<progress::Tracker>>,
+        bp_book_dir_path: &Path,
     ) -> Result<Value, String> {
         // println!("reading: {:?}", &bp_book_dir_path);
 
         let book_name = bp_book_dir_path.file_name().unwrap().to_str().unwrap();
-        let mut dot_file_path = bp_book_dir_path.clone();
-        let current_dir_path = bp_book_dir_path.clone(); // used in recursion
-                                                         // set the dotfile path
+        let mut dot_file_path = bp_book_dir_path.to_path_buf();
+        let current_dir_path = bp_book_dir_path.to_path_buf(); // used in recursion
+                                                               // set the dotfile path
         dot_file_path.push(format!(".{}.json", book_name));
 
-        let dot_file_contents: String;
-        match fs::read_to_string(&dot_file_path) {
-            Ok(_file) => dot_file_contents = _file,
+        let dot_file_contents: String = match fs::read_to_string(&dot_file_path) {
+            Ok(_file) => _file,
             Err(_) => return Err("failed to read dotfile".to_string()),
-        }
+        };
 
         let book_object: Mutex<exportable::BookDotFileRecursive>; // actual variable modified here
         let book_object_dispatch: exportable::BookDotFileRecursive; // used for iterating only
@@ -286,205 +300,194 @@ impl Worker {
         // println!("{:#?}", book_object);
 
         // iterate through the list of stored blueprints
-        match &book_object_dispatch.blueprint_book.order {
-            Some(unknown_bps) => {
-                unknown_bps.par_iter().for_each(|unknown_blueprint| {
-                    // book
-                    if let Some(known_book) = &unknown_blueprint.blueprint_book {
-                        let mut known_book_path = current_dir_path.clone();
-                        known_book_path.push(&known_book.label);
-
-                        let known_book_object: Option<Value>;
-                        match Worker::read_book_recursive(
-                            &mut prog_tracker.clone(),
-                            &known_book_path,
-                        ) {
-                            Ok(_book_obj) => {
-                                known_book_object = Some(_book_obj);
-                                prog_tracker.lock().unwrap().ok(ProgressType::Book(
+        // items are stored under their label, unless the order entry names their file
+        if let Some(unknown_bps) = &book_object_dispatch.blueprint_book.order {
+            unknown_bps.par_iter().for_each(|unknown_blueprint| {
+                // book
+                if let Some(known_book) = &unknown_blueprint.blueprint_book {
+                    let mut known_book_path = current_dir_path.clone();
+                    known_book_path
+                        .push(unknown_blueprint.file.as_ref().unwrap_or(&known_book.label));
+
+                    let known_book_object: Option<Value>;
+                    match Worker::read_book_recursive(prog_tracker, &known_book_path) {
+                        Ok(_book_obj) => {
+                            known_book_object = Some(_book_obj);
+                            prog_tracker.lock().unwrap().ok(ProgressType::Book(
+                                known_book_path
+                                    .file_name()
+                                    .unwrap()
+                                    .to_str()
+                                    .unwrap()
+                                    .to_string(),
+                            ))
+                        }
+                        Err(err_msg) => {
+                            known_book_object = None;
+                            prog_tracker.lock().unwrap().error(
+                                ProgressType::Book(
                                     known_book_path
                                         .file_name()
                                         .unwrap()
                                         .to_str()
                                         .unwrap()
                                         .to_string(),
-                                ))
-                            }
-                            Err(err_msg) => {
-                                known_book_object = None;
-                                prog_tracker.lock().unwrap().error(
-                                    ProgressType::Book(
-                                        known_book_path
-                                            .file_name()
-                                            .unwrap()
-                                            .to_str()
-                                            .unwrap()
-                                            .to_string(),
-                                    ),
-                                    Some(err_msg),
-                                );
-                            }
+                                ),
+                                Some(err_msg),
+                            );
                         }
+                    }
 
-                        match known_book_object {
-                            Some(_book_obj) => {
-                                if let Some(blueprint_vec) =
-                                    &mut book_object.lock().unwrap().blueprint_book.blueprints
-                                {
-                                    blueprint_vec.push(_book_obj);
-                                }
-                            }
-                            None => (),
+                    if let Some(_book_obj) = known_book_object {
+                        if let Some(blueprint_vec) =
+                            &mut book_object.lock().unwrap().blueprint_book.blueprints
+                        {
+                            blueprint_vec.push(_book_obj);
                         }
                     }
+                }
 
-                    // blueprint
-                    if let Some(known_bp) = &unknown_blueprint.blueprint {
-                        let mut known_bp_path = current_dir_path.clone();
-                        known_bp_path.push(&known_bp.label);
-                        known_bp_path.set_extension("json");
-
-                        let known_bp_object: Option<Value>;
-                        match Worker::read_blueprint(&known_bp_path) {
-                            Ok(_bp_obj) => {
-                                known_bp_object = Some(_bp_obj);
-                                prog_tracker.lock().unwrap().ok(ProgressType::Blueprint(
+                // blueprint
+                if let Some(known_bp) = &unknown_blueprint.blueprint {
+                    let mut known_bp_path = current_dir_path.clone();
+                    known_bp_path.push(unknown_blueprint.file.as_ref().unwrap_or(&known_bp.label));
+                    known_bp_path.set_extension("json");
+
+                    let known_bp_object: Option<Value>;
+                    match Worker::read_blueprint(&known_bp_path) {
+                        Ok(_bp_obj) => {
+                            known_bp_object = Some(_bp_obj);
+                            prog_tracker.lock().unwrap().ok(ProgressType::Blueprint(
+                                known_bp_path
+                                    .file_name()
+                                    .unwrap()
+                                    .to_str()
+                                    .unwrap()
+                                    .to_string(),
+                            ))
+                        }
+                        Err(err_msg) => {
+                            known_bp_object = None;
+                            prog_tracker.lock().unwrap().error(
+                                ProgressType::Blueprint(
                                     known_bp_path
                                         .file_name()
                                         .unwrap()
                                         .to_str()
                                         .unwrap()
                                         .to_string(),
-                                ))
-                            }
-                            Err(err_msg) => {
-                                known_bp_object = None;
-                                prog_tracker.lock().unwrap().error(
-                                    ProgressType::Blueprint(
-                                        known_bp_path
-                                            .file_name()
-                                            .unwrap()
-                                            .to_str()
-                                            .unwrap()
-                                            .to_string(),
-                                    ),
-                                    Some(err_msg),
-                                )
-                            }
+                                ),
+                                Some(err_msg),
+                            )
                         }
+                    }
 
-                        match known_bp_object {
-                            Some(_bp_obj) => {
-                                if let Some(blueprint_vec) =
-                                    &mut book_object.lock().unwrap().blueprint_book.blueprints
-                                {
-                                    blueprint_vec.push(_bp_obj);
-                                }
-                            }
-                            None => (),
+                    if let Some(_bp_obj) = known_bp_object {
+                        if let Some(blueprint_vec) =
+                            &mut book_object.lock().unwrap().blueprint_book.blueprints
+                        {
+                            blueprint_vec.push(_bp_obj);
                         }
                     }
+                }
 
-                    // upgrade planner
-                    if let Some(known_bp) = &unknown_blueprint.upgrade_planner {
-                        let mut known_bp_path = current_dir_path.clone();
-                        known_bp_path.push(&known_bp.label);
-                        known_bp_path.set_extension("json");
-
-                        let known_bp_object: Option<Value>;
-                        match Worker::read_blueprint(&known_bp_path) {
-                            Ok(_bp_obj) => {
-                                known_bp_object = Some(_bp_obj);
-                                prog_tracker
-                                    .lock()
-                                    .unwrap()
-                                    .ok(ProgressType::UpgradePlanner(
-                                        known_bp_path
-                                            .file_name()
-                                            .unwrap()
-                                            .to_str()
-                                            .unwrap()
-                                            .to_string(),
-                                    ))
-                            }
-                            Err(err_msg) => {
-                                known_bp_object = None;
-                                prog_tracker.lock().unwrap().error(
-                                    ProgressType::UpgradePlanner(
-                                        known_bp_path
-                                            .file_name()
-                                            .unwrap()
-                                            .to_str()
-                                            .unwrap()
-                                            .to_string(),
-                                    ),
-                                    Some(err_msg),
-                                )
-                            }
+                // upgrade planner
+                if let Some(known_bp) = &unknown_blueprint.upgrade_planner {
+                    let mut known_bp_path = current_dir_path.clone();
+                    known_bp_path.push(unknown_blueprint.file.as_ref().unwrap_or(&known_bp.label));
+                    known_bp_path.set_extension("json");
+
+                    let known_bp_object: Option<Value>;
+                    match Worker::read_blueprint(&known_bp_path) {
+                        Ok(_bp_obj) => {
+                            known_bp_object = Some(_bp_obj);
+                            prog_tracker
+                                .lock()
+                                .unwrap()
+                                .ok(ProgressType::UpgradePlanner(
+                                    known_bp_path
+                                        .file_name()
+                                        .unwrap()
+                                        .to_str()
+                                        .unwrap()
+                                        .to_string(),
+                                ))
+                        }
+                        Err(err_msg) => {
+                            known_bp_object = None;
+                            prog_tracker.lock().unwrap().error(
+                                ProgressType::UpgradePlanner(
+                                    known_bp_path
+                                        .file_name()
+                                        .unwrap()
+                                        .to_str()
+                                        .unwrap()
+                                        .to_string(),
+                                ),
+                                Some(err_msg),
+                            )
                         }
+                    }
 
-                        match known_bp_object {
-                            Some(_bp_obj) => {
-                                if let Some(blueprint_vec) =
-                                    &mut book_object.lock().unwrap().blueprint_book.blueprints
-                                {
-                                    blueprint_vec.push(_bp_obj);
-                                }
-                            }
-                            None => (),
+                    if let Some(_bp_obj) = known_bp_object {
+                        if let Some(blueprint_vec) =
+                            &mut book_object.lock().unwrap().blueprint_book.blueprints
+                        {
+                            blueprint_vec.push(_bp_obj);
                         }
                     }
+                }
 
-                    // decon planner
-                    if let Some(known_bp) = &unknown_blueprint.deconstruction_planner {
-                        let mut known_bp_path = current_dir_path.clone();
-                        known_bp_path.push(&known_bp.label);
-                        known_bp_path.set_extension("json");
-
-                        let known_bp_object: Option<Value>;
-                        match Worker::read_blueprint(&known_bp_path) {
-                            Ok(_bp_obj) => {
-                                known_bp_object = Some(_bp_obj);
-                                prog_tracker.lock().unwrap().ok(ProgressType::DeconPlanner(
+                // decon planner
+                if let Some(known_bp) = &unknown_blueprint.deconstruction_planner {
+                    let mut known_bp_path = current_dir_path.clone();
+                    known_bp_path.push(unknown_blueprint.file.as_ref().unwrap_or(&known_bp.label));
+                    known_bp_path.set_extension("json");
+
+                    let known_bp_object: Option<Value>;
+                    match Worker::read_blueprint(&known_bp_path) {
+                        Ok(_bp_obj) => {
+                            known_bp_object = Some(_bp_obj);
+                            prog_tracker.lock().unwrap().ok(ProgressType::DeconPlanner(
+                                known_bp_path
+                                    .file_name()
+                                    .unwrap()
+                                    .to_str()
+                                    .unwrap()
+                                    .to_string(),
+                            ))
+                        }
+                        Err(err_msg) => {
+                            known_bp_object = None;
+                            prog_tracker.lock().unwrap().error(
+                                ProgressType::DeconPlanner(
                                     known_bp_path
                                         .file_name()
                                         .unwrap()
                                         .to_str()
                                         .unwrap()
                                         .to_string(),
-                                ))
-                            }
-                            Err(err_msg) => {
-                                known_bp_object = None;
-                                prog_tracker.lock().unwrap().error(
-                                    ProgressType::DeconPlanner(
-                                        known_bp_path
-                                            .file_name()
-                                            .unwrap()
-                                            .to_str()
-                                            .unwrap()
-                                            .to_string(),
-                                    ),
-                                    Some(err_msg),
-                                )
-                            }
+                                ),
+                                Some(err_msg),
+                            )
                         }
+                    }
 
-                        match known_bp_object {
-                            Some(_bp_obj) => {
-                                if let Some(blueprint_vec) =
-                                    &mut book_object.lock().unwrap().blueprint_book.blueprints
-                                {
-                                    blueprint_vec.push(_bp_obj);
-                                }
-                            }
-                            None => (),
+                    if let Some(_bp_obj) = known_bp_object {
+                        if let Some(blueprint_vec) =
+                            &mut book_object.lock().unwrap().blueprint_book.blueprints
+                        {
+                            blueprint_vec.push(_bp_obj);
                         }
                     }
-                });
-            }
+                }
+            });
+        }
 
-            None => (),
+        // children are read in parallel, restore their slot order
+        let mut book_object = book_object.into_inner().unwrap();
+        if let Some(blueprint_vec) = &mut book_object.blueprint_book.blueprints {
+            blueprint_vec.sort_by_key(common::slot_index);
         }
 
         match serde_json::to_value(book_object) {
@@ -493,9 +496,23 @@ impl Worker {
         }
     }
 
+    /// Prints the size of the blueprint at each stage of deflating
+    fn report_sizes(
+        prog_tracker: &Arc<Mutex<progress::Tracker>>,
+        deflate_stats: &common::DeflateStats,
+    ) {
+        prog_tracker.lock().unwrap().msg(format!(
+            "raw {} bytes, compressed {} bytes, base64 {} bytes",
+            deflate_stats.raw, deflate_stats.compressed, deflate_stats.encoded
+        ));
+    }
+
     /// Takes the blueprint and writes it to a destination.
-    /// Returns an error message if it occurs
-    pub fn write_blueprint_to_file(&self, blueprint_json: &Value) -> Result<(), String> {
+    /// Returns the size of each deflate stage, or an error message if it occurs
+    pub fn write_blueprint_to_file(
+        &self,
+        blueprint_json: &Value,
+    ) -> Result<common::DeflateStats, String> {
         let mut write_dest: PathBuf = PathBuf::new();
         if let Some(_dir) = &self.dest {
             write_dest.push(_dir);
@@ -504,7 +521,7 @@ impl Worker {
             write_dest.push(_file);
         } else {
             let file_name: String;
-            match common::BlueprintType::classify(&blueprint_json) {
+            match common::BlueprintType::classify(blueprint_json) {
                 common::BlueprintType::Invalid => {
                     return Err("failed to determine blueprint type".to_string())
                 }
@@ -521,20 +538,26 @@ impl Worker {
                     file_name = name;
                 }
             }
-            write_dest.push(format!("{}{}", PREFIX_OUT, file_name));
+            write_dest.push(common::file_rename(format!("{}{}", PREFIX_OUT, file_name)));
         }
 
-        match serde_json::to_string(blueprint_json) {
-            Ok(blueprint_string) => {
-                let blueprint_string_deflated = common::factorio_deflate(blueprint_string.as_ref());
-                match fs::write(write_dest, blueprint_string_deflated.as_bytes()) {
-                    Ok(_) => return Ok(()),
-                    Err(_) => {
-                        return Err("file write error".to_string());
-                    }
-                }
-            }
-            Err(_) => return Err("serde_json serialize error".to_string()),
+        let out_file = match File::create(write_dest) {
+            Ok(_file) => _file,
+            Err(_) => return Err("file creation error".to_string()),
+        };
+
+        // stream the blueprint string straight into the file
+        match common::factorio_deflate_to_writer(
+            blueprint_json,
+            self.compression,
+            BufWriter::new(out_file),
+        )
+        .and_then(|(mut buf_writer, deflate_stats)| {
+            buf_writer.flush()?;
+            Ok(deflate_stats)
+        }) {
+            Ok(deflate_stats) => Ok(deflate_stats),
+            Err(_) => Err("file write error".to_string()),
         }
     }
 }
@@ -0,0 +1,216 @@
+//! Extracts a single blueprint, book or planner out of a larger book.
+
+use std::process::exit;
+
+use serde_json::Value;
+
+use crate::args;
+use crate::common::{self, BlueprintType, CompressionLevel};
+use crate::destination::Destination;
+use crate::progress::{self, CommandType};
+use crate::source::Source;
+
+/// Separates the segments of an item path
+pub const PATH_SEPARATOR: char = '/';
+
+pub struct Worker {
+    pub source: Source,
+    pub path: String,
+    pub destination: Destination,
+    pub compression: CompressionLevel,
+}
+
+impl Worker {
+    pub fn from(extract_args: &args::Extract) -> Worker {
+        Worker {
+            source: Source::from(&extract_args.source),
+            path: extract_args.path.clone(),
+            destination: Destination::from(&extract_args.output),
+            compression: extract_args.output.compression.unwrap_or_default(),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let extract_error = |err_msg: String| -> ! {
+            progress::Tracker::new_quiet_sync(CommandType::Extract)
+                .lock()
+                .unwrap()
+                .error_additional(err_msg);
+            exit(1);
+        };
+
+        let blueprint_obj = match self.source.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => extract_error(err_msg),
+        };
+
+        let mut extracted = match resolve(&blueprint_obj, &self.path) {
+            Ok(_item) => _item.clone(),
+            Err(err_msg) => extract_error(err_msg),
+        };
+        drop(blueprint_obj);
+
+        // the item is no longer inside a book
+        if let Some(head) = extracted.as_object_mut() {
+            head.remove("index");
+        }
+
+        if let Err(err_msg) = self.destination.write(extracted, self.compression) {
+            extract_error(err_msg);
+        }
+    }
+}
+
+/// Resolves a path to an item inside a book.
+///
+/// Each segment of the path selects a child of the current book, either by its slot index
+/// or by its label. Numeric segments are matched against slot indices first.
+/// The path may start with the label of the top-level book itself.
+pub fn resolve<'a>(root: &'a Value, path: &str) -> Result<&'a Value, String> {
+    let segments: Vec<&str> = path
+        .split(PATH_SEPARATOR)
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    match resolve_segments(root, &segments) {
+        Ok(_item) => Ok(_item),
+        Err(err_msg) => match (BlueprintType::classify(root), segments.split_first()) {
+            (BlueprintType::Book(label), Some((first, rest))) if label_matches(&label, first) => {
+                resolve_segments(root, rest)
+            }
+            _ => Err(err_msg),
+        },
+    }
+}
+
+/// Follows the path segments from the book down to the selected item
+fn resolve_segments<'a>(root: &'a Value, segments: &[&str]) -> Result<&'a Value, String> {
+    let mut current = root;
+    let mut resolved: Vec<&str> = vec![];
+
+    for segment in segments {
+        let children = match common::book_children(current) {
+            Some(_children) => _children,
+            None => {
+                return Err(format!(
+                    "\"{}\" is not a book",
+                    resolved.join(&PATH_SEPARATOR.to_string())
+                ))
+            }
+        };
+
+        let by_index = segment.parse::<u64>().ok().and_then(|index| {
+            children
+                .iter()
+                .find(|c| common::slot_index(c) == Some(index))
+        });
+        let by_label = || {
+            children
+                .iter()
+                .find(|child| match BlueprintType::classify(child) {
+                    BlueprintType::Book(label)
+                    | BlueprintType::Blueprint(label)
+                    | BlueprintType::UpgradePlanner(label)
+                    | BlueprintType::DeconPlanner(label) => label_matches(&label, segment),
+                    BlueprintType::Invalid => false,
+                })
+        };
+
+        current = match by_index.or_else(by_label) {
+            Some(_child) => _child,
+            None => {
+                resolved.push(segment);
+                return Err(format!(
+                    "no item at \"{}\"",
+                    resolved.join(&PATH_SEPARATOR.to_string())
+                ));
+            }
+        };
+        resolved.push(segment);
+    }
+
+    Ok(current)
+}
+
+/// Compares a label with a path segment.
+/// Segments may also be given as file names created by import.
+fn label_matches(label: &str, segment: &str) -> bool {
+    label == segment
+        || common::file_rename(label.to_string()) == segment
+        || common::readable_file_name(label) == segment
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn book() -> Value {
+        json!({
+            "blueprint_book": {
+                "label": "Book",
+                "active_index": 0,
+                "version": 1,
+                "blueprints": [
+                    {"index": 3, "blueprint_book": {
+                        "label": "Sub book",
+                        "active_index": 0,
+                        "version": 1,
+                        "blueprints": [
+                            {"index": 1, "blueprint": {"label": "Blueprint", "version": 1}},
+                            {"index": 7, "blueprint": {"label": "2", "version": 1}},
+                            {"index": 2, "blueprint": {"label": "Slot two", "version": 1}}
+                        ]
+                    }},
+                    {"index": 0, "blueprint": {"label": "Top level", "version": 1}}
+                ]
+            }
+        })
+    }
+
+    fn label_of(item: &Value) -> String {
+        match BlueprintType::classify(item) {
+            BlueprintType::Book(label) | BlueprintType::Blueprint(label) => label,
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_label_path() {
+        let book = book();
+        assert_eq!(
+            label_of(resolve(&book, "Book/Sub book/Blueprint").unwrap()),
+            "Blueprint"
+        );
+        assert_eq!(
+            label_of(resolve(&book, "Sub book/Blueprint").unwrap()),
+            "Blueprint"
+        );
+        assert_eq!(
+            label_of(resolve(&book, "Book/Sub_book").unwrap()),
+            "Sub book"
+        );
+        assert_eq!(label_of(resolve(&book, "Top level").unwrap()), "Top level");
+    }
+
+    #[test]
+    fn test_resolve_index_path() {
+        let book = book();
+        assert_eq!(label_of(resolve(&book, "3/1").unwrap()), "Blueprint");
+        assert_eq!(label_of(resolve(&book, "0").unwrap()), "Top level");
+        // slot indices take precedence over labels
+        assert_eq!(label_of(resolve(&book, "3/2").unwrap()), "Slot two");
+        assert_eq!(label_of(resolve(&book, "3/7").unwrap()), "2");
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let book = book();
+        assert_eq!(
+            resolve(&book, "Sub book/missing").unwrap_err(),
+            "no item at \"Sub book/missing\""
+        );
+        assert_eq!(resolve(&book, "0/1").unwrap_err(), "\"0\" is not a book");
+    }
+}
@@ -7,7 +7,6 @@ use serde::{Deserialize, Serialize};
 /// Typedef for arbitiary inner array data structure
 type InnerArray = Vec<serde_json::Value>;
 
-#[allow(dead_code)]
 pub const FACTORIO_BP_BOOK_KEY: &str = "blueprint_book";
 pub const FACTORIO_BP_KEY: &str = "blueprint";
 pub const FACTORIO_UP_PLANNER_KEY: &str = "upgrade_planner";
@@ -247,4 +246,33 @@ pub struct UnknownBlueprintType {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub index: Option<u16>,
+
+    /// Name of the item's file or directory in a tree, if it differs from the label
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Position of an entity or tile in a blueprint
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Entity in a blueprint, only the attributes this program reads.
+/// Entities are kept as raw JSON elsewhere so that no attribute is lost.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entity {
+    pub name: String,
+    pub position: Position,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<u8>,
+}
+
+/// Tile in a blueprint
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tile {
+    pub name: String,
+    pub position: Position,
 }
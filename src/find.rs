@@ -0,0 +1,267 @@
+//! Searches the blueprints of a book for entities, circuit signals or text.
+
+use std::process::exit;
+
+use crossterm::style::Stylize;
+use serde_json::Value;
+
+use crate::args;
+use crate::common;
+use crate::progress::{self, CommandType};
+use crate::source::Source;
+
+pub struct Worker {
+    /// Where to search, None if neither given nor set in fbpconvert.toml
+    pub source: Option<Source>,
+    pub query: Query,
+}
+
+/// Search criteria. Each criterion is checked on its own, then combined with AND or OR
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    /// Entity names, also matched against the recipe set in an entity
+    pub entities: Vec<String>,
+    /// Signal names used in the circuit settings of an entity
+    pub signals: Vec<String>,
+    /// Text in a label of the blueprint or its books, or in the blueprint's description.
+    /// Not case sensitive
+    pub texts: Vec<String>,
+    /// Match blueprints meeting any criterion instead of all of them
+    pub any: bool,
+}
+
+/// A blueprint that meets the query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Labels of the enclosing books and the blueprint, separated by "/"
+    pub path: String,
+    /// Entities matching an entity or signal criterion, all entities if there are none
+    pub entities: usize,
+}
+
+impl Query {
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty() && self.signals.is_empty() && self.texts.is_empty()
+    }
+
+    /// Checks a blueprint object (the value under the "blueprint" key) against the query.
+    /// `path` holds the labels of the enclosing books and the blueprint
+    pub fn matches(&self, path: &[String], blueprint: &Value) -> Option<Match> {
+        let entities = blueprint
+            .get("entities")
+            .and_then(|entities| entities.as_array())
+            .map(|entities| entities.as_slice())
+            .unwrap_or_default();
+
+        let entity_hits = self
+            .entities
+            .iter()
+            .map(|name| entities.iter().any(|entity| entity_is(entity, name)));
+        let signal_hits = self.signals.iter().map(|name| {
+            entities
+                .iter()
+                .any(|entity| entity_uses_signal(entity, name))
+        });
+        let text_hits = self
+            .texts
+            .iter()
+            .map(|text| blueprint_has_text(path, blueprint, text));
+
+        let mut criteria = entity_hits.chain(signal_hits).chain(text_hits);
+        let matched = match self.any {
+            true => criteria.any(|hit| hit),
+            false => criteria.all(|hit| hit),
+        };
+        if !matched {
+            return None;
+        }
+
+        let matching_entities = match self.entities.is_empty() && self.signals.is_empty() {
+            true => entities.len(),
+            false => entities
+                .iter()
+                .filter(|entity| {
+                    self.entities.iter().any(|name| entity_is(entity, name))
+                        || self
+                            .signals
+                            .iter()
+                            .any(|name| entity_uses_signal(entity, name))
+                })
+                .count(),
+        };
+
+        Some(Match {
+            path: path.join("/"),
+            entities: matching_entities,
+        })
+    }
+}
+
+/// Checks the name of an entity, or the recipe set in it
+fn entity_is(entity: &Value, name: &str) -> bool {
+    ["name", "recipe"]
+        .iter()
+        .any(|key| entity.get(key).and_then(|value| value.as_str()) == Some(name))
+}
+
+/// Checks the circuit settings of an entity for a signal
+fn entity_uses_signal(entity: &Value, name: &str) -> bool {
+    fn contains_signal(value: &Value, name: &str) -> bool {
+        match value {
+            Value::Object(object) => {
+                object.get("name").and_then(|n| n.as_str()) == Some(name)
+                    || object.values().any(|inner| contains_signal(inner, name))
+            }
+            Value::Array(array) => array.iter().any(|inner| contains_signal(inner, name)),
+            _ => false,
+        }
+    }
+
+    entity
+        .get("control_behavior")
+        .is_some_and(|control_behavior| contains_signal(control_behavior, name))
+}
+
+/// Checks the labels of the path and the description of a blueprint for text
+fn blueprint_has_text(path: &[String], blueprint: &Value, text: &str) -> bool {
+    let text = text.to_lowercase();
+    let description = blueprint.get("description").and_then(|d| d.as_str());
+
+    path.iter()
+        .map(String::as_str)
+        .chain(description)
+        .any(|haystack| haystack.to_lowercase().contains(&text))
+}
+
+impl Worker {
+    pub fn from(find_args: &args::Find) -> Worker {
+        Worker {
+            source: find_args.source.as_deref().map(Source::from),
+            query: Query {
+                entities: find_args.entity.clone(),
+                signals: find_args.signal.clone(),
+                texts: find_args.text.clone(),
+                any: find_args.any,
+            },
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let find_error = |err_msg: String| -> ! {
+            progress::Tracker::new_quiet_sync(CommandType::Find)
+                .lock()
+                .unwrap()
+                .error_additional(err_msg);
+            exit(1);
+        };
+
+        if self.query.is_empty() {
+            find_error("nothing to search for, give --entity, --signal or --text".to_string());
+        }
+
+        let source = match &self.source {
+            Some(_source) => _source,
+            None => find_error("no source given, and no tree set in fbpconvert.toml".to_string()),
+        };
+        let blueprint_obj = match source.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => find_error(err_msg),
+        };
+
+        let mut searched = 0;
+        let mut matches = vec![];
+        common::visit_blueprints(&blueprint_obj, &mut |path, blueprint| {
+            searched += 1;
+            matches.extend(self.query.matches(path, blueprint));
+        });
+
+        for found in &matches {
+            println!(
+                "{}  {}",
+                found.path.clone().green().bold(),
+                format!("entities {}", found.entities).dim()
+            );
+        }
+        println!("{} of {} blueprints match", matches.len(), searched);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn blueprint() -> Value {
+        json!({
+            "label": "Red science",
+            "description": "Early game SMELTING and gears",
+            "version": 1,
+            "entities": [
+                {"entity_number": 1, "name": "assembling-machine-2", "recipe": "iron-gear-wheel"},
+                {"entity_number": 2, "name": "beacon"},
+                {"entity_number": 3, "name": "beacon"},
+                {"entity_number": 4, "name": "inserter", "control_behavior": {
+                    "circuit_condition": {
+                        "first_signal": {"type": "virtual", "name": "signal-A"},
+                        "constant": 10,
+                        "comparator": "<"
+                    }
+                }}
+            ]
+        })
+    }
+
+    fn path() -> Vec<String> {
+        vec!["Main bus".to_string(), "Red science".to_string()]
+    }
+
+    fn query(entities: &[&str], signals: &[&str], texts: &[&str], any: bool) -> Query {
+        let owned = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        Query {
+            entities: owned(entities),
+            signals: owned(signals),
+            texts: owned(texts),
+            any,
+        }
+    }
+
+    #[test]
+    fn test_find_each_criterion() {
+        let found = query(&["beacon"], &[], &[], false)
+            .matches(&path(), &blueprint())
+            .unwrap();
+        assert_eq!(found.path, "Main bus/Red science");
+        assert_eq!(found.entities, 2);
+
+        // recipes are matched like entity names
+        assert!(query(&["iron-gear-wheel"], &[], &[], false)
+            .matches(&path(), &blueprint())
+            .is_some());
+        assert!(query(&[], &["signal-A"], &[], false)
+            .matches(&path(), &blueprint())
+            .is_some_and(|found| found.entities == 1));
+        assert!(query(&[], &["signal-B"], &[], false)
+            .matches(&path(), &blueprint())
+            .is_none());
+
+        // labels of enclosing books and descriptions, not case sensitive
+        let by_text = query(&[], &[], &["main BUS"], false);
+        assert_eq!(by_text.matches(&path(), &blueprint()).unwrap().entities, 4);
+        assert!(query(&[], &[], &["smelting"], false)
+            .matches(&path(), &blueprint())
+            .is_some());
+    }
+
+    #[test]
+    fn test_find_and_or() {
+        let all = query(&["beacon", "pipe"], &["signal-A"], &[], false);
+        assert!(all.matches(&path(), &blueprint()).is_none());
+
+        let any = query(&["beacon", "pipe"], &["signal-A"], &[], true);
+        assert_eq!(any.matches(&path(), &blueprint()).unwrap().entities, 3);
+
+        let all_present = query(&["beacon"], &["signal-A"], &["science"], false);
+        assert!(all_present.matches(&path(), &blueprint()).is_some());
+    }
+}
@@ -0,0 +1,329 @@
+//! Commits imported trees to the git repository they are in.
+//!
+//! The commit message lists the added, changed and removed items by their book path,
+//! from the difference between the manifests written before and after the import.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::factorio_structs;
+use crate::manifest::{self, Manifest, MANIFEST_FILE_NAME};
+
+/// Runs git in the directory, returning its output
+fn git(dir: &Path, git_args: &[&str]) -> Result<String, String> {
+    let output = match Command::new("git").args(git_args).current_dir(dir).output() {
+        Ok(_output) => _output,
+        Err(_) => return Err("failed to run git, is it installed?".to_string()),
+    };
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+        false => Err(format!(
+            "git {}: {}",
+            git_args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+/// An import into a directory of a git repository, committed once written
+pub struct ImportCommit {
+    dir: PathBuf,
+    /// The tree before the import
+    old: Manifest,
+}
+
+impl ImportCommit {
+    /// Checks that the directory is in a git repository without uncommitted changes,
+    /// then removes the files a previous import wrote for the item, so that items no longer
+    /// in it are removed from the tree. `item_name` is the file name of the imported item
+    pub fn prepare(dir: &Path, item_name: &str) -> Result<ImportCommit, String> {
+        git(dir, &["rev-parse", "--is-inside-work-tree"])
+            .map_err(|_| format!("{:?} is not inside a git repository", dir))?;
+        if !git(dir, &["status", "--porcelain", "--", "."])?.is_empty() {
+            return Err(format!(
+                "{:?} has uncommitted changes, commit or stash them first",
+                dir
+            ));
+        }
+
+        let old = match Manifest::read(dir)? {
+            Some(_manifest) => _manifest,
+            None => Manifest::build(dir)?,
+        };
+
+        // every file is committed, so the ones removed here can be restored from git.
+        // Files that are not items are not written by import, they are kept
+        let mut removed_dirs: Vec<PathBuf> = vec![];
+        for (path, entry) in &old.files {
+            if manifest::is_item_path(path, item_name)
+                && entry.item_type != manifest::INVALID_ITEM_TYPE
+            {
+                let file_path = dir.join(path);
+                if fs::remove_file(&file_path).is_err() {
+                    return Err(format!("failed to remove {:?}", file_path));
+                }
+                removed_dirs.extend(file_path.parent().map(Path::to_path_buf));
+            }
+        }
+        // deepest directories first, directories still holding other files are kept
+        removed_dirs.sort_by_key(|removed_dir| {
+            (
+                std::cmp::Reverse(removed_dir.components().count()),
+                removed_dir.clone(),
+            )
+        });
+        removed_dirs.dedup();
+        for removed_dir in removed_dirs {
+            let _ = fs::remove_dir(removed_dir);
+        }
+
+        Ok(ImportCommit {
+            dir: dir.to_path_buf(),
+            old,
+        })
+    }
+
+    /// Commits the import if all of its files were written. Otherwise nothing is committed and
+    /// the directory is restored to its last commit, files removed by prepare included
+    pub fn finish(&self, label: &str, written: bool) -> Result<Option<String>, String> {
+        if written {
+            return self.commit(label);
+        }
+        self.restore()?;
+        Err(format!(
+            "import failed, {:?} was restored to its last commit",
+            self.dir
+        ))
+    }
+
+    /// Stages the directory and commits it with a message listing the changes.
+    /// Returns the message, or None if the import changed nothing
+    fn commit(&self, label: &str) -> Result<Option<String>, String> {
+        let new = match Manifest::read(&self.dir)? {
+            Some(_manifest) => _manifest,
+            None => return Err(format!("no {} in {:?}", MANIFEST_FILE_NAME, self.dir)),
+        };
+        let message = match commit_message(label, &self.old, &new) {
+            Some(_message) => _message,
+            None => return Ok(None),
+        };
+
+        git(&self.dir, &["add", "--all", "--", "."])?;
+        git(&self.dir, &["commit", "--quiet", "-m", &message, "--", "."])?;
+        Ok(Some(message))
+    }
+
+    /// Discards every change made to the directory since prepare checked it was clean
+    fn restore(&self) -> Result<(), String> {
+        // checkout refuses a directory without tracked files
+        if !git(&self.dir, &["ls-files", "--", "."])?.is_empty() {
+            git(&self.dir, &["checkout", "--quiet", "--", "."])?;
+        }
+        git(&self.dir, &["clean", "--quiet", "-d", "--force", "--", "."])?;
+        Ok(())
+    }
+}
+
+/// Commit message for an import changing the tree from the old manifest to the new one.
+/// Books are only listed as changed when none of their items are
+pub fn commit_message(label: &str, old: &Manifest, new: &Manifest) -> Option<String> {
+    let changes = old.changes(new);
+    if changes.is_empty() {
+        return None;
+    }
+
+    let added: Vec<String> = changes
+        .added
+        .iter()
+        .map(|path| book_path(new, path))
+        .collect();
+    let removed: Vec<String> = changes
+        .deleted
+        .iter()
+        .map(|path| book_path(old, path))
+        .collect();
+    let mut changed: Vec<String> = changes
+        .modified
+        .iter()
+        .filter(|path| !is_book(new, path))
+        .map(|path| book_path(new, path))
+        .collect();
+    let books: Vec<String> = changes
+        .modified
+        .iter()
+        .filter(|path| is_book(new, path))
+        .map(|path| book_path(new, path))
+        .filter(|book| {
+            !added
+                .iter()
+                .chain(&removed)
+                .chain(&changed)
+                .any(|item| item.starts_with(book.as_str()))
+        })
+        .collect();
+    changed.extend(books);
+    changed.sort();
+
+    let mut counts = vec![];
+    let mut body = String::new();
+    for (heading, items) in [
+        ("added", &added),
+        ("changed", &changed),
+        ("removed", &removed),
+    ] {
+        if items.is_empty() {
+            continue;
+        }
+        counts.push(format!("{} {}", items.len(), heading));
+        body.push_str(&format!(
+            "\n{}{}:\n",
+            heading[..1].to_uppercase(),
+            &heading[1..]
+        ));
+        for item in items {
+            body.push_str(&format!("- {}\n", item));
+        }
+    }
+
+    Some(format!("Import {}: {}\n{}", label, counts.join(", "), body))
+}
+
+fn is_book(manifest: &Manifest, path: &str) -> bool {
+    manifest
+        .files
+        .get(path)
+        .is_some_and(|entry| entry.item_type == factorio_structs::FACTORIO_BP_BOOK_KEY)
+}
+
+/// Labels of the books above an item followed by its own label, "Book/Sub book/Blueprint".
+/// Books end with a slash
+fn book_path(manifest: &Manifest, path: &str) -> String {
+    let parts: Vec<&str> = path.split('/').collect();
+    let mut labels: Vec<String> = (1..parts.len())
+        .map(|depth| {
+            let dot_file = format!("{}/.{}.json", parts[..depth].join("/"), parts[depth - 1]);
+            match manifest.files.get(&dot_file) {
+                Some(_book) => _book.label.clone(),
+                None => parts[depth - 1].to_string(),
+            }
+        })
+        .collect();
+
+    match manifest.files.get(path) {
+        Some(_entry) if is_book(manifest, path) => labels.push(String::new()),
+        Some(_entry) if _entry.item_type != manifest::INVALID_ITEM_TYPE => {
+            labels.push(_entry.label.clone())
+        }
+        _ => labels.push(parts.last().unwrap_or(&"").to_string()),
+    }
+    labels.join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::progress::{self, CommandType};
+    use crate::{common, import};
+    use serde_json::{json, Value};
+
+    fn book(blueprints: Value) -> Value {
+        json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "label": "outer",
+                "active_index": 0,
+                "version": 1,
+                "blueprints": blueprints
+            }
+        })
+    }
+
+    fn import_commit(dir: &Path, book: Value) -> Option<String> {
+        let commit = ImportCommit::prepare(dir, "outer").unwrap();
+        let tracker = progress::Tracker::new_quiet_sync(CommandType::Import);
+        import::Worker::unknown_write(&tracker, book, dir, common::TreeStyle::default());
+        manifest::update(dir, "outer").unwrap();
+        commit.finish("outer", true).unwrap()
+    }
+
+    #[test]
+    fn test_import_commit() {
+        let repo = common::TestDir::new("git");
+        git(&repo, &["init", "--quiet"]).unwrap();
+        git(&repo, &["config", "user.name", "test"]).unwrap();
+        git(&repo, &["config", "user.email", "test@example.com"]).unwrap();
+        git(&repo, &["config", "commit.gpgsign", "false"]).unwrap();
+        let tree = repo.join("tree");
+        fs::create_dir_all(&tree).unwrap();
+
+        let first = book(json!([
+            {"index": 0, "blueprint": {"item": "blueprint", "label": "a", "version": 1}},
+            {"index": 1, "blueprint_book": {
+                "item": "blueprint-book", "label": "inner", "active_index": 0, "version": 1,
+                "blueprints": [
+                    {"index": 0, "blueprint": {"item": "blueprint", "label": "b", "version": 1}}
+                ]
+            }}
+        ]));
+        let message = import_commit(&tree, first.clone()).unwrap();
+        assert!(message.starts_with("Import outer: 4 added\n"));
+        assert!(message.contains("- outer/inner/b\n"));
+        assert!(message.contains("- outer/inner/\n"));
+
+        // importing the same book again changes nothing
+        assert_eq!(import_commit(&tree, first), None);
+
+        let second = book(json!([
+            {"index": 0, "blueprint": {"item": "blueprint", "label": "a", "version": 2}},
+            {"index": 1, "blueprint_book": {
+                "item": "blueprint-book", "label": "inner", "active_index": 0, "version": 1,
+                "blueprints": []
+            }},
+            {"index": 2, "blueprint": {"item": "blueprint", "label": "c", "version": 1}}
+        ]));
+        let message = import_commit(&tree, second).unwrap();
+        assert_eq!(
+            message,
+            "Import outer: 1 added, 1 changed, 1 removed\n\
+             \nAdded:\n- outer/c\n\
+             \nChanged:\n- outer/a\n\
+             \nRemoved:\n- outer/inner/b\n"
+        );
+        assert!(!tree.join("outer").join("inner").join("b.json").exists());
+
+        let log = git(&repo, &["log", "--format=%s"]).unwrap();
+        assert_eq!(
+            log.lines().collect::<Vec<_>>(),
+            vec![
+                "Import outer: 1 added, 1 changed, 1 removed",
+                "Import outer: 4 added"
+            ]
+        );
+        assert!(git(&repo, &["status", "--porcelain"]).unwrap().is_empty());
+
+        // a failed write creates no commit and restores the tree
+        let commit = ImportCommit::prepare(&tree, "outer").unwrap();
+        assert!(!tree.join("outer").exists());
+        fs::write(tree.join("outer"), "").unwrap();
+        let tracker = progress::Tracker::new_quiet_sync(CommandType::Import);
+        import::Worker::unknown_write(
+            &tracker,
+            book(json!([])),
+            &tree,
+            common::TreeStyle::default(),
+        );
+        assert_eq!(tracker.lock().unwrap().errors, 1);
+        assert!(commit.finish("outer", false).is_err());
+        assert_eq!(
+            git(&repo, &["log", "--format=%s"]).unwrap().lines().count(),
+            2
+        );
+        assert!(tree.join("outer").join("c.json").is_file());
+        assert!(git(&repo, &["status", "--porcelain"]).unwrap().is_empty());
+
+        // uncommitted changes are refused
+        fs::write(tree.join("stray.txt"), "").unwrap();
+        assert!(ImportCommit::prepare(&tree, "outer").is_err());
+    }
+}
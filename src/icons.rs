@@ -0,0 +1,472 @@
+//! Sets the icons of the blueprints and books of a tree, in place.
+//!
+//! A blueprint gets up to four icons for its most significant entities: each entity adds the
+//! weight of its kind, or its own weight if one is given, to its name. A book takes the icons
+//! shared most by its items.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::{Arc, Mutex};
+
+use crossterm::style::Stylize;
+use serde_json::{json, Value};
+
+use crate::args;
+use crate::book::TreeBook;
+use crate::common::{self, BlueprintType};
+use crate::factorio_structs;
+use crate::manifest;
+use crate::progress::{self, CommandType, ProgressType, Tracker};
+use crate::render::{self, Kind};
+use crate::replace::MAPPING_SEPARATOR;
+
+/// Most icons the game shows for an item
+pub const MAX_ICONS: usize = 4;
+
+/// Names of entity kinds accepted as weight keys
+const KIND_NAMES: [(&str, Kind); 11] = [
+    ("belt", Kind::Belt),
+    ("inserter", Kind::Inserter),
+    ("production", Kind::Production),
+    ("mining", Kind::Mining),
+    ("power", Kind::Power),
+    ("logistics", Kind::Logistics),
+    ("fluid", Kind::Fluid),
+    ("rail", Kind::Rail),
+    ("circuit", Kind::Circuit),
+    ("defense", Kind::Defense),
+    ("other", Kind::Other),
+];
+
+/// Entities placed by a differently named item
+const ENTITY_ITEMS: [(&str, &str); 5] = [
+    ("straight-rail", "rail"),
+    ("curved-rail", "rail"),
+    ("half-diagonal-rail", "rail"),
+    ("curved-rail-a", "rail"),
+    ("curved-rail-b", "rail"),
+];
+
+pub struct Worker {
+    pub tree: PathBuf,
+    pub auto: bool,
+    pub weights: Vec<String>,
+    pub overwrite: bool,
+}
+
+/// How much each entity counts towards becoming an icon
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weights {
+    pub kinds: HashMap<Kind, f64>,
+    /// Weights of single entities, taking precedence over their kind
+    pub entities: HashMap<String, f64>,
+}
+
+impl Default for Weights {
+    /// Machines over logistics, with belts, inserters and poles counting least
+    fn default() -> Weights {
+        let kinds = HashMap::from([
+            (Kind::Production, 10.0),
+            (Kind::Mining, 8.0),
+            (Kind::Power, 6.0),
+            (Kind::Defense, 6.0),
+            (Kind::Rail, 4.0),
+            (Kind::Fluid, 3.0),
+            (Kind::Logistics, 3.0),
+            (Kind::Circuit, 2.0),
+            (Kind::Other, 2.0),
+            (Kind::Inserter, 0.5),
+            (Kind::Belt, 0.25),
+        ]);
+        let poles = [
+            "small-electric-pole",
+            "medium-electric-pole",
+            "big-electric-pole",
+            "substation",
+        ];
+        let entities = poles
+            .into_iter()
+            .map(|pole| (pole.to_string(), 0.5))
+            .collect();
+
+        Weights { kinds, entities }
+    }
+}
+
+impl Weights {
+    /// Default weights changed by "name=weight" pairs, where the name is an entity or a kind:
+    /// belt, inserter, production, mining, power, logistics, fluid, rail, circuit, defense, other
+    pub fn parse(overrides: &[String]) -> Result<Weights, String> {
+        let mut weights = Weights::default();
+        for pair in overrides {
+            let (name, weight) = match pair
+                .split_once(MAPPING_SEPARATOR)
+                .map(|(name, weight)| (name.trim(), weight.trim().parse::<f64>()))
+            {
+                Some((name, Ok(weight))) if !name.is_empty() && weight >= 0.0 => (name, weight),
+                _ => return Err(format!("invalid weight \"{}\", expected name=weight", pair)),
+            };
+            match KIND_NAMES.iter().find(|(kind_name, _)| *kind_name == name) {
+                Some((_, kind)) => weights.kinds.insert(*kind, weight),
+                None => weights.entities.insert(name.to_string(), weight),
+            };
+        }
+        Ok(weights)
+    }
+
+    pub fn of(&self, entity_name: &str) -> f64 {
+        match self.entities.get(entity_name) {
+            Some(weight) => *weight,
+            None => {
+                let (_, _, kind) = render::entity_size(entity_name);
+                self.kinds.get(&kind).copied().unwrap_or(1.0)
+            }
+        }
+    }
+}
+
+/// Item names of the most significant entities of a blueprint object,
+/// the highest total weight first and by name on a tie
+pub fn blueprint_icons(blueprint: &Value, weights: &Weights) -> Vec<String> {
+    let mut scores: HashMap<String, f64> = HashMap::new();
+    for name in common::array(blueprint, "entities")
+        .iter()
+        .filter_map(|entity| entity.get("name").and_then(|name| name.as_str()))
+    {
+        *scores.entry(item_name(name).to_string()).or_default() += weights.of(name);
+    }
+    ranked(scores)
+}
+
+/// Icons shared most by the items of a book. An item's first icon counts the most
+pub fn book_icons(item_icons: &[Vec<String>]) -> Vec<String> {
+    let mut scores: HashMap<String, f64> = HashMap::new();
+    for icons in item_icons {
+        for (pos, icon) in icons.iter().take(MAX_ICONS).enumerate() {
+            *scores.entry(icon.clone()).or_default() += (MAX_ICONS - pos) as f64;
+        }
+    }
+    ranked(scores)
+}
+
+/// Names with a positive score, highest first and by name on a tie, up to the icon limit
+fn ranked(scores: HashMap<String, f64>) -> Vec<String> {
+    let mut ranked: Vec<(String, f64)> = scores
+        .into_iter()
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+    ranked.sort_by(|(a_name, a_score), (b_name, b_score)| {
+        b_score.total_cmp(a_score).then_with(|| a_name.cmp(b_name))
+    });
+    ranked
+        .into_iter()
+        .take(MAX_ICONS)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Item placing an entity
+fn item_name(entity_name: &str) -> &str {
+    ENTITY_ITEMS
+        .iter()
+        .find(|(entity, _)| *entity == entity_name)
+        .map_or(entity_name, |(_, item)| item)
+}
+
+/// Names of the icons of a blueprint, book or planner object
+pub fn icon_names(inner: &Value) -> Vec<String> {
+    let mut icons: Vec<&Value> = common::array(inner, "icons").iter().collect();
+    icons.sort_by_key(|icon| icon.get("index").and_then(|index| index.as_u64()));
+    icons
+        .into_iter()
+        .filter_map(|icon| icon.pointer("/signal/name").and_then(|name| name.as_str()))
+        .map(String::from)
+        .collect()
+}
+
+/// Icons array of item signals, in the format of the object's game version
+pub fn icons_value(names: &[String], inner: &Value) -> Value {
+    let is_2_0 = common::is_version_2(inner);
+    let icons = names
+        .iter()
+        .enumerate()
+        .map(|(pos, name)| match is_2_0 {
+            // items are the default signal type in 2.0
+            true => json!({"signal": {"name": name}, "index": pos + 1}),
+            false => json!({"signal": {"type": "item", "name": name}, "index": pos + 1}),
+        })
+        .collect();
+    Value::Array(icons)
+}
+
+impl Worker {
+    pub fn from(icons_args: &args::Icons) -> Worker {
+        Worker {
+            tree: PathBuf::from(icons_args.tree.clone().unwrap_or_default()),
+            auto: icons_args.auto,
+            weights: icons_args.weight.clone(),
+            overwrite: icons_args.overwrite,
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let prog_tracker = progress::Tracker::new_quiet_sync(CommandType::Icons);
+        let icons_error = |err_msg: String| -> ! {
+            prog_tracker.lock().unwrap().error_additional(err_msg);
+            exit(1);
+        };
+
+        if !self.auto {
+            icons_error("nothing to do, give --auto".to_string());
+        }
+        if self.tree.as_os_str().is_empty() {
+            icons_error("no tree given, and no tree set in fbpconvert.toml".to_string());
+        }
+        let weights = match Weights::parse(&self.weights) {
+            Ok(_weights) => _weights,
+            Err(err_msg) => icons_error(err_msg),
+        };
+
+        // the rewritten files are recorded in the manifest of the tree, if it has one
+        let refresh = match manifest::Refresh::prepare(&self.tree) {
+            Ok(_refresh) => _refresh,
+            Err(err_msg) => icons_error(err_msg),
+        };
+
+        let mut changed = 0;
+        let result = match self.tree.is_dir() {
+            true => self.icons_in_book(&prog_tracker, &self.tree, &weights, &mut changed),
+            false => self.icons_in_file(&self.tree, &weights, &mut changed),
+        };
+        // files written before an error are recorded as well
+        if let Some(Err(err_msg)) = refresh.as_ref().map(manifest::Refresh::finish) {
+            icons_error(err_msg);
+        }
+        if let Err(err_msg) = result {
+            icons_error(err_msg);
+        }
+
+        println!("set icons of {} items", changed);
+
+        let errors = prog_tracker.lock().unwrap().errors;
+        if errors != 0 {
+            icons_error(format!("{} items could not be updated", errors));
+        }
+    }
+
+    /// Sets the icons of a book directory's items, then of the book itself.
+    /// Returns the book's icons
+    fn icons_in_book(
+        &self,
+        prog_tracker: &Arc<Mutex<Tracker>>,
+        book_dir: &Path,
+        weights: &Weights,
+        changed: &mut usize,
+    ) -> Result<Vec<String>, String> {
+        let mut book = TreeBook::open(book_dir)?;
+
+        let mut item_icons = vec![];
+        for pos in 0..book.order().len() {
+            let entry = &book.order()[pos];
+            let key = match BlueprintType::classify(entry).key() {
+                Some(_key) => _key,
+                None => continue,
+            };
+            let entry_path = book.entry_path(entry)?;
+            let icons = match entry_path.is_dir() {
+                true => self.icons_in_book(prog_tracker, &entry_path, weights, changed),
+                false => self.icons_in_file(&entry_path, weights, changed),
+            };
+            match icons {
+                Ok(icons) => {
+                    // the order keeps a copy of each item's icons
+                    let entry_inner = &mut book.order_mut()[pos][key];
+                    if !icons.is_empty() {
+                        entry_inner["icons"] = icons_value(&icons, entry_inner);
+                    }
+                    item_icons.push(icons);
+                }
+                Err(err_msg) => {
+                    let item_name = entry_path.to_string_lossy().to_string();
+                    let progress_type = match entry_path.is_dir() {
+                        true => ProgressType::Book(item_name),
+                        false => ProgressType::Blueprint(item_name),
+                    };
+                    prog_tracker
+                        .lock()
+                        .unwrap()
+                        .error(progress_type, Some(err_msg))
+                }
+            }
+        }
+
+        let existing = icon_names(book.head());
+        let icons = match existing.is_empty() || self.overwrite {
+            true => book_icons(&item_icons),
+            false => existing.clone(),
+        };
+        if icons != existing && !icons.is_empty() {
+            let head = book.head_mut();
+            head["icons"] = icons_value(&icons, head);
+            Worker::report(book_dir, &icons);
+            *changed += 1;
+        }
+        book.save()?;
+
+        Ok(icons)
+    }
+
+    /// Sets the icons of a blueprint file. Returns the item's icons, planners keep theirs
+    fn icons_in_file(
+        &self,
+        json_file: &Path,
+        weights: &Weights,
+        changed: &mut usize,
+    ) -> Result<Vec<String>, String> {
+        let (mut unknown_bp, style) = match common::json_read_tree(json_file) {
+            Some(_val) => _val,
+            None => return Err(format!("{:?}: failed to read file", json_file)),
+        };
+        let key = match BlueprintType::classify(&unknown_bp).key() {
+            Some(_key) => _key,
+            None => return Err(format!("{:?}: invalid blueprint", json_file)),
+        };
+
+        let inner = &mut unknown_bp[key];
+        let existing = icon_names(inner);
+        if key != factorio_structs::FACTORIO_BP_KEY || !(existing.is_empty() || self.overwrite) {
+            return Ok(existing);
+        }
+        let icons = blueprint_icons(inner, weights);
+        if icons.is_empty() || icons == existing {
+            return Ok(existing);
+        }
+        inner["icons"] = icons_value(&icons, inner);
+
+        match File::create(json_file).and_then(|file| style.write(file, &unknown_bp)) {
+            Ok(_) => (),
+            Err(_) => return Err(format!("{:?}: error writing file", json_file)),
+        }
+        Worker::report(json_file, &icons);
+        *changed += 1;
+        Ok(icons)
+    }
+
+    fn report(item_path: &Path, icons: &[String]) {
+        println!(
+            "{}  {}",
+            item_path.to_string_lossy().green().bold(),
+            icons.join(", ").dim()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{export, import};
+    use std::fs;
+
+    fn blueprint(label: &str, entities: &[(&str, usize)]) -> Value {
+        let entities: Vec<Value> = entities
+            .iter()
+            .flat_map(|(name, count)| std::iter::repeat_n(*name, *count))
+            .enumerate()
+            .map(|(idx, name)| json!({"entity_number": idx + 1, "name": name}))
+            .collect();
+        json!({"label": label, "version": 1, "entities": entities})
+    }
+
+    #[test]
+    fn test_blueprint_icons() {
+        let smelting = blueprint(
+            "smelting",
+            &[
+                ("transport-belt", 60),
+                ("stone-furnace", 4),
+                ("inserter", 8),
+                ("small-electric-pole", 6),
+                ("straight-rail", 1),
+            ],
+        );
+        let weights = Weights::default();
+        assert_eq!(
+            blueprint_icons(&smelting, &weights),
+            ["stone-furnace", "transport-belt", "inserter", "rail"]
+        );
+
+        let weights = Weights::parse(&["belt=0".to_string(), "rail = 100".to_string()]).unwrap();
+        assert_eq!(
+            blueprint_icons(&smelting, &weights),
+            ["rail", "stone-furnace", "inserter", "small-electric-pole"]
+        );
+        assert!(Weights::parse(&["belt".to_string()]).is_err());
+        assert!(Weights::parse(&["belt=-1".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_book_icons() {
+        let item_icons = vec![
+            vec!["stone-furnace".to_string(), "inserter".to_string()],
+            vec!["inserter".to_string()],
+            vec![],
+        ];
+        assert_eq!(book_icons(&item_icons), ["inserter", "stone-furnace"]);
+        assert_eq!(
+            icons_value(
+                &["pipe".to_string()],
+                &json!({"version": 562949954076673u64})
+            ),
+            json!([{"signal": {"name": "pipe"}, "index": 1}])
+        );
+    }
+
+    #[test]
+    fn test_icons_in_tree() {
+        let dest = common::TestDir::new("icons");
+
+        let mut with_icons = blueprint("kept", &[("pipe", 1)]);
+        with_icons["icons"] = json!([{"signal": {"type": "item", "name": "pump"}, "index": 1}]);
+        let book = json!({"blueprint_book": {
+            "item": "blueprint-book", "label": "outer", "active_index": 0, "version": 1,
+            "blueprints": [
+                {"index": 0, "blueprint": blueprint("machines", &[("assembling-machine-1", 2)])},
+                {"index": 1, "blueprint": with_icons},
+            ]
+        }});
+        let tracker = progress::Tracker::new_quiet_sync(CommandType::Icons);
+        import::Worker::unknown_write(&tracker, book, &dest, common::TreeStyle::default());
+
+        let worker = Worker {
+            tree: dest.join("outer"),
+            auto: true,
+            weights: vec![],
+            overwrite: false,
+        };
+        let mut changed = 0;
+        let icons = worker
+            .icons_in_book(&tracker, &worker.tree, &Weights::default(), &mut changed)
+            .unwrap();
+        assert_eq!(icons, ["assembling-machine-1", "pump"]);
+        assert_eq!(changed, 2);
+
+        let exported = export::Worker::read_book_recursive(&tracker, &worker.tree).unwrap();
+        let children = common::book_children(&exported).unwrap();
+        assert_eq!(
+            icon_names(&children[0]["blueprint"]),
+            ["assembling-machine-1"]
+        );
+        assert_eq!(icon_names(&children[1]["blueprint"]), ["pump"]);
+
+        // unreadable items are counted as errors, the others are still updated
+        fs::write(worker.tree.join("kept.json"), "not json").unwrap();
+        let tracker = progress::Tracker::new_quiet_sync(CommandType::Icons);
+        worker
+            .icons_in_book(&tracker, &worker.tree, &Weights::default(), &mut changed)
+            .unwrap();
+        assert_eq!(tracker.lock().unwrap().errors, 1);
+    }
+}
@@ -1,22 +1,25 @@
 use std::fs;
 use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
+use std::path::Path;
 use std::process::exit;
 use std::sync::{Arc, Mutex};
 
 use copypasta::{self, ClipboardContext, ClipboardProvider};
 use rayon::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
 
 use crate::args;
 use crate::common::BlueprintType;
 use crate::progress::{self, ProgressType};
-use crate::{common, factorio_structs};
+use crate::{common, factorio_structs, git, manifest};
 use factorio_structs::importable;
 
 pub struct Worker {
     pub import_type: args::ImportSubCommands,
     dest: String,
+    style: common::TreeStyle,
+    commit: bool,
 }
 
 impl Worker {
@@ -34,35 +37,47 @@ impl Worker {
                     _copy.destination.clone().unwrap_or(".".to_string())
                 }
             },
+            style: match &_cmd_type {
+                args::ImportSubCommands::File(_file) => common::TreeStyle {
+                    naming: match _file.readable_names {
+                        true => common::FileNaming::Readable,
+                        false => _file.naming.unwrap_or_default(),
+                    },
+                    format: _file.file_format.unwrap_or_default(),
+                    canonical: _file.canonical.unwrap_or_default(),
+                },
+                args::ImportSubCommands::Clipboard(_copy) => common::TreeStyle {
+                    naming: match _copy.readable_names {
+                        true => common::FileNaming::Readable,
+                        false => _copy.naming.unwrap_or_default(),
+                    },
+                    format: _copy.file_format.unwrap_or_default(),
+                    canonical: _copy.canonical.unwrap_or_default(),
+                },
+            },
+            commit: match &_cmd_type {
+                args::ImportSubCommands::File(_file) => _file.commit,
+                args::ImportSubCommands::Clipboard(_copy) => _copy.commit,
+            },
         }
     }
 
     /// Main calling method for struct
     pub fn exec(&self) {
         // create new progress tracker instance
-        let mut progress_tracker = progress::Tracker::new_sync(progress::CommandType::Import);
+        let progress_tracker = progress::Tracker::new_sync(progress::CommandType::Import);
 
         // make the destination dir (if it doesnt exist)
-        match fs::create_dir_all(&self.dest) {
-            Err(_) => {
-                println!("Error creating deestination directory!");
-                progress_tracker.lock().unwrap().complete();
-                exit(1);
-            }
-            Ok(_) => (),
+        if fs::create_dir_all(&self.dest).is_err() {
+            println!("Error creating deestination directory!");
+            progress_tracker.lock().unwrap().complete();
+            exit(1);
         }
 
-        let blueprint_string: String;
-        let blueprint_inflated: String;
-
-        #[cfg(debug_assertions)]
-        let inflate_only: bool;
-
-        #[cfg(debug_assertions)]
-        match &self.import_type {
+        let blueprint_string: String = match &self.import_type {
             args::ImportSubCommands::File(_file) => {
-                match fs::read_to_string(&_file.infile.clone().unwrap()) {
-                    Ok(_str) => blueprint_string = _str,
+                match fs::read_to_string(_file.infile.clone().unwrap()) {
+                    Ok(_str) => _str,
                     Err(_) => {
                         let mut unlocked = progress_tracker.lock().unwrap();
                         unlocked.error_additional("file not found".to_string());
@@ -70,13 +85,12 @@ impl Worker {
                         exit(1);
                     }
                 }
-                inflate_only = _file.inflate_only;
             }
 
-            args::ImportSubCommands::Clipboard(_copy) => {
+            args::ImportSubCommands::Clipboard(_) => {
                 let mut clipboard = ClipboardContext::new().unwrap();
                 match clipboard.get_contents() {
-                    Ok(_clipboard) => blueprint_string = _clipboard,
+                    Ok(_clipboard) => _clipboard,
 
                     Err(_) => {
                         let mut unlocked = progress_tracker.lock().unwrap();
@@ -85,57 +99,30 @@ impl Worker {
                         exit(1);
                     }
                 }
-                inflate_only = _copy.inflate_only;
             }
-        }
-
-        #[cfg(not(debug_assertions))]
-        match &self.import_type {
-            args::ImportSubCommands::File(_file) => {
-                match fs::read_to_string(&_file.infile.clone().unwrap()) {
-                    Ok(_str) => blueprint_string = _str,
-                    Err(_) => {
-                        let mut unlocked = progress_tracker.lock().unwrap();
-                        unlocked.error_additional("file not found".to_string());
-                        unlocked.complete();
-                        exit(1);
-                    }
-                }
-            }
-
-            args::ImportSubCommands::Clipboard(_copy) => {
-                let mut clipboard = ClipboardContext::new().unwrap();
-                match clipboard.get_contents() {
-                    Ok(_clipboard) => blueprint_string = _clipboard,
+        };
 
-                    Err(_) => {
-                        let mut unlocked = progress_tracker.lock().unwrap();
-                        unlocked.error_additional("clipboard empty".to_string());
-                        unlocked.complete();
-                        exit(1);
-                    }
-                }
-            }
-        }
+        #[cfg(debug_assertions)]
+        let inflate_only: bool = match &self.import_type {
+            args::ImportSubCommands::File(_file) => _file.inflate_only,
+            args::ImportSubCommands::Clipboard(_copy) => _copy.inflate_only,
+        };
 
-        match common::factorio_inflate(blueprint_string.as_str()) {
-            Ok(blueprint) => {
-                blueprint_inflated = blueprint;
-            }
+        let blueprint_inflated: String = match common::factorio_inflate(blueprint_string.as_str()) {
+            Ok(blueprint) => blueprint,
             Err(e) => {
                 let mut unlocked = progress_tracker.lock().unwrap();
-                unlocked.error_additional(e.to_string());
+                unlocked.error_additional(e);
                 unlocked.complete();
                 exit(1);
             }
-        }
+        };
 
-        // convert the string to a json value
-        let blueprint_obj: serde_json::Value;
-        match serde_json::from_str(blueprint_inflated.as_str()) {
-            Ok(_obj) => {
-                blueprint_obj = _obj;
-            }
+        // convert the string to a json value.
+        // this is the only time the inflated string is parsed, the resulting value
+        // is moved (not copied) down the book tree as it is written to disk.
+        let blueprint_obj: Value = match serde_json::from_str(blueprint_inflated.as_str()) {
+            Ok(_obj) => _obj,
             Err(_) => {
                 let mut unlocked = progress_tracker.lock().unwrap();
                 unlocked.error_additional(
@@ -144,26 +131,48 @@ impl Worker {
                 unlocked.complete();
                 exit(1);
             }
-        }
+        };
+        drop(blueprint_inflated);
 
         #[cfg(debug_assertions)]
         if inflate_only {
             let mut unlocked = progress_tracker.lock().unwrap();
             unlocked.msg("inflating only...".to_string());
-            let mut out_file = File::create("inflated.json").expect("file creation error");
-
-            out_file
-                .write(
-                    serde_json::to_string_pretty(&blueprint_obj)
-                        .expect("unable to serialize")
-                        .as_bytes(),
-                )
-                .expect("unable to write to file");
+            let out_file = File::create("inflated.json").expect("file creation error");
+
+            common::json_write_pretty(out_file, &blueprint_obj).expect("unable to write to file");
             unlocked.complete();
             exit(0);
         }
 
-        // let blueprint_file_name: String;
+        let dest = Path::new(&self.dest);
+
+        // label and file name of the imported item, the manifest and commit only cover its files
+        let (label, item_name) = match BlueprintType::classify(&blueprint_obj) {
+            BlueprintType::Book(_label)
+            | BlueprintType::Blueprint(_label)
+            | BlueprintType::UpgradePlanner(_label)
+            | BlueprintType::DeconPlanner(_label) => {
+                let item_name = self.style.naming.file_name(&_label);
+                (_label, item_name)
+            }
+            BlueprintType::Invalid => (String::new(), String::new()),
+        };
+
+        // checked before anything is written, the files of the previous import are removed
+        let import_commit = match self.commit && !item_name.is_empty() {
+            true => match git::ImportCommit::prepare(dest, &item_name) {
+                Ok(_commit) => Some(_commit),
+                Err(err_msg) => {
+                    let mut unlocked = progress_tracker.lock().unwrap();
+                    unlocked.error_additional(err_msg);
+                    unlocked.complete();
+                    exit(1);
+                }
+            },
+            false => None,
+        };
+
         match BlueprintType::classify(&blueprint_obj) {
             BlueprintType::Invalid => {
                 let mut unlocked = progress_tracker.lock().unwrap();
@@ -177,7 +186,7 @@ impl Worker {
 
             BlueprintType::Blueprint(_bp_name) => {
                 let mut unlocked = progress_tracker.lock().unwrap();
-                match Worker::blueprint_write(&blueprint_obj, &PathBuf::from(&self.dest)) {
+                match Worker::blueprint_write(blueprint_obj, dest, self.style) {
                     Ok(()) => unlocked.ok(ProgressType::Blueprint(_bp_name)),
                     Err(err_msg) => {
                         unlocked.error(ProgressType::Blueprint(_bp_name), Some(err_msg))
@@ -187,9 +196,10 @@ impl Worker {
 
             BlueprintType::Book(_book_name) => {
                 match Worker::recursive_book_write(
-                    &mut progress_tracker,
-                    &blueprint_obj,
-                    &PathBuf::from(&self.dest),
+                    &progress_tracker,
+                    blueprint_obj,
+                    dest,
+                    self.style,
                 ) {
                     Ok(()) => progress_tracker
                         .lock()
@@ -203,7 +213,7 @@ impl Worker {
             }
 
             BlueprintType::UpgradePlanner(_planner) => {
-                match Worker::upgrade_planner_write(&blueprint_obj, &PathBuf::from(&self.dest)) {
+                match Worker::upgrade_planner_write(blueprint_obj, dest, self.style) {
                     Ok(_) => progress_tracker
                         .lock()
                         .unwrap()
@@ -216,7 +226,7 @@ impl Worker {
             }
 
             BlueprintType::DeconPlanner(_planner) => {
-                match Worker::decon_planner_write(&blueprint_obj, &PathBuf::from(&self.dest)) {
+                match Worker::decon_planner_write(blueprint_obj, dest, self.style) {
                     Ok(_) => progress_tracker
                         .lock()
                         .unwrap()
@@ -229,308 +239,492 @@ impl Worker {
             }
         }
 
+        // record the written files, for check and export to notice later edits.
+        // A partly written item is left out, its old entries report the difference
+        if progress_tracker.lock().unwrap().errors == 0 {
+            if let Err(err_msg) = manifest::update(dest, &item_name) {
+                progress_tracker.lock().unwrap().error_additional(err_msg);
+            }
+        }
+
+        if let Some(_commit) = import_commit {
+            let mut unlocked = progress_tracker.lock().unwrap();
+            let written = unlocked.errors == 0;
+            match _commit.finish(&label, written) {
+                Ok(Some(message)) => {
+                    unlocked.msg(format!("committed \"{}\"", message.lines().next().unwrap()))
+                }
+                Ok(None) => unlocked.msg("nothing changed, no commit created".to_string()),
+                Err(err_msg) => {
+                    unlocked.error_additional(err_msg);
+                    unlocked.complete();
+                    exit(1);
+                }
+            }
+        }
+
         progress_tracker.lock().unwrap().complete();
     }
 
     /// Writes a blueprint to file given the file path and blueprint object
     /// Returns an error message if encountered
-    fn blueprint_write(blueprint: &serde_json::Value, dir_path: &PathBuf) -> Result<(), String> {
-        let mut full_bp_path = dir_path.clone();
-        let mut bp_name = blueprint
-            .get(factorio_structs::FACTORIO_BP_KEY)
-            .and_then(|value| value.get("label"))
-            .and_then(|value| value.as_str())
-            .unwrap()
-            .to_string();
-
+    fn blueprint_write(
+        blueprint: Value,
+        dir_path: &Path,
+        style: common::TreeStyle,
+    ) -> Result<(), String> {
         // remove "index" key from the blueprint object
-        let mut blueprint_compliant: importable::BlueprintHead;
-        match serde_json::from_value(blueprint.to_owned()) {
-            Ok(result) => blueprint_compliant = result,
-            Err(_) => return Err("Error deserializing to compliant blueprint".to_string()),
+        let mut blueprint_compliant: importable::BlueprintHead =
+            match serde_json::from_value(blueprint) {
+                Ok(result) => result,
+                Err(_) => return Err("Error deserializing to compliant blueprint".to_string()),
+            };
+
+        let bp_name = style.naming.file_name(&blueprint_compliant.blueprint.label);
+        if style.naming == common::FileNaming::Renamed {
+            blueprint_compliant.blueprint.label = bp_name.clone();
         }
 
-        bp_name = common::file_rename(bp_name);
-        blueprint_compliant.blueprint.label = bp_name.clone();
-
+        let mut full_bp_path = dir_path.to_path_buf();
         full_bp_path.push(&bp_name);
         full_bp_path.set_extension("json");
 
-        let mut bp_file: File;
-
-        match File::create(&full_bp_path) {
-            Ok(_file) => bp_file = _file,
+        let bp_file: File = match File::create(&full_bp_path) {
+            Ok(_file) => _file,
             Err(_) => return Err("file creation error. check the file path".to_string()),
-        }
-
-        match bp_file.write(
-            serde_json::to_string_pretty(&blueprint_compliant)
-                .unwrap()
-                .as_bytes(),
-        ) {
-            Ok(_) => {
-                // println!("Created {}", &full_bp_path.to_string_lossy());
-                return Ok(());
-            }
-            Err(_) => {
-                // println!("Error creating {}", &full_bp_path.to_string_lossy());
-                return Err(format!(
-                    "Error creating {}",
-                    &full_bp_path.to_string_lossy()
-                ));
-                // return Err(bp_name);
-            }
+        };
+
+        match style.write(bp_file, &blueprint_compliant) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!(
+                "Error creating {}",
+                &full_bp_path.to_string_lossy()
+            )),
         }
     }
 
     /// Writes a upgrade planner
     fn upgrade_planner_write(
-        planner: &serde_json::Value,
-        dir_path: &PathBuf,
+        planner: Value,
+        dir_path: &Path,
+        style: common::TreeStyle,
     ) -> Result<(), String> {
-        let mut full_planner_path = dir_path.clone();
-
-        let mut planner_name = planner
-            .get(factorio_structs::FACTORIO_UP_PLANNER_KEY)
-            .and_then(|value| value.get("label"))
-            .and_then(|value| value.as_str())
-            .unwrap()
-            .to_string();
-
-        let mut planner_compliant: importable::UpgradeHead;
-        match serde_json::from_value(planner.to_owned()) {
-            Ok(result) => planner_compliant = result,
+        let mut planner_compliant: importable::UpgradeHead = match serde_json::from_value(planner) {
+            Ok(result) => result,
             Err(_) => return Err("Error deserializing to compliant planner".to_string()),
-        }
+        };
 
-        planner_name = common::file_rename(planner_name);
-        planner_compliant.upgrade_planner.label = planner_name.clone();
+        let planner_name = style
+            .naming
+            .file_name(&planner_compliant.upgrade_planner.label);
+        if style.naming == common::FileNaming::Renamed {
+            planner_compliant.upgrade_planner.label = planner_name.clone();
+        }
 
+        let mut full_planner_path = dir_path.to_path_buf();
         full_planner_path.push(&planner_name);
         full_planner_path.set_extension("json");
 
-        let mut planner_file = File::create(&full_planner_path).expect("file creation error");
+        let planner_file = File::create(&full_planner_path).expect("file creation error");
 
-        match planner_file.write(
-            serde_json::to_string_pretty(&planner_compliant)
-                .unwrap()
-                .as_bytes(),
-        ) {
-            Ok(_) => {
-                // println!("Created {}", &full_bp_path.to_string_lossy());
-                return Ok(());
-            }
-            Err(_) => {
-                // println!("Error creating {}", &full_bp_path.to_string_lossy());
-                return Err(format!(
-                    "Error creating {}",
-                    &full_planner_path.to_string_lossy()
-                ));
-                // return Err(bp_name);
-            }
+        match style.write(planner_file, &planner_compliant) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!(
+                "Error creating {}",
+                &full_planner_path.to_string_lossy()
+            )),
         }
     }
 
     /// Writes a decon / upgrade planner
-    fn decon_planner_write(planner: &serde_json::Value, dir_path: &PathBuf) -> Result<(), String> {
-        let mut full_planner_path = dir_path.clone();
-
-        let mut planner_name = planner
-            .get(factorio_structs::FACTORIO_DECON_PLANNER_KEY)
-            .and_then(|value| value.get("label"))
-            .and_then(|value| value.as_str())
-            .unwrap()
-            .to_string();
-
-        let mut planner_compliant: importable::DeconHead;
-        match serde_json::from_value(planner.to_owned()) {
-            Ok(result) => planner_compliant = result,
+    fn decon_planner_write(
+        planner: Value,
+        dir_path: &Path,
+        style: common::TreeStyle,
+    ) -> Result<(), String> {
+        let mut planner_compliant: importable::DeconHead = match serde_json::from_value(planner) {
+            Ok(result) => result,
             Err(_) => return Err("Error deserializing to compliant planner".to_string()),
-        }
+        };
 
-        planner_name = common::file_rename(planner_name);
-        planner_compliant.deconstruction_planner.label = planner_name.clone();
+        let planner_name = style
+            .naming
+            .file_name(&planner_compliant.deconstruction_planner.label);
+        if style.naming == common::FileNaming::Renamed {
+            planner_compliant.deconstruction_planner.label = planner_name.clone();
+        }
 
+        let mut full_planner_path = dir_path.to_path_buf();
         full_planner_path.push(&planner_name);
         full_planner_path.set_extension("json");
 
-        let mut planner_file = File::create(&full_planner_path).expect("file creation error");
+        let planner_file = File::create(&full_planner_path).expect("file creation error");
 
-        match planner_file.write(
-            serde_json::to_string_pretty(&planner_compliant)
-                .unwrap()
-                .as_bytes(),
-        ) {
-            Ok(_) => {
-                // println!("Created {}", &full_bp_path.to_string_lossy());
-                return Ok(());
-            }
-            Err(_) => {
-                // println!("Error creating {}", &full_bp_path.to_string_lossy());
-                return Err(format!(
-                    "Error creating {}",
-                    &full_planner_path.to_string_lossy()
-                ));
-                // return Err(bp_name);
-            }
+        match style.write(planner_file, &planner_compliant) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!(
+                "Error creating {}",
+                &full_planner_path.to_string_lossy()
+            )),
         }
     }
 
-    /// Recursively writes the book and its contents to file, given a known starting dir
+    /// Recursively writes the book and its contents to file, given a known starting dir.
+    /// The book is consumed: its children are detached and moved into the next
+    /// recursion level, so no part of the tree is deep-copied.
     /// Returns an error message if an error is encountered
-    fn recursive_book_write(
-        prog_tracker: &mut Arc<Mutex<progress::Tracker>>,
-        bp_book: &serde_json::Value,
-        bp_book_dir: &PathBuf,
+    pub(crate) fn recursive_book_write(
+        prog_tracker: &Arc<Mutex<progress::Tracker>>,
+        mut bp_book: Value,
+        bp_book_dir: &Path,
+        style: common::TreeStyle,
     ) -> Result<(), String> {
+        // detach the children from the book, leaving only the book parameters behind
+        let book_contents: Vec<Value> = match bp_book
+            .get_mut(factorio_structs::FACTORIO_BP_BOOK_KEY)
+            .and_then(|value| value.get_mut("blueprints"))
+            .map(Value::take)
+        {
+            Some(Value::Array(bp_arr)) => bp_arr,
+            _ => vec![],
+        };
+
         // local_book_copy contains dotfile information
-        let mut book_dot_file: importable::BookHead;
-        match serde_json::from_value(bp_book.clone()) {
-            Ok(_val) => book_dot_file = _val,
+        let mut book_dot_file: importable::BookHead = match serde_json::from_value(bp_book) {
+            Ok(_val) => _val,
             Err(_) => return Err("failed to deserialize blueprint book".to_string()),
-        }
+        };
+
+        // the order of the children is read by borrowing each child,
+        // only the child's label and index are copied
+        book_dot_file.blueprint_book.order = match book_contents
+            .iter()
+            .map(factorio_structs::UnknownBlueprintType::deserialize)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(_order) => Some(_order),
+            Err(_) => return Err("failed to deserialize blueprint book".to_string()),
+        };
 
         // remove invalid characters from book by renaming
-        book_dot_file.blueprint_book.label =
-            common::file_rename(book_dot_file.blueprint_book.label);
+        let book_dir_name = style.naming.file_name(&book_dot_file.blueprint_book.label);
+        if style.naming == common::FileNaming::Renamed {
+            book_dot_file.blueprint_book.label = book_dir_name.clone();
+        }
         // book dotfile name, resides in book directory
         let mut book_dot_file_name = ".".to_string();
-        book_dot_file_name.push_str(&book_dot_file.blueprint_book.label);
+        book_dot_file_name.push_str(&book_dir_name);
 
         // new starting dir for next recursion level
-        let mut current_dir_path = bp_book_dir.clone();
-        current_dir_path.push(&book_dot_file.blueprint_book.label);
+        let mut current_dir_path = bp_book_dir.to_path_buf();
+        current_dir_path.push(&book_dir_name);
 
         // iterator for the contents of dotfile book
-        // rename all names in dotfile (remove invalid chars)
+        // rename all names in dotfile (remove invalid chars), or record the readable file names
         // change iterators to rayon's parallel iterators using the for_each() method
         if let Some(ref mut _order) = book_dot_file.blueprint_book.order {
-            _order.par_iter_mut().for_each(|_unknown| {
-                match _unknown.blueprint.as_mut() {
-                    Some(_bp) => {
-                        _bp.label = common::file_rename(_bp.label.clone());
-                    }
-                    None => (),
+            match style.naming {
+                common::FileNaming::Renamed => {
+                    _order.par_iter_mut().for_each(Worker::order_entry_rename)
                 }
-                match _unknown.blueprint_book.as_mut() {
-                    Some(_book) => {
-                        _book.label = common::file_rename(_book.label.clone());
-                    }
-                    None => (),
+                common::FileNaming::Readable => {
+                    _order.par_iter_mut().for_each(Worker::order_entry_readable)
                 }
-                match _unknown.upgrade_planner.as_mut() {
-                    Some(_planner) => {
-                        _planner.label = common::file_rename(_planner.label.clone());
-                    }
-                    None => (),
-                }
-                match _unknown.deconstruction_planner.as_mut() {
-                    Some(_planner) => {
-                        _planner.label = common::file_rename(_planner.label.clone());
-                    }
-                    None => (),
-                }
-            });
+            }
+        }
+
+        // names differing only in case are written, but would collide once the tree
+        // is checked out on a case-insensitive filesystem
+        if let Some(ref _order) = book_dot_file.blueprint_book.order {
+            let file_names: Vec<String> = _order
+                .iter()
+                .filter_map(Worker::order_entry_file_name)
+                .collect();
+            for (first, second) in common::case_collisions(&file_names) {
+                prog_tracker.lock().unwrap().error_additional(format!(
+                    "{:?}: \"{}\" and \"{}\" differ only in case",
+                    current_dir_path, first, second
+                ));
+            }
         }
 
         // write the dotfile first, then constituent blueprints/books
-        match fs::create_dir_all(&current_dir_path) {
-            Ok(_) => (),
-            Err(_) => return Err("error creating blueprint book directory".to_string()),
+        if fs::create_dir_all(&current_dir_path).is_err() {
+            return Err("error creating blueprint book directory".to_string());
         }
 
         let mut dot_file_path = current_dir_path.clone();
         dot_file_path.push(book_dot_file_name);
         dot_file_path.set_extension("json");
-        let mut dot_file: File;
-        match File::create(&dot_file_path) {
-            Ok(_f) => {
-                dot_file = _f;
-            }
+        let dot_file: File = match File::create(&dot_file_path) {
+            Ok(_f) => _f,
             Err(_) => return Err("dotfile unable to be created".to_string()),
+        };
+
+        if style.write(dot_file, &book_dot_file).is_err() {
+            return Err("error writing to dotfile".to_string());
         }
+        drop(book_dot_file);
 
-        match dot_file.write(
-            serde_json::to_string_pretty(&book_dot_file)
-                .unwrap()
-                .as_bytes(),
-        ) {
-            Ok(_) => {}
-            Err(_) => return Err("error writing to dotfile".to_string()),
+        // recurse for all constituent blueprints/books, handing ownership of each child over
+        book_contents.into_par_iter().for_each(|unknown_bp| {
+            Worker::unknown_write(prog_tracker, unknown_bp, &current_dir_path, style);
+        });
+
+        Ok(())
+    }
+
+    /// Renames the label of a dotfile order entry to the file name of the item it refers to
+    pub(crate) fn order_entry_rename(order_entry: &mut factorio_structs::UnknownBlueprintType) {
+        if let Some(_bp) = order_entry.blueprint.as_mut() {
+            _bp.label = common::file_rename(_bp.label.clone());
+        }
+        if let Some(_book) = order_entry.blueprint_book.as_mut() {
+            _book.label = common::file_rename(_book.label.clone());
         }
+        if let Some(_planner) = order_entry.upgrade_planner.as_mut() {
+            _planner.label = common::file_rename(_planner.label.clone());
+        }
+        if let Some(_planner) = order_entry.deconstruction_planner.as_mut() {
+            _planner.label = common::file_rename(_planner.label.clone());
+        }
+    }
 
-        // get the vec of stuff
-        let book_contents = bp_book
-            .get("blueprint_book")
-            .and_then(|value| value.get("blueprints"))
-            .unwrap();
-
-        // recurse for all constituent blueprints/books
-        match book_contents {
-            serde_json::Value::Array(bp_arr) => {
-                bp_arr.par_iter().for_each(|unknown_bp| {
-                    match BlueprintType::classify(&unknown_bp) {
-                        BlueprintType::Invalid => (),
-
-                        BlueprintType::Book(_book_name) => {
-                            match Worker::recursive_book_write(
-                                &mut prog_tracker.clone(),
-                                &unknown_bp,
-                                &current_dir_path,
-                            ) {
-                                Ok(()) => prog_tracker
-                                    .lock()
-                                    .unwrap()
-                                    .ok(ProgressType::Book(_book_name)),
-                                Err(err_msg) => prog_tracker
-                                    .lock()
-                                    .unwrap()
-                                    .error(ProgressType::Book(_book_name), Some(err_msg)),
-                            }
-                        }
+    /// Label of the item a dotfile order entry refers to
+    fn order_entry_label(order_entry: &factorio_structs::UnknownBlueprintType) -> Option<&String> {
+        [
+            order_entry.blueprint.as_ref().map(|_bp| &_bp.label),
+            order_entry
+                .blueprint_book
+                .as_ref()
+                .map(|_book| &_book.label),
+            order_entry
+                .upgrade_planner
+                .as_ref()
+                .map(|_planner| &_planner.label),
+            order_entry
+                .deconstruction_planner
+                .as_ref()
+                .map(|_planner| &_planner.label),
+        ]
+        .into_iter()
+        .flatten()
+        .next()
+    }
 
-                        BlueprintType::Blueprint(_bp_name) => {
-                            match Worker::blueprint_write(&unknown_bp, &current_dir_path) {
-                                Ok(()) => prog_tracker
-                                    .lock()
-                                    .unwrap()
-                                    .ok(ProgressType::Blueprint(_bp_name)),
-                                Err(err_msg) => prog_tracker
-                                    .lock()
-                                    .unwrap()
-                                    .error(ProgressType::Blueprint(_bp_name), Some(err_msg)),
-                            }
-                        }
-                        BlueprintType::UpgradePlanner(_planner) => {
-                            match Worker::upgrade_planner_write(&unknown_bp, &current_dir_path) {
-                                Ok(()) => prog_tracker
-                                    .lock()
-                                    .unwrap()
-                                    .ok(ProgressType::UpgradePlanner(_planner)),
-                                Err(err_msg) => prog_tracker
-                                    .lock()
-                                    .unwrap()
-                                    .error(ProgressType::Blueprint(_planner), Some(err_msg)),
-                            }
+    /// Name of the file or directory written for a dotfile order entry, extension included
+    fn order_entry_file_name(
+        order_entry: &factorio_structs::UnknownBlueprintType,
+    ) -> Option<String> {
+        let name = order_entry
+            .file
+            .as_ref()
+            .or(Worker::order_entry_label(order_entry))?;
+        match order_entry.blueprint_book {
+            Some(_) => Some(name.clone()),
+            None => Some(format!("{}.json", name)),
+        }
+    }
+
+    /// Records the readable file name of the item a dotfile order entry refers to,
+    /// when it differs from the label. The label itself is kept
+    pub(crate) fn order_entry_readable(order_entry: &mut factorio_structs::UnknownBlueprintType) {
+        if let Some(_label) = Worker::order_entry_label(order_entry) {
+            let file_name = common::readable_file_name(_label);
+            if &file_name != _label {
+                order_entry.file = Some(file_name);
+            }
+        }
+    }
+
+    /// Writes a blueprint, book or planner into the directory, reporting the result to the tracker.
+    /// Invalid items are skipped
+    pub(crate) fn unknown_write(
+        prog_tracker: &Arc<Mutex<progress::Tracker>>,
+        unknown_bp: Value,
+        dir_path: &Path,
+        style: common::TreeStyle,
+    ) {
+        match BlueprintType::classify(&unknown_bp) {
+            BlueprintType::Invalid => (),
+
+            BlueprintType::Book(_book_name) => {
+                match Worker::recursive_book_write(prog_tracker, unknown_bp, dir_path, style) {
+                    Ok(()) => prog_tracker
+                        .lock()
+                        .unwrap()
+                        .ok(ProgressType::Book(_book_name)),
+                    Err(err_msg) => prog_tracker
+                        .lock()
+                        .unwrap()
+                        .error(ProgressType::Book(_book_name), Some(err_msg)),
+                }
+            }
+
+            BlueprintType::Blueprint(_bp_name) => {
+                match Worker::blueprint_write(unknown_bp, dir_path, style) {
+                    Ok(()) => prog_tracker
+                        .lock()
+                        .unwrap()
+                        .ok(ProgressType::Blueprint(_bp_name)),
+                    Err(err_msg) => prog_tracker
+                        .lock()
+                        .unwrap()
+                        .error(ProgressType::Blueprint(_bp_name), Some(err_msg)),
+                }
+            }
+            BlueprintType::UpgradePlanner(_planner) => {
+                match Worker::upgrade_planner_write(unknown_bp, dir_path, style) {
+                    Ok(()) => prog_tracker
+                        .lock()
+                        .unwrap()
+                        .ok(ProgressType::UpgradePlanner(_planner)),
+                    Err(err_msg) => prog_tracker
+                        .lock()
+                        .unwrap()
+                        .error(ProgressType::Blueprint(_planner), Some(err_msg)),
+                }
+            }
+            BlueprintType::DeconPlanner(_planner) => {
+                match Worker::decon_planner_write(unknown_bp, dir_path, style) {
+                    Ok(()) => prog_tracker
+                        .lock()
+                        .unwrap()
+                        .ok(ProgressType::DeconPlanner(_planner)),
+                    Err(err_msg) => prog_tracker
+                        .lock()
+                        .unwrap()
+                        .error(ProgressType::Blueprint(_planner), Some(err_msg)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_recursive_book_write_moves_children() {
+        let dest = common::TestDir::new("import");
+
+        let book = json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "label": "outer book",
+                "active_index": 0,
+                "version": 1,
+                "blueprints": [
+                    {
+                        "index": 0,
+                        "blueprint": {
+                            "item": "blueprint",
+                            "label": "a/b",
+                            "version": 1,
+                            "entities": [{"entity_number": 1, "name": "wooden-chest"}]
                         }
-                        BlueprintType::DeconPlanner(_planner) => {
-                            match Worker::decon_planner_write(&unknown_bp, &current_dir_path) {
-                                Ok(()) => prog_tracker
-                                    .lock()
-                                    .unwrap()
-                                    .ok(ProgressType::DeconPlanner(_planner)),
-                                Err(err_msg) => prog_tracker
-                                    .lock()
-                                    .unwrap()
-                                    .error(ProgressType::Blueprint(_planner), Some(err_msg)),
-                            }
+                    },
+                    {
+                        "index": 1,
+                        "blueprint_book": {
+                            "item": "blueprint-book",
+                            "label": "inner",
+                            "active_index": 0,
+                            "version": 1,
+                            "blueprints": []
                         }
                     }
-                });
+                ]
             }
-            _ => (),
-        }
+        });
+
+        let tracker = progress::Tracker::new_sync(progress::CommandType::Import);
+        Worker::recursive_book_write(&tracker, book, &dest, common::TreeStyle::default()).unwrap();
+
+        let book_dir = dest.join("outer_book");
+        let dot_file: Value =
+            serde_json::from_str(&fs::read_to_string(book_dir.join(".outer_book.json")).unwrap())
+                .unwrap();
+        let order = dot_file["blueprint_book"]["order"].as_array().unwrap();
+        assert_eq!(order.len(), 2);
+        assert_eq!(order[0]["blueprint"]["label"], "a_b");
+        assert!(dot_file["blueprint_book"].get("blueprints").is_none());
+
+        let blueprint: Value =
+            serde_json::from_str(&fs::read_to_string(book_dir.join("a_b.json")).unwrap()).unwrap();
+        assert_eq!(
+            blueprint["blueprint"]["entities"][0]["name"],
+            "wooden-chest"
+        );
+        assert!(book_dir.join("inner").join(".inner.json").is_file());
+    }
 
-        Ok(())
+    #[test]
+    fn test_readable_names_keep_labels() {
+        let dest = common::TestDir::new("readable");
+
+        let book = json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "label": "[item=iron-plate] Smelting",
+                "active_index": 0,
+                "version": 1,
+                "blueprints": [
+                    {
+                        "index": 0,
+                        "blueprint": {
+                            "item": "blueprint",
+                            "label": "[virtual-signal=signal-A] a/b",
+                            "version": 1
+                        }
+                    },
+                    {
+                        "index": 1,
+                        "blueprint": {"item": "blueprint", "label": "plain", "version": 1}
+                    }
+                ]
+            }
+        });
+
+        let tracker = progress::Tracker::new_sync(progress::CommandType::Import);
+        Worker::recursive_book_write(
+            &tracker,
+            book,
+            &dest,
+            common::TreeStyle {
+                naming: common::FileNaming::Readable,
+                ..common::TreeStyle::default()
+            },
+        )
+        .unwrap();
+
+        let book_dir = dest.join("iron-plate Smelting");
+        assert!(book_dir.join("signal-A a_b.json").is_file());
+        assert!(book_dir.join("plain.json").is_file());
+
+        let dot_file: Value = serde_json::from_str(
+            &fs::read_to_string(book_dir.join(".iron-plate Smelting.json")).unwrap(),
+        )
+        .unwrap();
+        let order = dot_file["blueprint_book"]["order"].as_array().unwrap();
+        assert_eq!(order[0]["file"], "signal-A a_b");
+        assert!(order[1].get("file").is_none());
+
+        // export reads the files back with their original labels
+        let exported = crate::export::Worker::read_book_recursive(&tracker, &book_dir).unwrap();
+        assert_eq!(
+            exported["blueprint_book"]["label"],
+            "[item=iron-plate] Smelting"
+        );
+        let labels: Vec<&str> = exported["blueprint_book"]["blueprints"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|bp| bp["blueprint"]["label"].as_str().unwrap())
+            .collect();
+        assert!(labels.contains(&"[virtual-signal=signal-A] a/b"));
+        assert!(labels.contains(&"plain"));
     }
 }
@@ -0,0 +1,304 @@
+//! Prints a summary of a blueprint, book or planner without writing any files.
+
+use std::process::exit;
+
+use crossterm::style::Stylize;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::args;
+use crate::common::{self, BlueprintType};
+use crate::factorio_structs::{Entity, Tile};
+use crate::progress::{self, CommandType};
+use crate::source::Source;
+
+/// Maximum number of characters of a description to show
+const DESCRIPTION_EXCERPT_LEN: usize = 60;
+
+pub struct Worker {
+    pub source: Source,
+}
+
+/// Area covered by the entities and tiles of a blueprint, in tiles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl BoundingBox {
+    /// Bounding box of a single tile-sized area with its top left corner at (x, y)
+    fn tile(x: f64, y: f64) -> BoundingBox {
+        BoundingBox {
+            min_x: x,
+            min_y: y,
+            max_x: x + 1.0,
+            max_y: y + 1.0,
+        }
+    }
+
+    /// Smallest bounding box containing both boxes
+    fn union(self, other: BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+}
+
+/// Contents of a single blueprint
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlueprintSummary {
+    pub entities: usize,
+    pub tiles: usize,
+    /// Tiles touched by an entity's centre or covered by a tile.
+    /// None if the blueprint is empty
+    pub bounding_box: Option<BoundingBox>,
+}
+
+impl BlueprintSummary {
+    /// Counts the entities and tiles of a blueprint object (the value under the "blueprint" key)
+    pub fn from(blueprint: &Value) -> BlueprintSummary {
+        let entities = blueprint
+            .get("entities")
+            .and_then(|entities| entities.as_array())
+            .map(|entities| entities.as_slice())
+            .unwrap_or_default();
+        let tiles = blueprint
+            .get("tiles")
+            .and_then(|tiles| tiles.as_array())
+            .map(|tiles| tiles.as_slice())
+            .unwrap_or_default();
+
+        let entity_boxes = entities
+            .iter()
+            .filter_map(|entity| Entity::deserialize(entity).ok())
+            .map(|entity| BoundingBox::tile(entity.position.x.floor(), entity.position.y.floor()));
+        let tile_boxes = tiles
+            .iter()
+            .filter_map(|tile| Tile::deserialize(tile).ok())
+            .map(|tile| BoundingBox::tile(tile.position.x, tile.position.y));
+
+        BlueprintSummary {
+            entities: entities.len(),
+            tiles: tiles.len(),
+            bounding_box: entity_boxes.chain(tile_boxes).reduce(BoundingBox::union),
+        }
+    }
+}
+
+impl Worker {
+    pub fn from(info_args: &args::Info) -> Worker {
+        Worker {
+            source: Source::from(&info_args.source),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let blueprint_obj = match self.source.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => {
+                progress::Tracker::new_quiet_sync(CommandType::Info)
+                    .lock()
+                    .unwrap()
+                    .error_additional(err_msg);
+                exit(1);
+            }
+        };
+
+        if let BlueprintType::Invalid = BlueprintType::classify(&blueprint_obj) {
+            progress::Tracker::new_quiet_sync(CommandType::Info)
+                .lock()
+                .unwrap()
+                .error(
+                    BlueprintType::Invalid,
+                    Some("invalid blueprint!".to_string()),
+                );
+            exit(1);
+        }
+
+        for line in Worker::describe(&blueprint_obj, "", None) {
+            println!("{}", line);
+        }
+    }
+
+    /// Describes an item and its children, one line per entry.
+    /// `prefix` holds the tree branches drawn in front of the item's children,
+    /// `branch` is drawn in front of the item itself (none for the top-level item).
+    pub fn describe(unknown_bp: &Value, prefix: &str, branch: Option<&str>) -> Vec<String> {
+        let bp_type = BlueprintType::classify(unknown_bp);
+        let inner = bp_type
+            .key()
+            .and_then(|key| unknown_bp.get(key))
+            .unwrap_or(&Value::Null);
+
+        let (type_name, label) = match &bp_type {
+            BlueprintType::Invalid => ("invalid", String::new()),
+            BlueprintType::Book(label) => ("book", label.clone()),
+            BlueprintType::Blueprint(label) => ("blueprint", label.clone()),
+            BlueprintType::UpgradePlanner(label) => ("upgrade planner", label.clone()),
+            BlueprintType::DeconPlanner(label) => ("deconstruction planner", label.clone()),
+        };
+
+        let mut details: Vec<String> = vec![];
+        if let Some(version) = inner.get("version").and_then(|version| version.as_u64()) {
+            details.push(format!("v{}", common::version_string(version)));
+        }
+        match &bp_type {
+            BlueprintType::Book(_) => {
+                let children = common::book_children(unknown_bp).map_or(0, |c| c.len());
+                details.push(format!("items {}", children));
+            }
+            BlueprintType::Blueprint(_) => {
+                let summary = BlueprintSummary::from(inner);
+                details.push(format!("entities {}", summary.entities));
+                details.push(format!("tiles {}", summary.tiles));
+                if let Some(bounding_box) = summary.bounding_box {
+                    details.push(format!(
+                        "{}x{} at ({}, {})",
+                        bounding_box.width(),
+                        bounding_box.height(),
+                        bounding_box.min_x,
+                        bounding_box.min_y
+                    ));
+                }
+            }
+            _ => (),
+        }
+
+        let slot = match common::slot_index(unknown_bp) {
+            Some(index) => format!("[{}] ", index),
+            None => String::new(),
+        };
+
+        let mut lines = vec![format!(
+            "{}{}{} {}  {}",
+            branch.unwrap_or(""),
+            slot,
+            type_name.green().bold(),
+            label,
+            details.join(", ").dim()
+        )];
+
+        let has_children = common::book_children(unknown_bp).is_some_and(|c| !c.is_empty());
+        if let Some(description) = inner.get("description").and_then(|d| d.as_str()) {
+            let continuation = if has_children { "│   " } else { "    " };
+            lines.push(format!(
+                "{}{}\"{}\"",
+                prefix,
+                continuation,
+                Worker::excerpt(description).italic()
+            ));
+        }
+
+        let children = common::sorted_book_children(unknown_bp);
+        for (idx, child) in children.iter().enumerate() {
+            let is_last = idx + 1 == children.len();
+            let child_branch = format!("{}{}", prefix, if is_last { "└── " } else { "├── " });
+            let child_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
+            lines.extend(Worker::describe(child, &child_prefix, Some(&child_branch)));
+        }
+
+        lines
+    }
+
+    /// First line of a description, shortened to a fixed length
+    fn excerpt(description: &str) -> String {
+        let first_line = description.lines().next().unwrap_or_default();
+        let mut excerpt: String = first_line.chars().take(DESCRIPTION_EXCERPT_LEN).collect();
+        if excerpt.len() < description.len() {
+            excerpt.push('…');
+        }
+        excerpt
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::factorio_structs;
+    use serde_json::json;
+
+    #[test]
+    fn test_blueprint_summary() {
+        let blueprint = json!({
+            "label": "summary",
+            "version": 1,
+            "entities": [
+                {"entity_number": 1, "name": "inserter", "position": {"x": -1.5, "y": 0.5}},
+                {"entity_number": 2, "name": "pipe", "position": {"x": 2.5, "y": 3.5}}
+            ],
+            "tiles": [{"name": "concrete", "position": {"x": 4, "y": -2}}]
+        });
+
+        let summary = BlueprintSummary::from(&blueprint);
+        assert_eq!(summary.entities, 2);
+        assert_eq!(summary.tiles, 1);
+        assert_eq!(
+            summary.bounding_box,
+            Some(BoundingBox {
+                min_x: -2.0,
+                min_y: -2.0,
+                max_x: 5.0,
+                max_y: 4.0
+            })
+        );
+
+        let empty = BlueprintSummary::from(&json!({"label": "empty", "version": 1}));
+        assert_eq!(empty.entities, 0);
+        assert_eq!(empty.bounding_box, None);
+    }
+
+    #[test]
+    fn test_describe_book_tree() {
+        let book = json!({
+            factorio_structs::FACTORIO_BP_BOOK_KEY: {
+                "label": "outer",
+                "active_index": 0,
+                "version": 281479275675648u64,
+                "blueprints": [
+                    {"index": 4, "upgrade_planner": {"label": "upgrades", "settings": {}}},
+                    {"index": 0, "blueprint": {"label": "first", "version": 1}}
+                ]
+            }
+        });
+
+        let lines = Worker::describe(&book, "", None);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("outer"));
+        assert!(lines[0].contains("v1.1.61"));
+        // children are listed in slot order, not in the order they are stored
+        assert!(lines[1].starts_with("├── [0] "));
+        assert!(lines[2].starts_with("└── [4] "));
+        assert!(lines[2].contains("upgrades"));
+    }
+
+    #[test]
+    fn test_excerpt() {
+        assert_eq!(Worker::excerpt("short"), "short");
+        assert_eq!(Worker::excerpt("first\nsecond"), "first…");
+        assert_eq!(
+            Worker::excerpt(&"x".repeat(100)).chars().count(),
+            DESCRIPTION_EXCERPT_LEN + 1
+        );
+    }
+
+    #[test]
+    fn test_version_string() {
+        assert_eq!(common::version_string(281479275675648), "1.1.61");
+        assert_eq!(common::version_string(281479276920832), "1.1.80");
+    }
+}
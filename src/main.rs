@@ -1,9 +1,29 @@
 mod args;
+mod bench;
+mod bom;
+mod book;
+mod browse;
 mod common;
+mod config;
+mod destination;
 mod export;
+mod extract;
 mod factorio_structs;
+mod find;
+mod git;
+mod icons;
 mod import;
+mod info;
+mod manifest;
+mod merge;
+mod planner;
 mod progress;
+mod render;
+mod replace;
+mod restructure;
+mod show;
+mod source;
+mod transform;
 // mod test_constants;
 
 use args::*;
@@ -12,7 +32,22 @@ use clap::Parser;
 // use clap_complete::{generate, Generator, Shell};
 
 fn main() {
-    let main_args = MainCliArgs::parse();
+    let mut main_args = MainCliArgs::parse();
+
+    // settings of fbpconvert.toml stand in for flags that were not given
+    match std::env::current_dir()
+        .map_err(|_| "failed to read the current directory".to_string())
+        .and_then(|current_dir| config::Config::discover(&current_dir))
+    {
+        Ok(_config) => _config.apply(&mut main_args.command),
+        Err(err_msg) => {
+            progress::Tracker::new_quiet_sync(progress::CommandType::Config)
+                .lock()
+                .unwrap()
+                .error_additional(err_msg);
+            std::process::exit(1);
+        }
+    }
 
     // if let Some(_gen) = main_args.generator {
     //     let cmd = MainCliArgs::command();
@@ -32,5 +67,90 @@ fn main() {
             let export_worker = export::Worker::from(_cmd_type);
             export_worker.exec();
         }
+
+        MainSubCommands::Bench(_bench_args) => {
+            let bench_worker = bench::Worker::from(_bench_args);
+            bench_worker.exec();
+        }
+
+        MainSubCommands::Info(_info_args) => {
+            let info_worker = info::Worker::from(_info_args);
+            info_worker.exec();
+        }
+
+        MainSubCommands::Bom(_bom_args) => {
+            let bom_worker = bom::Worker::from(_bom_args);
+            bom_worker.exec();
+        }
+
+        MainSubCommands::Extract(_extract_args) => {
+            let extract_worker = extract::Worker::from(_extract_args);
+            extract_worker.exec();
+        }
+
+        MainSubCommands::Merge(_merge_args) => {
+            let merge_worker = merge::Worker::from(_merge_args);
+            merge_worker.exec();
+        }
+
+        MainSubCommands::Restructure(_restructure_args) => {
+            let restructure_worker = restructure::Worker::from(_restructure_args);
+            restructure_worker.exec();
+        }
+
+        MainSubCommands::Find(_find_args) => {
+            let find_worker = find::Worker::from(_find_args);
+            find_worker.exec();
+        }
+
+        MainSubCommands::Replace(_replace_args) => {
+            let replace_worker = replace::Worker::from(_replace_args);
+            replace_worker.exec();
+        }
+
+        MainSubCommands::ApplyPlanner(_planner_args) => {
+            let planner_worker = planner::Worker::from(_planner_args);
+            planner_worker.exec();
+        }
+
+        MainSubCommands::Transform(_transform_args) => {
+            let transform_worker = transform::Worker::from(_transform_args);
+            transform_worker.exec();
+        }
+
+        MainSubCommands::Render(_render_args) => {
+            let render_worker = render::Worker::from(_render_args);
+            render_worker.exec();
+        }
+
+        MainSubCommands::Show(_show_args) => {
+            let show_worker = show::Worker::from(_show_args);
+            show_worker.exec();
+        }
+
+        MainSubCommands::Browse(_browse_args) => {
+            let browse_worker = browse::Worker::from(_browse_args);
+            browse_worker.exec();
+        }
+
+        MainSubCommands::Icons(_icons_args) => {
+            let icons_worker = icons::Worker::from(_icons_args);
+            icons_worker.exec();
+        }
+
+        MainSubCommands::Check(_check_args) => {
+            let check_worker = manifest::Worker::from(_check_args);
+            check_worker.exec();
+        }
+
+        MainSubCommands::Config(_cmd_type) => {
+            let config_worker = config::Worker::from(_cmd_type);
+            config_worker.exec();
+        }
+
+        MainSubCommands::Book(_cmd_type) => {
+            let book_worker = book::Worker::from(_cmd_type);
+            book_worker.exec();
+        }
     }
 }
@@ -0,0 +1,590 @@
+//! Integrity manifest of a tree created by import.
+//!
+//! Import writes an fbpconvert.lock at the tree root, listing every JSON file with the label,
+//! type and slot path of its item and a hash of its contents. The check command compares
+//! the tree against it to find files edited by hand, deleted or added since the import.
+//! Commands editing the tree in place record their own edits with [`Refresh`].
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use crossterm::style::Stylize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::args;
+use crate::common::{self, BlueprintType};
+use crate::extract::PATH_SEPARATOR;
+use crate::progress::{self, CommandType};
+
+/// Name of the manifest file, at the tree root
+pub const MANIFEST_FILE_NAME: &str = "fbpconvert.lock";
+
+/// Manifest format, bumped when entries change
+const MANIFEST_VERSION: u32 = 1;
+
+/// Type of the entry of a file that is not a blueprint, book or planner
+pub const INVALID_ITEM_TYPE: &str = "invalid";
+
+/// A file of the tree as it was written by import
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub label: String,
+    /// Key of the item type, such as "blueprint" or "blueprint_book", or "invalid"
+    #[serde(rename = "type")]
+    pub item_type: String,
+    /// Slot indices from the top-level book down to the item, "3/1". Empty for the top level
+    pub slot: String,
+    /// SHA-256 of the file contents, in hex
+    pub sha256: String,
+}
+
+/// Files of a tree by their path relative to the tree root, with "/" separators
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub version: u32,
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+/// Differences between a manifest and the tree, as paths relative to the tree root
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+    pub added: Vec<String>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.deleted.is_empty() && self.added.is_empty()
+    }
+}
+
+impl Manifest {
+    /// Lists the JSON files of the tree with their current contents
+    pub fn build(tree_root: &Path) -> Result<Manifest, String> {
+        let tree_files = match common::tree_json_files(tree_root) {
+            Ok(_files) => _files,
+            Err(_) => return Err(format!("failed to list the files of {:?}", tree_root)),
+        };
+
+        Ok(Manifest {
+            version: MANIFEST_VERSION,
+            files: Manifest::entries(tree_root, tree_files)?,
+        })
+    }
+
+    /// Entries of the files by their path relative to the tree root.
+    /// Files that are not blueprints, books or planners are listed with the invalid type
+    /// and no label, so that changes to them are still noticed
+    fn entries(
+        tree_root: &Path,
+        tree_files: Vec<PathBuf>,
+    ) -> Result<BTreeMap<String, ManifestEntry>, String> {
+        // slot index of each book directory, read from its dotfile
+        let mut dir_slots: HashMap<PathBuf, Option<u64>> = HashMap::new();
+        let mut files = BTreeMap::new();
+        for file_path in tree_files {
+            let contents = match fs::read(&file_path) {
+                Ok(_contents) => _contents,
+                Err(_) => return Err(format!("failed to read {:?}", file_path)),
+            };
+            let item: Value = serde_json::from_slice(&contents).unwrap_or_default();
+            let bp_type = BlueprintType::classify(&item);
+            let (item_type, label) = match (bp_type.key(), bp_type) {
+                (Some(key), BlueprintType::Book(label))
+                | (Some(key), BlueprintType::Blueprint(label))
+                | (Some(key), BlueprintType::UpgradePlanner(label))
+                | (Some(key), BlueprintType::DeconPlanner(label)) => (key.to_string(), label),
+                _ => (INVALID_ITEM_TYPE.to_string(), String::new()),
+            };
+
+            // a dotfile describes the book directory it is in
+            let is_dot_file = file_path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            let mut slots: Vec<String> = file_path
+                .parent()
+                .into_iter()
+                .flat_map(|dir| dir.ancestors())
+                .take_while(|dir| dir.starts_with(tree_root) && *dir != tree_root)
+                .skip(is_dot_file as usize)
+                .filter_map(|dir| {
+                    *dir_slots
+                        .entry(dir.to_path_buf())
+                        .or_insert_with(|| book_slot(dir))
+                })
+                .map(|index| index.to_string())
+                .collect();
+            slots.reverse();
+            if let Some(index) = common::slot_index(&item) {
+                slots.push(index.to_string());
+            }
+
+            files.insert(
+                relative_path(tree_root, &file_path),
+                ManifestEntry {
+                    label,
+                    item_type,
+                    slot: slots.join(&PATH_SEPARATOR.to_string()),
+                    sha256: sha256_hex(&contents),
+                },
+            );
+        }
+
+        Ok(files)
+    }
+
+    /// Reads the manifest at the tree root, None if there is none
+    pub fn read(tree_root: &Path) -> Result<Option<Manifest>, String> {
+        let manifest_path = tree_root.join(MANIFEST_FILE_NAME);
+        if !manifest_path.is_file() {
+            return Ok(None);
+        }
+        match fs::read_to_string(&manifest_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+        {
+            Some(_manifest) => Ok(Some(_manifest)),
+            None => Err(format!("failed to read {:?}", manifest_path)),
+        }
+    }
+
+    /// Writes the manifest at the tree root
+    pub fn write(&self, tree_root: &Path) -> Result<(), String> {
+        let manifest_path = tree_root.join(MANIFEST_FILE_NAME);
+        match File::create(&manifest_path)
+            .and_then(|manifest_file| common::json_write_pretty(manifest_file, self))
+        {
+            Ok(()) => Ok(()),
+            Err(_) => Err(format!("failed to write {:?}", manifest_path)),
+        }
+    }
+
+    /// Compares the manifest with the current state of the tree
+    pub fn changes(&self, current: &Manifest) -> Changes {
+        let mut changes = Changes::default();
+        for (path, entry) in &self.files {
+            match current.files.get(path) {
+                Some(_current) if _current.sha256 != entry.sha256 => {
+                    changes.modified.push(path.clone())
+                }
+                Some(_) => (),
+                None => changes.deleted.push(path.clone()),
+            }
+        }
+        changes.added = current
+            .files
+            .keys()
+            .filter(|path| !self.files.contains_key(*path))
+            .cloned()
+            .collect();
+        changes
+    }
+}
+
+/// Records the files import wrote for an item in the manifest of the tree, after import.
+/// Entries of other items are kept as they are, so their edits are still reported.
+/// `item_name` is the file name of the imported item
+pub fn update(tree_root: &Path, item_name: &str) -> Result<(), String> {
+    let mut manifest = match Manifest::read(tree_root)? {
+        Some(_manifest) => _manifest,
+        None => Manifest {
+            version: MANIFEST_VERSION,
+            files: BTreeMap::new(),
+        },
+    };
+    manifest.version = MANIFEST_VERSION;
+    manifest
+        .files
+        .retain(|path, _| !is_item_path(path, item_name));
+
+    let mut item_files = vec![];
+    for item_path in [
+        tree_root.join(item_name),
+        tree_root.join(format!("{}.json", item_name)),
+    ] {
+        if !item_path.exists() {
+            continue;
+        }
+        match common::tree_json_files(&item_path) {
+            Ok(_files) => item_files.extend(_files),
+            Err(_) => return Err(format!("failed to list the files of {:?}", item_path)),
+        }
+    }
+    manifest
+        .files
+        .extend(Manifest::entries(tree_root, item_files)?);
+    manifest.write(tree_root)
+}
+
+/// Whether a manifest path belongs to the item with this file name at the tree root:
+/// its JSON file, or any file of its book directory
+pub fn is_item_path(path: &str, item_name: &str) -> bool {
+    path == format!("{}.json", item_name)
+        || path
+            .strip_prefix(item_name)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Keeps the manifest of a tree up to date with the files a command edits in place,
+/// under a file or directory of the tree. Files that differed from the manifest before
+/// the command are left as they were listed, so edits made by hand are still reported
+pub struct Refresh {
+    tree_root: PathBuf,
+    /// Scope of the refresh, relative to the tree root. Empty for the whole tree
+    scope: String,
+    /// Entries of the files modified before the command
+    modified: BTreeMap<String, ManifestEntry>,
+    /// Files not in the manifest before the command
+    added: BTreeSet<String>,
+    /// Entries of the files deleted before the command
+    deleted: BTreeMap<String, ManifestEntry>,
+}
+
+impl Refresh {
+    /// Notes the files under the path that differ from the manifest, before the command
+    /// edits them. Returns None if the path is not inside a tree with a manifest
+    pub fn prepare(path: &Path) -> Result<Option<Refresh>, String> {
+        let scope_path = match path.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => path,
+        };
+        let scope_path = match scope_path.canonicalize() {
+            Ok(_path) => _path,
+            Err(_) => return Ok(None),
+        };
+        let tree_root = match find_tree_root(&scope_path) {
+            Some(_root) => _root,
+            None => return Ok(None),
+        };
+        let manifest = match Manifest::read(&tree_root)? {
+            Some(_manifest) => _manifest,
+            None => return Ok(None),
+        };
+
+        let scope = relative_path(&tree_root, &scope_path);
+        let current = Refresh::scope_entries(&tree_root, &scope_path)?;
+        let mut refresh = Refresh {
+            tree_root,
+            scope,
+            modified: BTreeMap::new(),
+            added: current
+                .keys()
+                .filter(|path| !manifest.files.contains_key(*path))
+                .cloned()
+                .collect(),
+            deleted: BTreeMap::new(),
+        };
+        for (path, entry) in manifest.files {
+            if !refresh.in_scope(&path) {
+                continue;
+            }
+            match current.get(&path) {
+                Some(_current) if _current.sha256 != entry.sha256 => {
+                    refresh.modified.insert(path, entry);
+                }
+                Some(_) => (),
+                None => {
+                    refresh.deleted.insert(path, entry);
+                }
+            }
+        }
+        Ok(Some(refresh))
+    }
+
+    /// Records the files under the path as the command left them
+    pub fn finish(&self) -> Result<(), String> {
+        let mut manifest = match Manifest::read(&self.tree_root)? {
+            Some(_manifest) => _manifest,
+            None => return Ok(()),
+        };
+        manifest.files.retain(|path, _| !self.in_scope(path));
+
+        let scope_path = self.tree_root.join(&self.scope);
+        let current = match scope_path.exists() {
+            true => Refresh::scope_entries(&self.tree_root, &scope_path)?,
+            false => BTreeMap::new(),
+        };
+        for (path, entry) in current {
+            if let Some(_before) = self.modified.get(&path) {
+                manifest.files.insert(path, _before.clone());
+            } else if !self.added.contains(&path) {
+                manifest.files.insert(path, entry);
+            }
+        }
+        for (path, entry) in &self.deleted {
+            manifest
+                .files
+                .entry(path.clone())
+                .or_insert_with(|| entry.clone());
+        }
+        manifest.write(&self.tree_root)
+    }
+
+    fn in_scope(&self, path: &str) -> bool {
+        self.scope.is_empty()
+            || path == self.scope
+            || path
+                .strip_prefix(&self.scope)
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
+
+    fn scope_entries(
+        tree_root: &Path,
+        scope_path: &Path,
+    ) -> Result<BTreeMap<String, ManifestEntry>, String> {
+        match common::tree_json_files(scope_path) {
+            Ok(_files) => Manifest::entries(tree_root, _files),
+            Err(_) => Err(format!("failed to list the files of {:?}", scope_path)),
+        }
+    }
+}
+
+/// Closest directory holding a manifest, starting from the path itself
+pub fn find_tree_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| dir.join(MANIFEST_FILE_NAME).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Warnings about the files an export of the path includes that were added or modified
+/// since the last import. Nothing is reported for trees without a manifest
+pub fn export_warnings(source_path: &Path) -> Vec<String> {
+    let source_path = match source_path.canonicalize() {
+        Ok(_path) => _path,
+        Err(_) => return vec![],
+    };
+    let tree_root = match find_tree_root(&source_path) {
+        Some(_root) => _root,
+        None => return vec![],
+    };
+    let manifest = match Manifest::read(&tree_root) {
+        Ok(Some(_manifest)) => _manifest,
+        Ok(None) => return vec![],
+        Err(err_msg) => return vec![err_msg],
+    };
+    let current = match Manifest::build(&tree_root) {
+        Ok(_current) => _current,
+        Err(err_msg) => return vec![err_msg],
+    };
+
+    let source_files: Vec<String> = match common::tree_json_files(&source_path) {
+        Ok(_files) => _files
+            .iter()
+            .map(|file_path| relative_path(&tree_root, file_path))
+            .collect(),
+        Err(_) => return vec![],
+    };
+    let changes = manifest.changes(&current);
+    source_files
+        .into_iter()
+        .filter_map(|path| {
+            if changes.modified.contains(&path) {
+                Some(format!("modified since import: {}", path))
+            } else if changes.added.contains(&path) {
+                Some(format!("not in {}: {}", MANIFEST_FILE_NAME, path))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Slot index of a book directory, from its dotfile
+fn book_slot(book_dir: &Path) -> Option<u64> {
+    let dir_name = book_dir.file_name()?.to_string_lossy();
+    let dot_file = fs::read_to_string(book_dir.join(format!(".{}.json", dir_name))).ok()?;
+    common::slot_index(&serde_json::from_str(&dot_file).ok()?)
+}
+
+fn relative_path(tree_root: &Path, file_path: &Path) -> String {
+    file_path
+        .strip_prefix(tree_root)
+        .unwrap_or(file_path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn sha256_hex(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub struct Worker {
+    pub tree: String,
+}
+
+impl Worker {
+    pub fn from(check_args: &args::Check) -> Worker {
+        Worker {
+            tree: check_args.tree.clone().unwrap_or(".".to_string()),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let check_error = |err_msg: String| -> ! {
+            progress::Tracker::new_quiet_sync(CommandType::Check)
+                .lock()
+                .unwrap()
+                .error_additional(err_msg);
+            exit(1);
+        };
+
+        let tree_root = Path::new(&self.tree);
+        let manifest = match Manifest::read(tree_root) {
+            Ok(Some(_manifest)) => _manifest,
+            Ok(None) => check_error(format!("no {} in {:?}", MANIFEST_FILE_NAME, tree_root)),
+            Err(err_msg) => check_error(err_msg),
+        };
+        let current = match Manifest::build(tree_root) {
+            Ok(_current) => _current,
+            Err(err_msg) => check_error(err_msg),
+        };
+
+        let changes = manifest.changes(&current);
+        for path in &changes.modified {
+            println!("{}\t{}", "modified".yellow().bold(), path);
+        }
+        for path in &changes.deleted {
+            println!("{}\t{}", "deleted".red().bold(), path);
+        }
+        for path in &changes.added {
+            println!("{}\t\t{}", "added".green().bold(), path);
+        }
+
+        if !changes.is_empty() {
+            exit(1);
+        }
+        println!(
+            "{} files match {}",
+            manifest.files.len(),
+            MANIFEST_FILE_NAME
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::import;
+    use serde_json::json;
+
+    #[test]
+    fn test_manifest_changes() {
+        let dest = common::TestDir::new("manifest");
+
+        let book = json!({
+            "blueprint_book": {
+                "item": "blueprint-book",
+                "label": "outer",
+                "active_index": 0,
+                "version": 1,
+                "blueprints": [
+                    {"index": 2, "blueprint": {"item": "blueprint", "label": "a", "version": 1}},
+                    {
+                        "index": 5,
+                        "blueprint_book": {
+                            "item": "blueprint-book",
+                            "label": "inner",
+                            "active_index": 0,
+                            "version": 1,
+                            "blueprints": [
+                                {"index": 1, "blueprint": {"item": "blueprint", "label": "b", "version": 1}}
+                            ]
+                        }
+                    }
+                ]
+            }
+        });
+        let tracker = progress::Tracker::new_sync(CommandType::Import);
+        import::Worker::unknown_write(&tracker, book, &dest, common::TreeStyle::default());
+        update(&dest, "outer").unwrap();
+
+        let manifest = Manifest::read(&dest).unwrap().unwrap();
+        let paths: Vec<&str> = manifest.files.keys().map(String::as_str).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "outer/.outer.json",
+                "outer/a.json",
+                "outer/inner/.inner.json",
+                "outer/inner/b.json"
+            ]
+        );
+        let slots: Vec<&str> = manifest
+            .files
+            .values()
+            .map(|entry| entry.slot.as_str())
+            .collect();
+        assert_eq!(slots, vec!["", "2", "5", "5/1"]);
+        assert_eq!(manifest.files["outer/inner/b.json"].label, "b");
+        assert_eq!(manifest.files["outer/inner/b.json"].item_type, "blueprint");
+        assert!(manifest
+            .changes(&Manifest::build(&dest).unwrap())
+            .is_empty());
+
+        // edit, delete and add a file
+        let outer = dest.join("outer");
+        let a_path = outer.join("a.json");
+        let edited = fs::read_to_string(&a_path)
+            .unwrap()
+            .replace("\"a\"", "\"edited\"");
+        fs::write(&a_path, edited).unwrap();
+        fs::remove_file(outer.join("inner").join("b.json")).unwrap();
+        fs::write(
+            outer.join("c.json"),
+            json!({"blueprint": {"item": "blueprint", "label": "c", "version": 1}}).to_string(),
+        )
+        .unwrap();
+
+        let changes = manifest.changes(&Manifest::build(&dest).unwrap());
+        assert_eq!(changes.modified, vec!["outer/a.json"]);
+        assert_eq!(changes.deleted, vec!["outer/inner/b.json"]);
+        assert_eq!(changes.added, vec!["outer/c.json"]);
+
+        let warnings = export_warnings(&outer);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].ends_with("outer/a.json"));
+        assert!(warnings[1].ends_with("outer/c.json"));
+        assert!(export_warnings(&outer.join("inner")).is_empty());
+
+        // a later import records only its own files, the edits above are still reported
+        let other = json!({"blueprint": {"item": "blueprint", "label": "other", "version": 1}});
+        import::Worker::unknown_write(&tracker, other, &dest, common::TreeStyle::default());
+        update(&dest, "other").unwrap();
+        let manifest = Manifest::read(&dest).unwrap().unwrap();
+        assert!(manifest.files.contains_key("other.json"));
+        let changes = manifest.changes(&Manifest::build(&dest).unwrap());
+        assert_eq!(changes.modified, vec!["outer/a.json"]);
+        assert_eq!(changes.deleted, vec!["outer/inner/b.json"]);
+        assert_eq!(changes.added, vec!["outer/c.json"]);
+    }
+
+    #[test]
+    fn test_manifest_invalid_files() {
+        let dest = common::TestDir::new("manifest-invalid");
+        fs::write(dest.join("notes.json"), "not json").unwrap();
+        fs::write(dest.join("other.json"), "{\"other\": 1}").unwrap();
+
+        let manifest = Manifest::build(&dest).unwrap();
+        let notes = &manifest.files["notes.json"];
+        assert_eq!(notes.item_type, INVALID_ITEM_TYPE);
+        assert_eq!(notes.label, "");
+        assert_eq!(manifest.files["other.json"].item_type, INVALID_ITEM_TYPE);
+
+        // an edit is reported as a modification, not as a deleted and an added file
+        fs::write(dest.join("notes.json"), "still not json").unwrap();
+        let changes = manifest.changes(&Manifest::build(&dest).unwrap());
+        assert_eq!(changes.modified, vec!["notes.json"]);
+        assert!(changes.deleted.is_empty() && changes.added.is_empty());
+    }
+}
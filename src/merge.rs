@@ -0,0 +1,286 @@
+//! Merges two blueprint books into one.
+
+use std::process::exit;
+
+use serde_json::Value;
+
+use crate::args::{self, BookPolicy, LabelPolicy};
+use crate::common::{self, BlueprintType, CompressionLevel};
+use crate::destination::Destination;
+use crate::factorio_structs;
+use crate::progress::{self, CommandType};
+use crate::source::Source;
+
+pub struct Worker {
+    pub left: Source,
+    pub right: Source,
+    pub policy: Policy,
+    pub destination: Destination,
+    pub compression: CompressionLevel,
+}
+
+/// How items found in both books are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Policy {
+    /// Blueprints and planners with the same label
+    pub labels: LabelPolicy,
+    /// Sub-books with the same label
+    pub books: BookPolicy,
+}
+
+impl Worker {
+    pub fn from(merge_args: &args::Merge) -> Worker {
+        Worker {
+            left: Source::from(&merge_args.left),
+            right: Source::from(&merge_args.right),
+            policy: Policy {
+                labels: merge_args.same_label,
+                books: merge_args.sub_books,
+            },
+            destination: Destination::from(&merge_args.output),
+            compression: merge_args.output.compression.unwrap_or_default(),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let merge_error = |err_msg: String| -> ! {
+            progress::Tracker::new_quiet_sync(CommandType::Merge)
+                .lock()
+                .unwrap()
+                .error_additional(err_msg);
+            exit(1);
+        };
+
+        let read_book = |source: &Source| match source.read() {
+            Ok(_obj) => match BlueprintType::classify(&_obj) {
+                BlueprintType::Book(_) => _obj,
+                _ => merge_error(format!("{:?} is not a blueprint book", source)),
+            },
+            Err(err_msg) => merge_error(err_msg),
+        };
+        let left = read_book(&self.left);
+        let right = read_book(&self.right);
+
+        let merged = merge_books(left, right, self.policy);
+        if let Err(err_msg) = self.destination.write(merged, self.compression) {
+            merge_error(err_msg);
+        }
+    }
+}
+
+/// Combines the right book into the left one.
+///
+/// The left book's label, description and icons are kept. Its items come first in slot order,
+/// followed by the items only found in the right book. Slot indices are reassigned without gaps
+/// and the left book's active item stays active.
+pub fn merge_books(mut left: Value, mut right: Value, policy: Policy) -> Value {
+    let (mut merged, active_pos) = common::take_book_children(&mut left);
+    let (right_children, _) = common::take_book_children(&mut right);
+
+    // only items of the left book are matched, so same-label items within the right book are kept
+    let left_len = merged.len();
+    for right_child in right_children {
+        let right_key = item_key(&right_child);
+        let same = merged[..left_len]
+            .iter()
+            .position(|left_child| right_key.is_some() && item_key(left_child) == right_key);
+
+        match (same, right_key) {
+            (None, _) => merged.push(right_child),
+            (Some(pos), Some((factorio_structs::FACTORIO_BP_BOOK_KEY, _))) => {
+                let left_child = merged[pos].take();
+                merged[pos] = match policy.books {
+                    BookPolicy::Merge => merge_books(left_child, right_child, policy),
+                    BookPolicy::Nest => nest_book(left_child, right_child),
+                };
+            }
+            (Some(pos), _) => match policy.labels {
+                LabelPolicy::KeepBoth => merged.push(right_child),
+                LabelPolicy::Left => (),
+                LabelPolicy::Newer => {
+                    if item_version(&right_child) > item_version(&merged[pos]) {
+                        merged[pos] = right_child;
+                    }
+                }
+            },
+        }
+    }
+
+    common::set_book_children(&mut left, merged, active_pos);
+    left
+}
+
+/// Places the right book inside the left book, after its last item.
+/// The contents of the two books are not merged, only renumbered
+fn nest_book(mut left: Value, mut right: Value) -> Value {
+    let (mut children, active_pos) = common::take_book_children(&mut left);
+
+    let (right_children, right_active_pos) = common::take_book_children(&mut right);
+    common::set_book_children(&mut right, right_children, right_active_pos);
+    children.push(right);
+
+    common::set_book_children(&mut left, children, active_pos);
+    left
+}
+
+/// Identifies an item by its type and label.
+/// Labels are compared as file names, as trees created by import store them renamed
+fn item_key(unknown_bp: &Value) -> Option<(&'static str, String)> {
+    let bp_type = BlueprintType::classify(unknown_bp);
+    match (bp_type.key(), bp_type) {
+        (Some(key), BlueprintType::Book(label))
+        | (Some(key), BlueprintType::Blueprint(label))
+        | (Some(key), BlueprintType::UpgradePlanner(label))
+        | (Some(key), BlueprintType::DeconPlanner(label)) => {
+            Some((key, common::file_rename(label)))
+        }
+        _ => None,
+    }
+}
+
+/// Game version an item was saved with
+fn item_version(unknown_bp: &Value) -> u64 {
+    BlueprintType::classify(unknown_bp)
+        .key()
+        .and_then(|key| unknown_bp[key]["version"].as_u64())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn blueprint(label: &str, index: u64, version: u64) -> Value {
+        json!({"index": index, "blueprint": {"item": "blueprint", "label": label, "version": version}})
+    }
+
+    fn book(label: &str, active_index: u64, children: Vec<Value>) -> Value {
+        json!({"blueprint_book": {
+            "item": "blueprint-book",
+            "label": label,
+            "active_index": active_index,
+            "version": 1,
+            "blueprints": children
+        }})
+    }
+
+    /// Labels and versions of the children, checking that slots are numbered without gaps
+    fn contents(bp_book: &Value) -> Vec<(String, u64)> {
+        common::book_children(bp_book)
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(pos, child)| {
+                assert_eq!(common::slot_index(child), Some(pos as u64));
+                let label = match BlueprintType::classify(child) {
+                    BlueprintType::Book(label) | BlueprintType::Blueprint(label) => label,
+                    _ => String::new(),
+                };
+                (label, item_version(child))
+            })
+            .collect()
+    }
+
+    fn labels(expected: &[(&str, u64)]) -> Vec<(String, u64)> {
+        expected
+            .iter()
+            .map(|(label, version)| (label.to_string(), *version))
+            .collect()
+    }
+
+    fn left() -> Value {
+        book(
+            "team",
+            6,
+            vec![blueprint("smelting", 6, 2), blueprint("mall", 2, 1)],
+        )
+    }
+
+    fn right() -> Value {
+        book(
+            "personal",
+            0,
+            vec![blueprint("smelting", 0, 3), blueprint("trains", 9, 1)],
+        )
+    }
+
+    #[test]
+    fn test_merge_label_policies() {
+        let policy = |labels| Policy {
+            labels,
+            books: BookPolicy::Merge,
+        };
+
+        let keep_both = merge_books(left(), right(), policy(LabelPolicy::KeepBoth));
+        assert_eq!(
+            contents(&keep_both),
+            labels(&[("mall", 1), ("smelting", 2), ("smelting", 3), ("trains", 1)])
+        );
+        assert_eq!(keep_both["blueprint_book"]["label"], "team");
+        // the active item followed its new slot
+        assert_eq!(keep_both["blueprint_book"]["active_index"], 1);
+
+        let prefer_left = merge_books(left(), right(), policy(LabelPolicy::Left));
+        assert_eq!(
+            contents(&prefer_left),
+            labels(&[("mall", 1), ("smelting", 2), ("trains", 1)])
+        );
+
+        let newer = merge_books(left(), right(), policy(LabelPolicy::Newer));
+        assert_eq!(
+            contents(&newer),
+            labels(&[("mall", 1), ("smelting", 3), ("trains", 1)])
+        );
+    }
+
+    #[test]
+    fn test_merge_sub_books() {
+        let left = book(
+            "team",
+            0,
+            vec![json!({"index": 3, "blueprint_book": left()["blueprint_book"]})],
+        );
+        let right = book(
+            "personal",
+            0,
+            vec![json!({"index": 0, "blueprint_book": {
+                "label": "team", "active_index": 0, "version": 1,
+                "blueprints": [blueprint("trains", 4, 1)]
+            }})],
+        );
+
+        let merged = merge_books(
+            left.clone(),
+            right.clone(),
+            Policy {
+                labels: LabelPolicy::Left,
+                books: BookPolicy::Merge,
+            },
+        );
+        assert_eq!(contents(&merged), labels(&[("team", 1)]));
+        assert_eq!(
+            contents(&merged["blueprint_book"]["blueprints"][0]),
+            labels(&[("mall", 1), ("smelting", 2), ("trains", 1)])
+        );
+
+        let nested = merge_books(
+            left,
+            right,
+            Policy {
+                labels: LabelPolicy::Left,
+                books: BookPolicy::Nest,
+            },
+        );
+        let inner = &nested["blueprint_book"]["blueprints"][0];
+        assert_eq!(
+            contents(inner),
+            labels(&[("mall", 1), ("smelting", 2), ("team", 1)])
+        );
+        assert_eq!(
+            contents(&inner["blueprint_book"]["blueprints"][2]),
+            labels(&[("trains", 1)])
+        );
+    }
+}
@@ -0,0 +1,479 @@
+//! Applies upgrade and deconstruction planners to blueprints without the game.
+
+use std::collections::HashSet;
+use std::process::exit;
+
+use serde_json::Value;
+
+use crate::args;
+use crate::common::{self, BlueprintType, CompressionLevel};
+use crate::destination::Destination;
+use crate::factorio_structs::importable;
+use crate::progress::{self, CommandType};
+use crate::replace::{self, NameMap};
+use crate::source::Source;
+
+pub struct Worker {
+    pub planner: Source,
+    pub target: Source,
+    pub destination: Destination,
+    pub compression: CompressionLevel,
+}
+
+/// Replacements of an upgrade planner, split by the type of thing they replace
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpgradeMappings {
+    /// Entity names
+    pub entities: NameMap,
+    /// Item names, used for modules requested by entities
+    pub items: NameMap,
+}
+
+impl UpgradeMappings {
+    /// Reads the mapper entries of an upgrade planner's settings.
+    /// Incomplete mappers are skipped. If several mappers replace the same name,
+    /// the one in the lowest slot applies, like in the game
+    pub fn from(settings: &Value) -> UpgradeMappings {
+        let mut mappers: Vec<&Value> = settings
+            .get("mappers")
+            .and_then(|mappers| mappers.as_array())
+            .into_iter()
+            .flatten()
+            .collect();
+        mappers.sort_by_key(|mapper| common::slot_index(mapper));
+
+        let mut mappings = UpgradeMappings::default();
+        for mapper in mappers {
+            let signal = |end: &str| -> Option<(&str, &str)> {
+                let signal = mapper.get(end)?;
+                Some((signal.get("type")?.as_str()?, signal.get("name")?.as_str()?))
+            };
+            let (from_type, from_name, to_name) = match (signal("from"), signal("to")) {
+                (Some((from_type, from_name)), Some((to_type, to_name)))
+                    if from_type == to_type =>
+                {
+                    (from_type, from_name, to_name)
+                }
+                _ => continue,
+            };
+
+            let name_map = match from_type {
+                "entity" => &mut mappings.entities,
+                "item" => &mut mappings.items,
+                _ => continue,
+            };
+            name_map
+                .entry(from_name.to_string())
+                .or_insert_with(|| to_name.to_string());
+        }
+
+        mappings
+    }
+
+    /// Upgrades every entity of a blueprint or book.
+    /// Returns the number of entities changed
+    pub fn apply(&self, unknown_bp: &mut Value) -> usize {
+        let mut upgraded = 0;
+        common::visit_blueprints_mut(unknown_bp, &mut |blueprint| {
+            for entity in blueprint
+                .get_mut("entities")
+                .and_then(|entities| entities.as_array_mut())
+                .into_iter()
+                .flatten()
+            {
+                let changes = replace::replace_entity_name(entity, &self.entities)
+                    + replace::replace_item_requests(entity, &self.items);
+                if changes > 0 {
+                    upgraded += 1;
+                }
+            }
+        });
+        upgraded
+    }
+}
+
+/// Whether the names of a filter list are selected or excluded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Whitelist,
+    Blacklist,
+}
+
+/// Entity or tile filters of a deconstruction planner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameFilter {
+    pub mode: FilterMode,
+    pub names: HashSet<String>,
+}
+
+impl NameFilter {
+    /// Reads a filter list and its mode (0: whitelist, 1: blacklist) from the planner's settings
+    fn from(settings: &Value, filters_key: &str, mode_key: &str) -> NameFilter {
+        NameFilter {
+            mode: match settings.get(mode_key).and_then(|mode| mode.as_u64()) {
+                Some(1) => FilterMode::Blacklist,
+                _ => FilterMode::Whitelist,
+            },
+            names: settings
+                .get(filters_key)
+                .and_then(|filters| filters.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|filter| filter.get("name")?.as_str())
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    /// Checks if the filter selects a name. An empty whitelist selects everything
+    pub fn selects(&self, name: &str) -> bool {
+        match self.mode {
+            FilterMode::Whitelist => self.names.is_empty() || self.names.contains(name),
+            FilterMode::Blacklist => !self.names.contains(name),
+        }
+    }
+}
+
+/// When a deconstruction planner removes tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileSelection {
+    /// Only if no entities are selected
+    Normal,
+    Always,
+    Never,
+    /// Tiles only, entities are never selected
+    Only,
+}
+
+/// Selection settings of a deconstruction planner.
+/// The trees and rocks only setting is ignored, blueprints contain neither
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeconFilters {
+    pub entities: NameFilter,
+    pub tiles: NameFilter,
+    pub tile_selection: TileSelection,
+}
+
+impl DeconFilters {
+    pub fn from(settings: &Value) -> DeconFilters {
+        DeconFilters {
+            entities: NameFilter::from(settings, "entity_filters", "entity_filter_mode"),
+            tiles: NameFilter::from(settings, "tile_filters", "tile_filter_mode"),
+            tile_selection: match settings
+                .get("tile_selection_mode")
+                .and_then(|mode| mode.as_u64())
+            {
+                Some(1) => TileSelection::Always,
+                Some(2) => TileSelection::Never,
+                Some(3) => TileSelection::Only,
+                _ => TileSelection::Normal,
+            },
+        }
+    }
+
+    /// Removes the selected entities and tiles from every blueprint of a blueprint or book,
+    /// along with the wires and train schedules of removed entities.
+    /// Each blueprint is treated as one selection. Returns the number of entities and tiles removed
+    pub fn apply(&self, unknown_bp: &mut Value) -> (usize, usize) {
+        let (mut removed_entities, mut removed_tiles) = (0, 0);
+
+        common::visit_blueprints_mut(unknown_bp, &mut |blueprint| {
+            let selects_entity = |entity: &Value| {
+                self.tile_selection != TileSelection::Only
+                    && entity
+                        .get("name")
+                        .and_then(|name| name.as_str())
+                        .is_some_and(|name| self.entities.selects(name))
+            };
+
+            let mut removed_numbers: HashSet<u64> = HashSet::new();
+            let mut removed_here = 0;
+            if let Some(entities) = blueprint
+                .get_mut("entities")
+                .and_then(|entities| entities.as_array_mut())
+            {
+                let before = entities.len();
+                entities.retain(|entity| {
+                    let selected = selects_entity(entity);
+                    if selected {
+                        removed_numbers
+                            .extend(entity.get("entity_number").and_then(|n| n.as_u64()));
+                    }
+                    !selected
+                });
+                removed_here = before - entities.len();
+                removed_entities += removed_here;
+            }
+
+            let remove_tiles = match self.tile_selection {
+                TileSelection::Normal => removed_here == 0,
+                TileSelection::Always | TileSelection::Only => true,
+                TileSelection::Never => false,
+            };
+            if let Some(tiles) = blueprint
+                .get_mut("tiles")
+                .and_then(|tiles| tiles.as_array_mut())
+                .filter(|_| remove_tiles)
+            {
+                let before = tiles.len();
+                tiles.retain(|tile| {
+                    !tile
+                        .get("name")
+                        .and_then(|name| name.as_str())
+                        .is_some_and(|name| self.tiles.selects(name))
+                });
+                removed_tiles += before - tiles.len();
+            }
+
+            if !removed_numbers.is_empty() {
+                remove_references(blueprint, &removed_numbers);
+            }
+        });
+
+        (removed_entities, removed_tiles)
+    }
+}
+
+/// Removes wires, power pole connections and train schedule entries of removed entities
+fn remove_references(blueprint: &mut Value, removed: &HashSet<u64>) {
+    let is_removed = |id: Option<&Value>| {
+        id.and_then(|id| id.as_u64())
+            .is_some_and(|id| removed.contains(&id))
+    };
+
+    for entity in blueprint
+        .get_mut("entities")
+        .and_then(|entities| entities.as_array_mut())
+        .into_iter()
+        .flatten()
+    {
+        // 1.1: {"connections": {"1": {"red": [{"entity_id": 2}]}, "Cu0": [{"entity_id": 3}]}}
+        if let Some(connections) = entity
+            .get_mut("connections")
+            .and_then(|connections| connections.as_object_mut())
+        {
+            for point in connections.values_mut() {
+                let wire_lists: Vec<&mut Value> = match point {
+                    Value::Object(colors) => colors.values_mut().collect(),
+                    wires => vec![wires],
+                };
+                for wires in wire_lists {
+                    if let Some(wires) = wires.as_array_mut() {
+                        wires.retain(|wire| !is_removed(wire.get("entity_id")));
+                    }
+                }
+            }
+        }
+
+        if let Some(neighbours) = entity
+            .get_mut("neighbours")
+            .and_then(|neighbours| neighbours.as_array_mut())
+        {
+            neighbours.retain(|neighbour| !is_removed(Some(neighbour)));
+        }
+    }
+
+    // 2.0: [[entity, connector, entity, connector], ...]
+    if let Some(wires) = blueprint
+        .get_mut("wires")
+        .and_then(|wires| wires.as_array_mut())
+    {
+        wires.retain(|wire| !is_removed(wire.get(0)) && !is_removed(wire.get(2)));
+    }
+
+    for schedule in blueprint
+        .get_mut("schedules")
+        .and_then(|schedules| schedules.as_array_mut())
+        .into_iter()
+        .flatten()
+    {
+        if let Some(locomotives) = schedule
+            .get_mut("locomotives")
+            .and_then(|locomotives| locomotives.as_array_mut())
+        {
+            locomotives.retain(|locomotive| !is_removed(Some(locomotive)));
+        }
+    }
+}
+
+impl Worker {
+    pub fn from(planner_args: &args::ApplyPlanner) -> Worker {
+        Worker {
+            planner: Source::from(&planner_args.planner),
+            target: Source::from(&planner_args.target),
+            destination: Destination::from(&planner_args.output),
+            compression: planner_args.output.compression.unwrap_or_default(),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let prog_tracker = progress::Tracker::new_quiet_sync(CommandType::ApplyPlanner);
+        let planner_error = |err_msg: String| -> ! {
+            prog_tracker.lock().unwrap().error_additional(err_msg);
+            exit(1);
+        };
+
+        let planner_obj = match self.planner.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => planner_error(err_msg),
+        };
+        let mut target_obj = match self.target.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => planner_error(err_msg),
+        };
+        if let BlueprintType::Invalid = BlueprintType::classify(&target_obj) {
+            planner_error("invalid target blueprint!".to_string());
+        }
+
+        let changed = match BlueprintType::classify(&planner_obj) {
+            BlueprintType::UpgradePlanner(_) => {
+                let planner: importable::UpgradeHead = match serde_json::from_value(planner_obj) {
+                    Ok(_planner) => _planner,
+                    Err(_) => planner_error("failed to deserialize upgrade planner".to_string()),
+                };
+                let mappings = UpgradeMappings::from(&planner.upgrade_planner.settings);
+                format!("upgraded {} entities", mappings.apply(&mut target_obj))
+            }
+            BlueprintType::DeconPlanner(_) => {
+                let planner: importable::DeconHead = match serde_json::from_value(planner_obj) {
+                    Ok(_planner) => _planner,
+                    Err(_) => {
+                        planner_error("failed to deserialize deconstruction planner".to_string())
+                    }
+                };
+                let filters = DeconFilters::from(&planner.deconstruction_planner.settings);
+                let (entities, tiles) = filters.apply(&mut target_obj);
+                format!("removed {} entities and {} tiles", entities, tiles)
+            }
+            _ => planner_error(format!("{:?} is not a planner", self.planner)),
+        };
+
+        if let Err(err_msg) = self.destination.write(target_obj, self.compression) {
+            planner_error(err_msg);
+        }
+        // the blueprint string is the only output on stdout
+        if self.destination != Destination::Stdout {
+            prog_tracker.lock().unwrap().msg(changed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn settings() -> Value {
+        json!({"mappers": [
+            {"index": 2, "from": {"type": "entity", "name": "fast-transport-belt"},
+             "to": {"type": "entity", "name": "transport-belt"}},
+            {"index": 0, "from": {"type": "entity", "name": "fast-transport-belt"},
+             "to": {"type": "entity", "name": "express-transport-belt"}},
+            {"index": 1, "from": {"type": "item", "name": "speed-module"},
+             "to": {"type": "item", "name": "speed-module-2"}},
+            {"index": 3, "from": {"type": "entity", "name": "inserter"}},
+            {"index": 4, "from": {"type": "entity", "name": "fast-splitter"},
+             "to": {"type": "item", "name": "express-splitter"}}
+        ]})
+    }
+
+    #[test]
+    fn test_upgrade_mappings() {
+        let mappings = UpgradeMappings::from(&settings());
+
+        // the mapper in the lowest slot wins
+        assert_eq!(
+            mappings.entities["fast-transport-belt"],
+            "express-transport-belt"
+        );
+        assert_eq!(mappings.items["speed-module"], "speed-module-2");
+        // incomplete mappers and mappers between types are ignored
+        assert_eq!(mappings.entities.len(), 1);
+        assert_eq!(mappings.items.len(), 1);
+    }
+
+    #[test]
+    fn test_upgrade_book() {
+        let mut book = json!({"blueprint_book": {
+            "label": "book", "active_index": 0, "version": 1,
+            "blueprints": [
+                {"index": 0, "blueprint": {"label": "bus", "version": 1, "entities": [
+                    {"entity_number": 1, "name": "fast-transport-belt", "direction": 2},
+                    {"entity_number": 2, "name": "assembling-machine-2", "items": {"speed-module": 2}},
+                    {"entity_number": 3, "name": "inserter"}
+                ]}}
+            ]
+        }});
+
+        assert_eq!(UpgradeMappings::from(&settings()).apply(&mut book), 2);
+
+        let entities = &book["blueprint_book"]["blueprints"][0]["blueprint"]["entities"];
+        assert_eq!(entities[0]["name"], "express-transport-belt");
+        assert_eq!(entities[0]["direction"], 2);
+        assert_eq!(entities[1]["items"], json!({"speed-module-2": 2}));
+        assert_eq!(entities[2]["name"], "inserter");
+    }
+
+    #[test]
+    fn test_decon_filters() {
+        let whitelist = DeconFilters::from(&json!({
+            "entity_filters": [{"index": 1, "name": "small-lamp"}],
+            "tile_filters": [{"index": 1, "name": "landfill"}],
+            "trees_and_rocks_only": true
+        }));
+        assert!(whitelist.entities.selects("small-lamp"));
+        assert!(!whitelist.entities.selects("pipe"));
+        assert_eq!(whitelist.tile_selection, TileSelection::Normal);
+
+        let blacklist = DeconFilters::from(&json!({
+            "entity_filter_mode": 1,
+            "entity_filters": [{"index": 1, "name": "small-lamp"}],
+            "tile_selection_mode": 3
+        }));
+        assert!(!blacklist.entities.selects("small-lamp"));
+        assert!(blacklist.entities.selects("pipe"));
+        // an empty whitelist selects every tile
+        assert!(blacklist.tiles.selects("landfill"));
+        assert_eq!(blacklist.tile_selection, TileSelection::Only);
+    }
+
+    #[test]
+    fn test_decon_removes_entities_and_wires() {
+        let mut blueprint = json!({"blueprint": {"label": "lamps", "version": 1,
+            "entities": [
+                {"entity_number": 1, "name": "small-electric-pole", "neighbours": [3],
+                 "connections": {"1": {"red": [{"entity_id": 2}, {"entity_id": 3}]}}},
+                {"entity_number": 2, "name": "small-lamp"},
+                {"entity_number": 3, "name": "small-electric-pole", "neighbours": [1]}
+            ],
+            "wires": [[1, 1, 2, 1], [1, 1, 3, 1]],
+            "tiles": [{"name": "landfill", "position": {"x": 0, "y": 0}}]
+        }});
+
+        let lamps = DeconFilters::from(&json!({
+            "entity_filters": [{"index": 1, "name": "small-lamp"}],
+            "tile_filters": [{"index": 1, "name": "landfill"}]
+        }));
+        assert_eq!(lamps.apply(&mut blueprint), (1, 0));
+
+        let inner = &blueprint["blueprint"];
+        assert_eq!(inner["entities"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            inner["entities"][0]["connections"]["1"]["red"],
+            json!([{"entity_id": 3}])
+        );
+        assert_eq!(inner["entities"][0]["neighbours"], json!([3]));
+        assert_eq!(inner["wires"], json!([[1, 1, 3, 1]]));
+
+        let landfill = DeconFilters::from(&json!({
+            "tile_filters": [{"index": 1, "name": "landfill"}],
+            "tile_selection_mode": 3
+        }));
+        assert_eq!(landfill.apply(&mut blueprint), (0, 1));
+        assert_eq!(
+            blueprint["blueprint"]["entities"].as_array().unwrap().len(),
+            2
+        );
+    }
+}
@@ -10,10 +10,27 @@ use crossterm::{cursor, terminal, ExecutableCommand, QueueableCommand};
 /// Type of blueprint: take from common module
 pub use crate::common::BlueprintType as ProgressType;
 
-/// Type of subcommand: import or export
+/// Type of subcommand
 pub enum CommandType {
     Import,
     Export,
+    Bench,
+    Info,
+    Bom,
+    Extract,
+    Merge,
+    Restructure,
+    Find,
+    Replace,
+    ApplyPlanner,
+    Transform,
+    Render,
+    Show,
+    Browse,
+    Icons,
+    Check,
+    Config,
+    Book,
 }
 
 /// Progress tracker for data display.
@@ -24,6 +41,8 @@ pub struct Tracker {
     pub read_books: u16,
     pub read_planners: u16,
     pub errors: u16,
+    /// Suppresses per-file messages, only the counters are updated
+    pub quiet: bool,
 }
 
 impl Tracker {
@@ -38,6 +57,7 @@ impl Tracker {
             read_books: 0,
             read_planners: 0,
             errors: 0,
+            quiet: false,
         }
     }
 
@@ -53,6 +73,23 @@ impl Tracker {
             read_books: 0,
             read_planners: 0,
             errors: 0,
+            quiet: false,
+        };
+
+        Arc::new(Mutex::new(_tracker))
+    }
+
+    /// Tracker enclosed in reference-counted mutex, without per-file messages.
+    /// Errors are still written to stdout.
+    pub fn new_quiet_sync(command: CommandType) -> Arc<Mutex<Tracker>> {
+        let _tracker = Tracker {
+            std_out: std::io::stdout(),
+            command,
+            read_blueprints: 0,
+            read_books: 0,
+            read_planners: 0,
+            errors: 0,
+            quiet: true,
         };
 
         Arc::new(Mutex::new(_tracker))
@@ -73,22 +110,23 @@ impl Tracker {
             ProgressType::UpgradePlanner(_planner) => {
                 file_name = _planner;
                 self.read_planners += 1;
-            },
+            }
             ProgressType::DeconPlanner(_planner) => {
                 file_name = _planner;
                 self.read_planners += 1;
-            },
-            ProgressType::Invalid => return
-            // {
-            //     file_name = "invalid?".to_string()
-            // },
+            }
+            ProgressType::Invalid => return,
+        }
+
+        if self.quiet {
+            return;
         }
 
         self.std_out
             .queue(terminal::Clear(terminal::ClearType::CurrentLine))
             .unwrap();
         self.std_out
-            .write(format!("{}\t{}\n", "ok".green().bold(), file_name).as_bytes())
+            .write_all(format!("{}\t{}\n", "ok".green().bold(), file_name).as_bytes())
             .unwrap();
         self.std_out.queue(cursor::MoveToPreviousLine(1)).unwrap();
 
@@ -97,11 +135,15 @@ impl Tracker {
 
     /// Custom non-error message, may be overwritten
     pub fn msg_temp(&mut self, ok_msg: String) {
+        if self.quiet {
+            return;
+        }
+
         self.std_out
             .queue(terminal::Clear(terminal::ClearType::CurrentLine))
             .unwrap();
         self.std_out
-            .write(format!("{}\t{}\n", "msg".green().bold(), ok_msg).as_bytes())
+            .write_all(format!("{}\t{}\n", "msg".green().bold(), ok_msg).as_bytes())
             .unwrap();
         self.std_out.queue(cursor::MoveToPreviousLine(1)).unwrap();
 
@@ -115,7 +157,7 @@ impl Tracker {
             .queue(terminal::Clear(terminal::ClearType::CurrentLine))
             .unwrap();
         self.std_out
-            .write(format!("{}\t{}\n", "msg".green().bold(), ok_msg).as_bytes())
+            .write_all(format!("{}\t{}\n", "msg".green().bold(), ok_msg).as_bytes())
             .unwrap();
         self.std_out.queue(cursor::MoveToNextLine(1)).unwrap();
 
@@ -144,15 +186,12 @@ impl Tracker {
             .queue(terminal::Clear(terminal::ClearType::CurrentLine))
             .unwrap();
         self.std_out
-            .write(format!("{}\t{}\n", "err".red().bold(), file_name).as_bytes())
+            .write_all(format!("{}\t{}\n", "err".red().bold(), file_name).as_bytes())
             .unwrap();
-        match err_msg {
-            Some(message) => {
-                self.std_out
-                    .write(format!("{}\t{}\n", "msg".red().bold(), message).as_bytes())
-                    .unwrap();
-            }
-            None => (),
+        if let Some(message) = err_msg {
+            self.std_out
+                .write_all(format!("{}\t{}\n", "msg".red().bold(), message).as_bytes())
+                .unwrap();
         }
         self.std_out.queue(cursor::MoveToNextLine(1)).unwrap();
 
@@ -165,7 +204,7 @@ impl Tracker {
             .queue(terminal::Clear(terminal::ClearType::CurrentLine))
             .unwrap();
         self.std_out
-            .write(format!("{}\t{}\n", "err".red().bold(), err_msg).as_bytes())
+            .write_all(format!("{}\t{}\n", "err".red().bold(), err_msg).as_bytes())
             .unwrap();
         self.std_out.queue(cursor::MoveToNextLine(1)).unwrap();
 
@@ -178,7 +217,7 @@ impl Tracker {
             .queue(terminal::Clear(terminal::ClearType::CurrentLine))
             .unwrap();
         self.std_out
-            .write(
+            .write_all(
                 format!(
                     "{}\t\t{}\n{}\t{}\n{}\t{}\n{}\t\t{}\n",
                     "books".green().bold(),
@@ -201,7 +240,7 @@ impl Tracker {
     /// Waits for a keypress before continuing
     pub fn pause(message: String) {
         std::io::stdout()
-            .write(
+            .write_all(
                 format!(
                     "{}\n{}",
                     message,
@@ -212,7 +251,43 @@ impl Tracker {
             .unwrap();
         std::io::stdout().queue(cursor::MoveToNextLine(1)).unwrap();
         std::io::stdout().flush().unwrap();
-        std::io::stdin().read(&mut [0]).unwrap();
+        let _ = std::io::stdin().read(&mut [0]);
+    }
+}
+
+/// Full-screen terminal in raw mode for interactive commands.
+/// The previous screen is restored when dropped
+pub struct Screen {
+    std_out: std::io::Stdout,
+}
+
+impl Screen {
+    /// Switches to the alternate screen and enables raw mode
+    pub fn enter() -> std::io::Result<Screen> {
+        let mut _stdout = std::io::stdout();
+        terminal::enable_raw_mode()?;
+        _stdout.execute(terminal::EnterAlternateScreen)?;
+        _stdout.execute(cursor::Hide)?;
+
+        Ok(Screen { std_out: _stdout })
+    }
+
+    /// Columns and rows of the terminal
+    pub fn size() -> (u16, u16) {
+        terminal::size().unwrap_or((80, 24))
+    }
+
+    /// Output for queueing draw commands, flushed by the caller
+    pub fn out(&mut self) -> &mut std::io::Stdout {
+        &mut self.std_out
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = self.std_out.execute(terminal::LeaveAlternateScreen);
+        let _ = self.std_out.execute(cursor::Show);
+        let _ = terminal::disable_raw_mode();
     }
 }
 
@@ -0,0 +1,675 @@
+//! Draws blueprints as SVG images.
+//!
+//! Entities are drawn as coloured footprint rectangles labelled with the initials of their
+//! name, using a table of vanilla entity sizes. Directions are shown as arrows, and tiles and
+//! circuit wires are drawn below and above the entities. A book is drawn as a contact sheet
+//! with one cell per blueprint.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+use serde_json::Value;
+
+use crate::args;
+use crate::common;
+use crate::common::BlueprintType;
+use crate::progress::{self, CommandType};
+use crate::source::Source;
+
+/// Width and height in pixels of a contact sheet cell
+const CELL_SIZE: u32 = 256;
+/// Height in pixels of the caption below a contact sheet cell
+const CAPTION_HEIGHT: u32 = 24;
+/// Background of the drawing
+const BACKGROUND: &str = "#1e1e1e";
+
+/// Groups of entities sharing a colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Belt,
+    Inserter,
+    Production,
+    Mining,
+    Power,
+    Logistics,
+    Fluid,
+    Rail,
+    Circuit,
+    Defense,
+    Other,
+}
+
+impl Kind {
+    fn colour(&self) -> &'static str {
+        match self {
+            Kind::Belt => "#d6a928",
+            Kind::Inserter => "#6fa8dc",
+            Kind::Production => "#4a7aa8",
+            Kind::Mining => "#8b6b3e",
+            Kind::Power => "#c0504d",
+            Kind::Logistics => "#a07850",
+            Kind::Fluid => "#3f9a8f",
+            Kind::Rail => "#7a7a7a",
+            Kind::Circuit => "#6aa84f",
+            Kind::Defense => "#9c3f6a",
+            Kind::Other => "#9e9e9e",
+        }
+    }
+}
+
+/// Vanilla entity footprints in tiles when facing north: name, width, height, kind
+#[rustfmt::skip]
+const ENTITY_SIZES: &[(&str, u32, u32, Kind)] = &[
+    ("transport-belt", 1, 1, Kind::Belt),
+    ("fast-transport-belt", 1, 1, Kind::Belt),
+    ("express-transport-belt", 1, 1, Kind::Belt),
+    ("turbo-transport-belt", 1, 1, Kind::Belt),
+    ("underground-belt", 1, 1, Kind::Belt),
+    ("splitter", 2, 1, Kind::Belt),
+    ("loader", 1, 2, Kind::Belt),
+    ("burner-inserter", 1, 1, Kind::Inserter),
+    ("inserter", 1, 1, Kind::Inserter),
+    ("long-handed-inserter", 1, 1, Kind::Inserter),
+    ("fast-inserter", 1, 1, Kind::Inserter),
+    ("filter-inserter", 1, 1, Kind::Inserter),
+    ("stack-inserter", 1, 1, Kind::Inserter),
+    ("stack-filter-inserter", 1, 1, Kind::Inserter),
+    ("bulk-inserter", 1, 1, Kind::Inserter),
+    ("assembling-machine-1", 3, 3, Kind::Production),
+    ("assembling-machine-2", 3, 3, Kind::Production),
+    ("assembling-machine-3", 3, 3, Kind::Production),
+    ("stone-furnace", 2, 2, Kind::Production),
+    ("steel-furnace", 2, 2, Kind::Production),
+    ("electric-furnace", 3, 3, Kind::Production),
+    ("chemical-plant", 3, 3, Kind::Production),
+    ("oil-refinery", 5, 5, Kind::Production),
+    ("centrifuge", 3, 3, Kind::Production),
+    ("lab", 3, 3, Kind::Production),
+    ("beacon", 3, 3, Kind::Production),
+    ("rocket-silo", 9, 9, Kind::Production),
+    ("electromagnetic-plant", 4, 4, Kind::Production),
+    ("foundry", 5, 5, Kind::Production),
+    ("biochamber", 3, 3, Kind::Production),
+    ("cryogenic-plant", 5, 5, Kind::Production),
+    ("recycler", 2, 4, Kind::Production),
+    ("crusher", 2, 3, Kind::Production),
+    ("captive-biter-spawner", 5, 5, Kind::Production),
+    ("burner-mining-drill", 2, 2, Kind::Mining),
+    ("electric-mining-drill", 3, 3, Kind::Mining),
+    ("big-mining-drill", 5, 5, Kind::Mining),
+    ("pumpjack", 3, 3, Kind::Mining),
+    ("agricultural-tower", 3, 3, Kind::Mining),
+    ("asteroid-collector", 3, 3, Kind::Mining),
+    ("small-electric-pole", 1, 1, Kind::Power),
+    ("medium-electric-pole", 1, 1, Kind::Power),
+    ("big-electric-pole", 2, 2, Kind::Power),
+    ("substation", 2, 2, Kind::Power),
+    ("boiler", 3, 2, Kind::Power),
+    ("steam-engine", 3, 5, Kind::Power),
+    ("steam-turbine", 3, 5, Kind::Power),
+    ("solar-panel", 3, 3, Kind::Power),
+    ("accumulator", 2, 2, Kind::Power),
+    ("nuclear-reactor", 5, 5, Kind::Power),
+    ("heat-exchanger", 3, 2, Kind::Power),
+    ("heat-pipe", 1, 1, Kind::Power),
+    ("power-switch", 2, 2, Kind::Power),
+    ("lightning-rod", 1, 1, Kind::Power),
+    ("lightning-collector", 2, 2, Kind::Power),
+    ("fusion-reactor", 6, 6, Kind::Power),
+    ("fusion-generator", 3, 5, Kind::Power),
+    ("heating-tower", 3, 3, Kind::Power),
+    ("wooden-chest", 1, 1, Kind::Logistics),
+    ("iron-chest", 1, 1, Kind::Logistics),
+    ("steel-chest", 1, 1, Kind::Logistics),
+    ("roboport", 4, 4, Kind::Logistics),
+    ("cargo-landing-pad", 8, 8, Kind::Logistics),
+    ("pipe", 1, 1, Kind::Fluid),
+    ("pipe-to-ground", 1, 1, Kind::Fluid),
+    ("pump", 1, 2, Kind::Fluid),
+    ("offshore-pump", 1, 2, Kind::Fluid),
+    ("storage-tank", 3, 3, Kind::Fluid),
+    ("straight-rail", 2, 2, Kind::Rail),
+    ("curved-rail", 4, 8, Kind::Rail),
+    ("half-diagonal-rail", 2, 4, Kind::Rail),
+    ("curved-rail-a", 2, 5, Kind::Rail),
+    ("curved-rail-b", 3, 5, Kind::Rail),
+    ("rail-ramp", 4, 16, Kind::Rail),
+    ("rail-support", 4, 4, Kind::Rail),
+    ("rail-signal", 1, 1, Kind::Rail),
+    ("rail-chain-signal", 1, 1, Kind::Rail),
+    ("train-stop", 2, 2, Kind::Rail),
+    ("locomotive", 2, 6, Kind::Rail),
+    ("cargo-wagon", 2, 6, Kind::Rail),
+    ("fluid-wagon", 2, 6, Kind::Rail),
+    ("artillery-wagon", 2, 6, Kind::Rail),
+    ("arithmetic-combinator", 1, 2, Kind::Circuit),
+    ("decider-combinator", 1, 2, Kind::Circuit),
+    ("selector-combinator", 1, 2, Kind::Circuit),
+    ("constant-combinator", 1, 1, Kind::Circuit),
+    ("programmable-speaker", 1, 1, Kind::Circuit),
+    ("display-panel", 1, 1, Kind::Circuit),
+    ("small-lamp", 1, 1, Kind::Circuit),
+    ("radar", 3, 3, Kind::Circuit),
+    ("stone-wall", 1, 1, Kind::Defense),
+    ("gate", 1, 1, Kind::Defense),
+    ("land-mine", 1, 1, Kind::Defense),
+    ("gun-turret", 2, 2, Kind::Defense),
+    ("laser-turret", 2, 2, Kind::Defense),
+    ("flamethrower-turret", 2, 3, Kind::Defense),
+    ("artillery-turret", 3, 3, Kind::Defense),
+    ("rocket-turret", 3, 3, Kind::Defense),
+    ("tesla-turret", 2, 2, Kind::Defense),
+    ("railgun-turret", 4, 4, Kind::Defense),
+];
+
+/// Footprint of modded tiers and variants, recognised by the end of their name
+#[rustfmt::skip]
+const ENTITY_SUFFIXES: &[(&str, u32, u32, Kind)] = &[
+    ("transport-belt", 1, 1, Kind::Belt),
+    ("underground-belt", 1, 1, Kind::Belt),
+    ("splitter", 2, 1, Kind::Belt),
+    ("loader", 1, 2, Kind::Belt),
+    ("inserter", 1, 1, Kind::Inserter),
+    ("chest", 1, 1, Kind::Logistics),
+    ("pipe", 1, 1, Kind::Fluid),
+];
+
+/// Footprint of an entity facing north as width, height and kind. Unknown entities take one tile
+pub fn entity_size(name: &str) -> (u32, u32, Kind) {
+    ENTITY_SIZES
+        .iter()
+        .find(|(known, ..)| *known == name)
+        .or_else(|| {
+            ENTITY_SUFFIXES
+                .iter()
+                .find(|(suffix, ..)| name.ends_with(suffix))
+        })
+        .map(|(_, width, height, kind)| (*width, *height, *kind))
+        .unwrap_or((1, 1, Kind::Other))
+}
+
+/// Footprint of an entity turned to its direction as width, height and kind
+pub fn turned_size(name: &str, direction: u64, direction_count: u64) -> (f64, f64, Kind) {
+    let (width, height, kind) = entity_size(name);
+    // east and west turn the footprint sideways
+    match (direction * 4 / direction_count) % 2 {
+        0 => (width as f64, height as f64, kind),
+        _ => (height as f64, width as f64, kind),
+    }
+}
+
+/// Initials of the words of a name, numbers kept whole: "assembling-machine-2" is "AM2"
+pub fn short_name(name: &str) -> String {
+    name.split(['-', '_'])
+        .filter_map(|word| match word.parse::<u32>() {
+            Ok(_) => Some(word.to_string()),
+            Err(_) => word
+                .chars()
+                .next()
+                .map(|c| c.to_ascii_uppercase().to_string()),
+        })
+        .collect()
+}
+
+/// Escapes text for use in SVG
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Area covered by a drawing, in tiles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Bounds {
+    fn empty() -> Bounds {
+        Bounds {
+            min_x: f64::INFINITY,
+            min_y: f64::INFINITY,
+            max_x: f64::NEG_INFINITY,
+            max_y: f64::NEG_INFINITY,
+        }
+    }
+
+    fn include(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x + width);
+        self.max_y = self.max_y.max(y + height);
+    }
+
+    /// The bounds with a margin, or a single tile if nothing was drawn
+    fn padded(&self, margin: f64) -> Bounds {
+        if self.min_x > self.max_x {
+            return Bounds {
+                min_x: -margin,
+                min_y: -margin,
+                max_x: 1.0 + margin,
+                max_y: 1.0 + margin,
+            };
+        }
+        Bounds {
+            min_x: self.min_x - margin,
+            min_y: self.min_y - margin,
+            max_x: self.max_x + margin,
+            max_y: self.max_y + margin,
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+
+    fn view_box(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.min_x,
+            self.min_y,
+            self.width(),
+            self.height()
+        )
+    }
+}
+
+/// SVG elements of a blueprint in tile coordinates, and the area they cover
+#[derive(Debug, Clone)]
+pub struct Drawing {
+    pub body: String,
+    pub bounds: Bounds,
+}
+
+impl Drawing {
+    /// Draws a blueprint object (the value under the "blueprint" key)
+    pub fn from(blueprint: &Value) -> Drawing {
+        let mut body = String::new();
+        let mut bounds = Bounds::empty();
+        let direction_count = common::direction_count(blueprint);
+
+        for tile in common::array(blueprint, "tiles") {
+            let name = tile
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or_default();
+            let (x, y) = match common::position(tile) {
+                Some(_pos) => _pos,
+                None => continue,
+            };
+            bounds.include(x, y, 1.0, 1.0);
+            let _ = write!(
+                body,
+                r#"<rect x="{}" y="{}" width="1" height="1" fill="{}"><title>{}</title></rect>"#,
+                x,
+                y,
+                tile_colour(name),
+                escape(name)
+            );
+        }
+
+        let mut centres: HashMap<u64, (f64, f64)> = HashMap::new();
+        for entity in common::array(blueprint, "entities") {
+            let name = entity
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or_default();
+            let (x, y) = match common::position(entity) {
+                Some(_pos) => _pos,
+                None => continue,
+            };
+            if let Some(number) = entity.get("entity_number").and_then(|n| n.as_u64()) {
+                centres.insert(number, (x, y));
+            }
+
+            let direction = entity.get("direction").and_then(|d| d.as_u64());
+            let (width, height, kind) = turned_size(name, direction.unwrap_or(0), direction_count);
+            let (left, top) = (x - width / 2.0, y - height / 2.0);
+            bounds.include(left, top, width, height);
+
+            let _ = write!(
+                body,
+                r#"<g><title>{}</title><rect x="{}" y="{}" width="{}" height="{}" rx="0.1" fill="{}" stroke="{}" stroke-width="0.04"/>"#,
+                escape(name),
+                left + 0.05,
+                top + 0.05,
+                width - 0.1,
+                height - 0.1,
+                kind.colour(),
+                BACKGROUND
+            );
+            let size = width.min(height);
+            if direction.is_some() || matches!(kind, Kind::Belt | Kind::Inserter) {
+                let mut angle = direction.unwrap_or(0) as f64 * 360.0 / direction_count as f64;
+                // inserters face their pickup side, the arrow follows the items
+                if kind == Kind::Inserter {
+                    angle = (angle + 180.0) % 360.0;
+                }
+                let arrow = size * 0.3;
+                let _ = write!(
+                    body,
+                    r##"<path d="M {} {} L {} {} L {} {} Z" fill="#ffffff" fill-opacity="0.45" transform="rotate({} {} {})"/>"##,
+                    x,
+                    y - arrow,
+                    x - arrow * 0.7,
+                    y + arrow * 0.5,
+                    x + arrow * 0.7,
+                    y + arrow * 0.5,
+                    angle,
+                    x,
+                    y
+                );
+            }
+            let _ = write!(
+                body,
+                r##"<text x="{}" y="{}" font-size="{}" font-family="sans-serif" text-anchor="middle" dominant-baseline="central" fill="#ffffff">{}</text></g>"##,
+                x,
+                y,
+                size * 0.3,
+                escape(&short_name(name))
+            );
+        }
+
+        for (from, to, colour) in circuit_wires(blueprint) {
+            let (Some(from), Some(to)) = (centres.get(&from), centres.get(&to)) else {
+                continue;
+            };
+            // red and green wires between the same entities are drawn side by side
+            let shift = match colour {
+                "red" => -0.06,
+                _ => 0.06,
+            };
+            let _ = write!(
+                body,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="0.08" stroke-linecap="round"/>"#,
+                from.0 + shift,
+                from.1 + shift,
+                to.0 + shift,
+                to.1 + shift,
+                wire_colour(colour)
+            );
+        }
+
+        Drawing { body, bounds }
+    }
+
+    /// The drawing as a standalone SVG image, `scale` pixels per tile
+    pub fn svg(&self, scale: f64) -> String {
+        let bounds = self.bounds.padded(1.0);
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{}"><rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>{}</svg>"#,
+            (bounds.width() * scale).ceil(),
+            (bounds.height() * scale).ceil(),
+            bounds.view_box(),
+            bounds.min_x,
+            bounds.min_y,
+            bounds.width(),
+            bounds.height(),
+            BACKGROUND,
+            self.body
+        ) + "\n"
+    }
+}
+
+/// Draws labelled blueprints side by side in rows of `columns` cells
+pub fn contact_sheet(cells: &[(String, Drawing)], columns: usize) -> String {
+    let columns = columns.clamp(1, cells.len().max(1));
+    let rows = cells.len().div_ceil(columns).max(1);
+    let (cell_width, cell_height) = (CELL_SIZE + 8, CELL_SIZE + CAPTION_HEIGHT + 8);
+
+    let mut sheet = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}"><rect width="100%" height="100%" fill="{}"/>"#,
+        columns as u32 * cell_width,
+        rows as u32 * cell_height,
+        BACKGROUND
+    );
+    for (pos, (label, drawing)) in cells.iter().enumerate() {
+        let x = (pos % columns) as u32 * cell_width + 4;
+        let y = (pos / columns) as u32 * cell_height + 4;
+        let _ = write!(
+            sheet,
+            r##"<svg x="{}" y="{}" width="{}" height="{}" viewBox="{}">{}</svg><text x="{}" y="{}" font-size="14" font-family="sans-serif" text-anchor="middle" fill="#e0e0e0">{}</text>"##,
+            x,
+            y,
+            CELL_SIZE,
+            CELL_SIZE,
+            drawing.bounds.padded(1.0).view_box(),
+            drawing.body,
+            x + CELL_SIZE / 2,
+            y + CELL_SIZE + CAPTION_HEIGHT - 8,
+            escape(label)
+        );
+    }
+    sheet + "</svg>\n"
+}
+
+/// Circuit wires between entities as entity numbers and colour, each listed once
+fn circuit_wires(blueprint: &Value) -> BTreeSet<(u64, u64, &'static str)> {
+    let mut wires = BTreeSet::new();
+    let mut add = |a: u64, b: u64, colour: &'static str| {
+        if a != b {
+            wires.insert((a.min(b), a.max(b), colour));
+        }
+    };
+
+    for entity in common::array(blueprint, "entities") {
+        let number = match entity.get("entity_number").and_then(|n| n.as_u64()) {
+            Some(_number) => _number,
+            None => continue,
+        };
+        // 1.1: {"1": {"red": [{"entity_id": 2}]}, "2": {...}}
+        for (_, point) in entity
+            .get("connections")
+            .and_then(|c| c.as_object())
+            .into_iter()
+            .flatten()
+            .filter(|(circuit, _)| !circuit.starts_with("Cu"))
+        {
+            for colour in ["red", "green"] {
+                for target in point
+                    .get(colour)
+                    .and_then(|t| t.as_array())
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Some(other) = target.get("entity_id").and_then(|e| e.as_u64()) {
+                        add(number, other, colour);
+                    }
+                }
+            }
+        }
+    }
+
+    // 2.0: [[entity, connector, entity, connector]], odd connectors red, even green, then copper
+    for wire in common::array(blueprint, "wires") {
+        let wire: Vec<u64> = wire
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_u64())
+            .collect();
+        if let [from, connector, to, _] = wire[..] {
+            match connector {
+                1 | 3 => add(from, to, "red"),
+                2 | 4 => add(from, to, "green"),
+                _ => (),
+            }
+        }
+    }
+
+    wires
+}
+
+fn wire_colour(colour: &str) -> &'static str {
+    match colour {
+        "red" => "#e0413a",
+        _ => "#3fc24a",
+    }
+}
+
+fn tile_colour(name: &str) -> &'static str {
+    match name {
+        "stone-path" => "#5a5044",
+        "concrete" => "#4a4a4a",
+        "hazard-concrete-left" | "hazard-concrete-right" => "#5c5226",
+        "refined-concrete" => "#3c3c3c",
+        "refined-hazard-concrete-left" | "refined-hazard-concrete-right" => "#4e4620",
+        "landfill" => "#4d4330",
+        _ => "#404040",
+    }
+}
+
+pub struct Worker {
+    pub source: Source,
+    pub outfile: Option<PathBuf>,
+    pub scale: f64,
+    pub columns: usize,
+}
+
+impl Worker {
+    pub fn from(render_args: &args::Render) -> Worker {
+        Worker {
+            source: Source::from(&render_args.source),
+            outfile: render_args.outfile.as_ref().map(PathBuf::from),
+            scale: render_args.scale,
+            columns: render_args.columns,
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let prog_tracker = progress::Tracker::new_quiet_sync(CommandType::Render);
+        let render_error = |err_msg: String| -> ! {
+            prog_tracker.lock().unwrap().error_additional(err_msg);
+            exit(1);
+        };
+
+        let blueprint_obj = match self.source.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => render_error(err_msg),
+        };
+
+        let mut cells = vec![];
+        common::visit_blueprints(&blueprint_obj, &mut |path, blueprint| {
+            // the label of the rendered book itself is left out
+            let label = match path.len() {
+                1 => path.join("/"),
+                _ => path[1..].join("/"),
+            };
+            cells.push((label, Drawing::from(blueprint)));
+        });
+
+        let svg = match BlueprintType::classify(&blueprint_obj) {
+            BlueprintType::Blueprint(_) => cells[0].1.svg(self.scale),
+            BlueprintType::Book(_) if !cells.is_empty() => contact_sheet(&cells, self.columns),
+            BlueprintType::Book(_) => render_error("book contains no blueprints".to_string()),
+            _ => render_error(format!("{:?} is not a blueprint or book", self.source)),
+        };
+
+        match &self.outfile {
+            None => print!("{}", svg),
+            Some(outfile) => {
+                if fs::write(outfile, svg).is_err() {
+                    render_error(format!("{:?}: error writing file", outfile));
+                }
+                prog_tracker
+                    .lock()
+                    .unwrap()
+                    .msg(format!("rendered {} blueprints", cells.len()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn blueprint() -> Value {
+        json!({"label": "wired", "version": 281479275675648u64, "entities": [
+            {"entity_number": 1, "name": "assembling-machine-2", "position": {"x": 1.5, "y": 1.5}},
+            {"entity_number": 2, "name": "fast-splitter", "position": {"x": 4, "y": 0.5},
+             "direction": 2},
+            {"entity_number": 3, "name": "small-lamp", "position": {"x": 6.5, "y": 0.5},
+             "connections": {"1": {"red": [{"entity_id": 4}], "green": [{"entity_id": 4}]}}},
+            {"entity_number": 4, "name": "constant-combinator", "position": {"x": 8.5, "y": 0.5},
+             "connections": {"1": {"red": [{"entity_id": 3}]}}}
+        ], "tiles": [{"name": "concrete", "position": {"x": -2, "y": 3}}]})
+    }
+
+    #[test]
+    fn test_entity_size() {
+        assert_eq!(
+            entity_size("assembling-machine-2"),
+            (3, 3, Kind::Production)
+        );
+        assert_eq!(entity_size("fast-splitter"), (2, 1, Kind::Belt));
+        assert_eq!(entity_size("mystery-box"), (1, 1, Kind::Other));
+        assert_eq!(short_name("assembling-machine-2"), "AM2");
+        assert_eq!(short_name("fast-transport-belt"), "FTB");
+    }
+
+    #[test]
+    fn test_draw_blueprint() {
+        let drawing = Drawing::from(&blueprint());
+        assert_eq!(
+            drawing.bounds,
+            Bounds {
+                min_x: -2.0,
+                min_y: -0.5,
+                max_x: 9.0,
+                max_y: 4.0
+            }
+        );
+        // the splitter faces east, so it is one tile wide
+        assert!(drawing
+            .body
+            .contains(r#"<rect x="3.55" y="-0.45" width="0.9" height="1.9""#));
+        // one arrow for the splitter
+        assert_eq!(drawing.body.matches("<path").count(), 1);
+        // the red wire is listed by both entities but drawn once
+        assert_eq!(drawing.body.matches(r##"stroke="#e0413a""##).count(), 1);
+        assert_eq!(drawing.body.matches(r##"stroke="#3fc24a""##).count(), 1);
+
+        let svg = drawing.svg(10.0);
+        assert!(svg.starts_with(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="130" height="65" viewBox="-3 -1.5 13 6.5">"#
+        ));
+    }
+
+    #[test]
+    fn test_inserter_arrow_follows_items() {
+        // facing east, it picks up from the east and drops to the west
+        let drawing = Drawing::from(&json!({"label": "inserter", "version": 1, "entities": [
+            {"entity_number": 1, "name": "inserter", "position": {"x": 0.5, "y": 0.5},
+             "direction": 2}
+        ]}));
+        assert!(drawing.body.contains(r#"transform="rotate(270 0.5 0.5)""#));
+    }
+
+    #[test]
+    fn test_contact_sheet() {
+        let cells: Vec<(String, Drawing)> = ["a", "b & c", "d"]
+            .iter()
+            .map(|label| (label.to_string(), Drawing::from(&blueprint())))
+            .collect();
+        let sheet = contact_sheet(&cells, 2);
+
+        assert!(sheet
+            .starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="528" height="576">"#));
+        assert_eq!(sheet.matches("<svg x=").count(), 3);
+        assert!(sheet.contains(">b &amp; c</text>"));
+    }
+}
@@ -0,0 +1,329 @@
+//! Replaces entity and item names in every blueprint of a tree, in place.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use crossterm::style::Stylize;
+use serde_json::Value;
+
+use crate::args;
+use crate::common;
+use crate::manifest;
+use crate::progress::{self, CommandType};
+
+/// Separates the old and new name in a mapping
+pub const MAPPING_SEPARATOR: char = '=';
+
+pub struct Worker {
+    pub tree: PathBuf,
+    pub mappings: Vec<String>,
+    pub dry_run: bool,
+}
+
+/// Old names mapped to new names
+pub type NameMap = HashMap<String, String>;
+
+/// Parses mappings given as "from=to"
+pub fn parse_mappings(mappings: &[String]) -> Result<NameMap, String> {
+    let mut name_map = NameMap::new();
+    for mapping in mappings {
+        match mapping.split_once(MAPPING_SEPARATOR) {
+            Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+                name_map.insert(from.trim().to_string(), to.trim().to_string());
+            }
+            _ => return Err(format!("invalid mapping \"{}\", expected from=to", mapping)),
+        }
+    }
+    Ok(name_map)
+}
+
+/// Replaces names in a blueprint or book, returning the number of names changed
+pub fn replace_names(unknown_bp: &mut Value, name_map: &NameMap) -> usize {
+    let mut changes = 0;
+    common::visit_blueprints_mut(unknown_bp, &mut |blueprint| {
+        for entity in blueprint
+            .get_mut("entities")
+            .and_then(|entities| entities.as_array_mut())
+            .into_iter()
+            .flatten()
+        {
+            changes += replace_entity_name(entity, name_map)
+                + replace_item_requests(entity, name_map)
+                + replace_filters(entity, name_map);
+        }
+    });
+    changes
+}
+
+/// Replaces the name of an entity
+pub fn replace_entity_name(entity: &mut Value, name_map: &NameMap) -> usize {
+    replace_name_field(entity, name_map)
+}
+
+/// Replaces the items requested for an entity, such as modules
+pub fn replace_item_requests(entity: &mut Value, name_map: &NameMap) -> usize {
+    let mut changes = 0;
+    match entity.get_mut("items") {
+        // 1.1: {"speed-module": 2}
+        Some(Value::Object(requests)) => {
+            let renamed: Vec<(String, String)> = requests
+                .keys()
+                .filter_map(|name| Some((name.clone(), name_map.get(name)?.clone())))
+                .collect();
+            for (from, to) in renamed {
+                let count = requests.remove(&from).unwrap();
+                // merge with a request for the new item, if there already is one
+                let total = count.as_u64().unwrap_or(0)
+                    + requests.get(&to).and_then(|c| c.as_u64()).unwrap_or(0);
+                requests.insert(to, total.into());
+                changes += 1;
+            }
+        }
+        // 2.0: [{"id": {"name": "speed-module"}, "items": {...}}]
+        Some(Value::Array(requests)) => {
+            for request in requests {
+                if let Some(id) = request.get_mut("id") {
+                    changes += replace_name_field(id, name_map);
+                }
+            }
+        }
+        _ => (),
+    }
+    changes
+}
+
+/// Replaces the item filters of splitters, inserters, loaders, logistic and infinity chests
+fn replace_filters(entity: &mut Value, name_map: &NameMap) -> usize {
+    let mut changes = 0;
+
+    // splitters: "filter" is an item name in 1.1 and an object with a name in 2.0
+    match entity.get_mut("filter") {
+        Some(Value::String(name)) => {
+            if let Some(new_name) = name_map.get(name.as_str()) {
+                *name = new_name.clone();
+                changes += 1;
+            }
+        }
+        Some(filter) => changes += replace_name_field(filter, name_map),
+        None => (),
+    }
+
+    for pointer in ["/filters", "/request_filters", "/infinity_settings/filters"] {
+        if let Some(filters) = entity.pointer_mut(pointer) {
+            changes += replace_in_filters(filters, name_map);
+        }
+    }
+    if let Some(sections) = entity
+        .pointer_mut("/request_filters/sections")
+        .and_then(|sections| sections.as_array_mut())
+    {
+        for section in sections {
+            if let Some(filters) = section.get_mut("filters") {
+                changes += replace_in_filters(filters, name_map);
+            }
+        }
+    }
+
+    changes
+}
+
+/// Replaces the names of a filter list
+fn replace_in_filters(filters: &mut Value, name_map: &NameMap) -> usize {
+    filters
+        .as_array_mut()
+        .into_iter()
+        .flatten()
+        .map(|filter| replace_name_field(filter, name_map))
+        .sum()
+}
+
+/// Replaces the "name" field of an object
+fn replace_name_field(object: &mut Value, name_map: &NameMap) -> usize {
+    let new_name = match object
+        .get("name")
+        .and_then(|name| name.as_str())
+        .and_then(|name| name_map.get(name))
+    {
+        Some(_name) => _name.clone(),
+        None => return 0,
+    };
+    object["name"] = new_name.into();
+    1
+}
+
+impl Worker {
+    pub fn from(replace_args: &args::Replace) -> Worker {
+        Worker {
+            tree: PathBuf::from(replace_args.tree.clone().unwrap_or_default()),
+            mappings: replace_args.map.clone(),
+            dry_run: replace_args.dry_run,
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let prog_tracker = progress::Tracker::new_quiet_sync(CommandType::Replace);
+        let replace_error = |err_msg: String| -> ! {
+            prog_tracker.lock().unwrap().error_additional(err_msg);
+            exit(1);
+        };
+
+        let name_map = match parse_mappings(&self.mappings) {
+            Ok(_map) if !_map.is_empty() => _map,
+            Ok(_) => replace_error("no mappings given".to_string()),
+            Err(err_msg) => replace_error(err_msg),
+        };
+        if self.tree.as_os_str().is_empty() {
+            replace_error("no tree given, and no tree set in fbpconvert.toml".to_string());
+        }
+        if !self.tree.exists() {
+            replace_error(format!("{:?}: no such file or directory", self.tree));
+        }
+        let json_files = match common::tree_json_files(&self.tree) {
+            Ok(_files) => _files,
+            Err(_) => replace_error(format!("{:?}: unable to read directory", self.tree)),
+        };
+        // the rewritten files are recorded in the manifest of the tree, if it has one
+        let refresh = match self.dry_run {
+            true => None,
+            false => match manifest::Refresh::prepare(&self.tree) {
+                Ok(_refresh) => _refresh,
+                Err(err_msg) => replace_error(err_msg),
+            },
+        };
+
+        let (mut total_changes, mut changed_files) = (0, 0);
+        for json_file in json_files {
+            match Worker::replace_in_file(&json_file, &name_map, self.dry_run) {
+                Ok(0) => (),
+                Ok(changes) => {
+                    println!(
+                        "{}  {}",
+                        json_file.to_string_lossy().green().bold(),
+                        format!("{} changes", changes).dim()
+                    );
+                    total_changes += changes;
+                    changed_files += 1;
+                }
+                Err(err_msg) => prog_tracker.lock().unwrap().error_additional(err_msg),
+            }
+        }
+        if let Some(Err(err_msg)) = refresh.as_ref().map(manifest::Refresh::finish) {
+            replace_error(err_msg);
+        }
+
+        let summary = format!("{} changes in {} files", total_changes, changed_files);
+        match self.dry_run {
+            true => println!("{} (dry run, no files written)", summary),
+            false => println!("{}", summary),
+        }
+    }
+
+    /// Replaces names in a JSON file, rewriting it unless this is a dry run.
+    /// Returns the number of names changed
+    fn replace_in_file(
+        json_file: &Path,
+        name_map: &NameMap,
+        dry_run: bool,
+    ) -> Result<usize, String> {
+        let (mut unknown_bp, style) = match common::json_read_tree(json_file) {
+            Some(_val) => _val,
+            None => return Err(format!("{:?}: failed to read file", json_file)),
+        };
+
+        let changes = replace_names(&mut unknown_bp, name_map);
+        if changes == 0 || dry_run {
+            return Ok(changes);
+        }
+
+        match File::create(json_file).and_then(|file| style.write(file, &unknown_bp)) {
+            Ok(_) => Ok(changes),
+            Err(_) => Err(format!("{:?}: error writing file", json_file)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    fn name_map() -> NameMap {
+        parse_mappings(&[
+            "fast-transport-belt=express-transport-belt".to_string(),
+            "fast-splitter = express-splitter".to_string(),
+            "speed-module=speed-module-3".to_string(),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_mappings() {
+        assert_eq!(name_map()["fast-splitter"], "express-splitter");
+        assert!(parse_mappings(&["no-separator".to_string()]).is_err());
+        assert!(parse_mappings(&["=to".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_replace_names() {
+        let mut blueprint = json!({"blueprint": {"label": "bus", "version": 1, "entities": [
+            {"entity_number": 1, "name": "fast-transport-belt"},
+            {"entity_number": 2, "name": "fast-splitter", "filter": "fast-transport-belt"},
+            {"entity_number": 3, "name": "assembling-machine-2",
+             "items": {"speed-module": 2, "speed-module-3": 1}},
+            {"entity_number": 4, "name": "beacon",
+             "items": [{"id": {"name": "speed-module"}, "items": {"in_inventory": []}}]},
+            {"entity_number": 5, "name": "filter-inserter",
+             "filters": [{"index": 1, "name": "fast-transport-belt"}, {"index": 2, "name": "pipe"}]},
+            {"entity_number": 6, "name": "requester-chest",
+             "request_filters": {"sections": [{"index": 1, "filters": [
+                 {"index": 1, "name": "fast-splitter", "count": 10}]}]}}
+        ]}});
+
+        assert_eq!(replace_names(&mut blueprint, &name_map()), 7);
+
+        let entities = &blueprint["blueprint"]["entities"];
+        assert_eq!(entities[0]["name"], "express-transport-belt");
+        assert_eq!(entities[1]["name"], "express-splitter");
+        assert_eq!(entities[1]["filter"], "express-transport-belt");
+        assert_eq!(entities[2]["items"], json!({"speed-module-3": 3}));
+        assert_eq!(entities[3]["items"][0]["id"]["name"], "speed-module-3");
+        assert_eq!(entities[4]["filters"][0]["name"], "express-transport-belt");
+        assert_eq!(entities[4]["filters"][1]["name"], "pipe");
+        assert_eq!(
+            entities[5]["request_filters"]["sections"][0]["filters"][0]["name"],
+            "express-splitter"
+        );
+    }
+
+    #[test]
+    fn test_replace_in_file_dry_run() {
+        let dest = common::TestDir::new("replace");
+        let json_file = dest.join("belt.json");
+        let blueprint = json!({"blueprint": {"label": "belt", "version": 1, "entities": [
+            {"entity_number": 1, "name": "fast-transport-belt"}
+        ]}, "index": 0});
+        common::json_write_pretty(File::create(&json_file).unwrap(), &blueprint).unwrap();
+        let read_back =
+            || -> Value { serde_json::from_str(&fs::read_to_string(&json_file).unwrap()).unwrap() };
+
+        assert_eq!(
+            Worker::replace_in_file(&json_file, &name_map(), true),
+            Ok(1)
+        );
+        assert_eq!(read_back(), blueprint);
+
+        assert_eq!(
+            Worker::replace_in_file(&json_file, &name_map(), false),
+            Ok(1)
+        );
+        assert_eq!(
+            read_back()["blueprint"]["entities"][0]["name"],
+            "express-transport-belt"
+        );
+        assert_eq!(read_back()["index"], 0);
+    }
+}
@@ -0,0 +1,333 @@
+//! Flattens nested books, or groups the items of a book into sub-books.
+
+use std::collections::BTreeMap;
+use std::process::exit;
+
+use serde_json::{json, Value};
+
+use crate::args::{self, GroupBy};
+use crate::common::{self, BlueprintType, CompressionLevel};
+use crate::destination::Destination;
+use crate::factorio_structs;
+use crate::progress::{self, CommandType};
+use crate::source::Source;
+
+pub struct Worker {
+    pub source: Source,
+    pub flatten: bool,
+    pub group_by: Option<GroupBy>,
+    pub separator: String,
+    pub depth: Option<usize>,
+    pub destination: Destination,
+    pub compression: CompressionLevel,
+}
+
+impl Worker {
+    pub fn from(restructure_args: &args::Restructure) -> Worker {
+        Worker {
+            source: Source::from(&restructure_args.source),
+            flatten: restructure_args.flatten,
+            group_by: restructure_args.group_by,
+            separator: restructure_args.separator.clone(),
+            depth: restructure_args.depth,
+            destination: Destination::from(&restructure_args.output),
+            compression: restructure_args.output.compression.unwrap_or_default(),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let restructure_error = |err_msg: String| -> ! {
+            progress::Tracker::new_quiet_sync(CommandType::Restructure)
+                .lock()
+                .unwrap()
+                .error_additional(err_msg);
+            exit(1);
+        };
+
+        if !self.flatten && self.group_by.is_none() {
+            restructure_error("nothing to do, give --flatten or --group-by".to_string());
+        }
+
+        let blueprint_obj = match self.source.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => restructure_error(err_msg),
+        };
+        if !matches!(
+            BlueprintType::classify(&blueprint_obj),
+            BlueprintType::Book(_)
+        ) {
+            restructure_error(format!("{:?} is not a blueprint book", self.source));
+        }
+
+        // grouping starts from a flat book
+        let flat_book = flatten(blueprint_obj, &self.separator);
+        let restructured = match self.group_by {
+            None => flat_book,
+            Some(GroupBy::Prefix) => group_by_prefix(flat_book, &self.separator, self.depth),
+            Some(GroupBy::Entity) => group_by_entity(flat_book),
+        };
+
+        if let Err(err_msg) = self.destination.write(restructured, self.compression) {
+            restructure_error(err_msg);
+        }
+    }
+}
+
+/// Moves every blueprint and planner of a book and its sub-books into the top-level book,
+/// depth first in slot order. The labels of the enclosing sub-books are prefixed to each label
+pub fn flatten(mut bp_book: Value, separator: &str) -> Value {
+    fn flatten_recursive(
+        mut unknown_bp: Value,
+        path: &mut Vec<String>,
+        separator: &str,
+        flat: &mut Vec<Value>,
+    ) {
+        match BlueprintType::classify(&unknown_bp) {
+            BlueprintType::Invalid => (),
+            BlueprintType::Book(label) => {
+                let (children, _) = common::take_book_children(&mut unknown_bp);
+                path.push(label);
+                for child in children {
+                    flatten_recursive(child, path, separator, flat);
+                }
+                path.pop();
+            }
+            BlueprintType::Blueprint(label)
+            | BlueprintType::UpgradePlanner(label)
+            | BlueprintType::DeconPlanner(label) => {
+                let mut item = unknown_bp;
+                if !path.is_empty() {
+                    let key = BlueprintType::classify(&item).key().unwrap();
+                    item[key]["label"] =
+                        format!("{}{}{}", path.join(separator), separator, label).into();
+                }
+                flat.push(item);
+            }
+        }
+    }
+
+    let (children, _) = common::take_book_children(&mut bp_book);
+    let mut flat = vec![];
+    for child in children {
+        flatten_recursive(child, &mut vec![], separator, &mut flat);
+    }
+
+    common::set_book_children(&mut bp_book, flat, None);
+    bp_book
+}
+
+/// Groups items whose labels start with the same prefix into a sub-book named after it.
+/// The prefix and separator are removed from the labels. Sub-books are grouped again
+/// until `depth` levels of sub-books are created, without limit if none
+pub fn group_by_prefix(bp_book: Value, separator: &str, depth: Option<usize>) -> Value {
+    if depth == Some(0) || separator.is_empty() {
+        return bp_book;
+    }
+
+    let mut grouped = group(bp_book, |item| {
+        let key = BlueprintType::classify(item).key()?;
+        let label = item[key]["label"].as_str()?;
+        let (prefix, rest) = label.split_once(separator)?;
+        let (prefix, rest) = (prefix.trim().to_string(), rest.trim().to_string());
+        if prefix.is_empty() || rest.is_empty() {
+            return None;
+        }
+        item[key]["label"] = rest.into();
+        Some(prefix)
+    });
+
+    let (children, active_pos) = common::take_book_children(&mut grouped);
+    let children = children
+        .into_iter()
+        .map(|child| match BlueprintType::classify(&child) {
+            BlueprintType::Book(_) => group_by_prefix(child, separator, depth.map(|d| d - 1)),
+            _ => child,
+        })
+        .collect();
+
+    common::set_book_children(&mut grouped, children, active_pos);
+    grouped
+}
+
+/// Groups blueprints into sub-books named after their most common entity.
+/// Planners and blueprints without entities stay in the top-level book
+pub fn group_by_entity(bp_book: Value) -> Value {
+    group(bp_book, |item| {
+        dominant_entity(item.get(factorio_structs::FACTORIO_BP_KEY)?)
+    })
+}
+
+/// Most common entity of a blueprint object, the first by name on a tie
+pub fn dominant_entity(blueprint: &Value) -> Option<String> {
+    let mut counts: BTreeMap<&str, u64> = BTreeMap::new();
+    for entity in blueprint
+        .get("entities")
+        .and_then(|entities| entities.as_array())
+        .into_iter()
+        .flatten()
+    {
+        if let Some(name) = entity.get("name").and_then(|name| name.as_str()) {
+            *counts.entry(name).or_default() += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .fold(
+            None,
+            |dominant: Option<(&str, u64)>, (name, count)| match dominant {
+                Some((_, max)) if max >= count => dominant,
+                _ => Some((name, count)),
+            },
+        )
+        .map(|(name, _)| name.to_string())
+}
+
+/// Moves the items of a book into new sub-books, one per group name returned by `group_of`.
+/// `group_of` may rename an item it returns a group for, other items must be left unchanged.
+/// Items without a group stay in the book. Sub-books are placed where their first item was
+fn group<F>(mut bp_book: Value, mut group_of: F) -> Value
+where
+    F: FnMut(&mut Value) -> Option<String>,
+{
+    let (children, _) = common::take_book_children(&mut bp_book);
+    let version = bp_book[factorio_structs::FACTORIO_BP_BOOK_KEY]["version"].clone();
+
+    let mut grouped: Vec<Value> = vec![];
+    let mut group_pos: BTreeMap<String, usize> = BTreeMap::new();
+    for mut child in children {
+        match group_of(&mut child) {
+            None => grouped.push(child),
+            Some(group_name) => {
+                let pos = *group_pos.entry(group_name.clone()).or_insert_with(|| {
+                    grouped.push(json!({
+                        factorio_structs::FACTORIO_BP_BOOK_KEY: {
+                            "item": "blueprint-book",
+                            "label": group_name,
+                            "active_index": 0,
+                            "version": version,
+                            "blueprints": []
+                        }
+                    }));
+                    grouped.len() - 1
+                });
+                grouped[pos][factorio_structs::FACTORIO_BP_BOOK_KEY]["blueprints"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(child);
+            }
+        }
+    }
+
+    for pos in group_pos.values() {
+        let sub_book = &mut grouped[*pos];
+        let (sub_children, _) = common::take_book_children(sub_book);
+        common::set_book_children(sub_book, sub_children, None);
+    }
+
+    common::set_book_children(&mut bp_book, grouped, None);
+    bp_book
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn blueprint(label: &str, entities: &[&str]) -> Value {
+        let entities: Vec<Value> = entities
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| json!({"entity_number": idx + 1, "name": name, "position": {"x": idx, "y": 0}}))
+            .collect();
+        json!({"blueprint": {"item": "blueprint", "label": label, "version": 1, "entities": entities}})
+    }
+
+    fn book(label: &str, children: Vec<Value>) -> Value {
+        let children = children
+            .into_iter()
+            .enumerate()
+            .map(|(index, mut child)| {
+                child["index"] = index.into();
+                child
+            })
+            .collect::<Vec<_>>();
+        json!({"blueprint_book": {
+            "item": "blueprint-book", "label": label, "active_index": 0, "version": 1,
+            "blueprints": children
+        }})
+    }
+
+    /// Labels of the children, sub-books listed with their contents
+    fn outline(bp_book: &Value) -> Vec<String> {
+        common::book_children(bp_book)
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(pos, child)| {
+                assert_eq!(common::slot_index(child), Some(pos as u64));
+                match BlueprintType::classify(child) {
+                    BlueprintType::Book(label) => {
+                        format!("{}[{}]", label, outline(child).join(","))
+                    }
+                    BlueprintType::Blueprint(label) | BlueprintType::UpgradePlanner(label) => label,
+                    _ => String::new(),
+                }
+            })
+            .collect()
+    }
+
+    fn nested() -> Value {
+        book(
+            "root",
+            vec![
+                blueprint("top", &["pipe"]),
+                book(
+                    "oil",
+                    vec![
+                        book("cracking", vec![blueprint("heavy", &["chemical-plant"])]),
+                        json!({"upgrade_planner": {"label": "pipes", "settings": {}}}),
+                    ],
+                ),
+                blueprint("last", &[]),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_flatten() {
+        let flat = flatten(nested(), "/");
+        assert_eq!(
+            outline(&flat),
+            ["top", "oil/cracking/heavy", "oil/pipes", "last"]
+        );
+        assert_eq!(flat["blueprint_book"]["label"], "root");
+    }
+
+    #[test]
+    fn test_group_by_prefix_restores_flattened() {
+        let flat = flatten(nested(), "/");
+        assert_eq!(
+            outline(&group_by_prefix(flat.clone(), "/", None)),
+            ["top", "oil[cracking[heavy],pipes]", "last"]
+        );
+        assert_eq!(
+            outline(&group_by_prefix(flat, "/", Some(1))),
+            ["top", "oil[cracking/heavy,pipes]", "last"]
+        );
+    }
+
+    #[test]
+    fn test_group_by_entity() {
+        let flat = book(
+            "root",
+            vec![
+                blueprint("a", &["inserter", "inserter", "stone-furnace"]),
+                blueprint("b", &["stone-furnace", "inserter"]),
+                blueprint("c", &[]),
+                blueprint("d", &["inserter"]),
+            ],
+        );
+        assert_eq!(outline(&group_by_entity(flat)), ["inserter[a,b,d]", "c"]);
+    }
+}
@@ -0,0 +1,474 @@
+//! Previews a blueprint in the terminal as a grid of characters, one cell per tile.
+//!
+//! Belts and inserters are drawn as arrows in their direction, poles as crosses, and larger
+//! entities as boxes labelled with their initials, coloured by the kind of entity. Blueprints
+//! larger than the terminal can be panned and zoomed out.
+
+use std::io::{self, IsTerminal, Write};
+use std::process::exit;
+
+use crossterm::event::{self, Event, KeyCode};
+use crossterm::style::{self, Color, Stylize};
+use crossterm::{cursor, terminal, QueueableCommand};
+use serde_json::Value;
+
+use crate::args;
+use crate::common::{self, BlueprintType};
+use crate::factorio_structs;
+use crate::progress::{self, CommandType, Screen};
+use crate::render::{self, Kind};
+use crate::source::Source;
+
+/// Belt arrows for the eight directions, starting north
+const BELT_ARROWS: [char; 8] = ['↑', '↗', '→', '↘', '↓', '↙', '←', '↖'];
+/// Inserter arrows for the four directions the items move in, starting north
+const INSERTER_ARROWS: [char; 4] = ['▲', '►', '▼', '◄'];
+/// Glyph of a tile without an entity on it
+const TILE_GLYPH: char = '·';
+
+/// A character of the preview
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub glyph: char,
+    /// Kind of the entity drawn, none for tiles
+    pub kind: Option<Kind>,
+}
+
+impl Cell {
+    pub fn colour(&self) -> Color {
+        match self.kind {
+            None => Color::DarkGrey,
+            Some(Kind::Belt) => Color::Yellow,
+            Some(Kind::Inserter) => Color::Cyan,
+            Some(Kind::Production) => Color::Blue,
+            Some(Kind::Mining) => Color::DarkYellow,
+            Some(Kind::Power) => Color::Red,
+            Some(Kind::Logistics) => Color::Magenta,
+            Some(Kind::Fluid) => Color::DarkCyan,
+            Some(Kind::Rail) => Color::Grey,
+            Some(Kind::Circuit) => Color::Green,
+            Some(Kind::Defense) => Color::DarkMagenta,
+            Some(Kind::Other) => Color::White,
+        }
+    }
+}
+
+/// Tiles of a blueprint as cells, row by row
+#[derive(Debug, Clone)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<Option<Cell>>,
+}
+
+/// Entity placed on the grid
+struct Placed<'a> {
+    name: &'a str,
+    left: i64,
+    top: i64,
+    width: usize,
+    height: usize,
+    kind: Kind,
+    /// Direction rounded to one of eight, none if the entity has no direction
+    direction: Option<usize>,
+}
+
+impl Grid {
+    /// Draws a blueprint object (the value under the "blueprint" key)
+    pub fn from(blueprint: &Value) -> Grid {
+        let direction_count = common::direction_count(blueprint);
+
+        let tiles: Vec<(i64, i64)> = common::array(blueprint, "tiles")
+            .iter()
+            .filter_map(common::position)
+            .map(|(x, y)| (x.floor() as i64, y.floor() as i64))
+            .collect();
+        let placed: Vec<Placed> = common::array(blueprint, "entities")
+            .iter()
+            .filter_map(|entity| {
+                let name = entity.get("name")?.as_str()?;
+                let (x, y) = common::position(entity)?;
+                let direction = entity.get("direction").and_then(|d| d.as_u64());
+                let (width, height, kind) =
+                    render::turned_size(name, direction.unwrap_or(0), direction_count);
+                Some(Placed {
+                    name,
+                    // rounding absorbs positions that are slightly off the grid
+                    left: (x - width / 2.0).round() as i64,
+                    top: (y - height / 2.0).round() as i64,
+                    width: width as usize,
+                    height: height as usize,
+                    kind,
+                    direction: direction.map(|d| (d * 8 / direction_count) as usize % 8),
+                })
+            })
+            .collect();
+
+        let corners = tiles
+            .iter()
+            .map(|(x, y)| (*x, *y, *x + 1, *y + 1))
+            .chain(placed.iter().map(|entity| {
+                (
+                    entity.left,
+                    entity.top,
+                    entity.left + entity.width as i64,
+                    entity.top + entity.height as i64,
+                )
+            }));
+        let (min_x, min_y, max_x, max_y) = corners.fold(
+            (i64::MAX, i64::MAX, i64::MIN, i64::MIN),
+            |(min_x, min_y, max_x, max_y), (left, top, right, bottom)| {
+                (
+                    min_x.min(left),
+                    min_y.min(top),
+                    max_x.max(right),
+                    max_y.max(bottom),
+                )
+            },
+        );
+        if min_x > max_x {
+            return Grid {
+                width: 0,
+                height: 0,
+                cells: vec![],
+            };
+        }
+
+        let (width, height) = ((max_x - min_x) as usize, (max_y - min_y) as usize);
+        let mut grid = Grid {
+            width,
+            height,
+            cells: vec![None; width * height],
+        };
+        for (x, y) in tiles {
+            grid.set(
+                x - min_x,
+                y - min_y,
+                Cell {
+                    glyph: TILE_GLYPH,
+                    kind: None,
+                },
+            );
+        }
+        for entity in placed {
+            let glyphs = entity_glyphs(&entity);
+            for row in 0..entity.height {
+                for col in 0..entity.width {
+                    grid.set(
+                        entity.left - min_x + col as i64,
+                        entity.top - min_y + row as i64,
+                        Cell {
+                            glyph: glyphs[row * entity.width + col],
+                            kind: Some(entity.kind),
+                        },
+                    );
+                }
+            }
+        }
+        grid
+    }
+
+    fn set(&mut self, x: i64, y: i64, cell: Cell) {
+        if (0..self.width as i64).contains(&x) && (0..self.height as i64).contains(&y) {
+            self.cells[y as usize * self.width + x as usize] = Some(cell);
+        }
+    }
+
+    /// Cell shown for the block of `zoom` by `zoom` tiles at a position.
+    /// Entities are preferred over tiles, the first in reading order
+    pub fn block(&self, x: usize, y: usize, zoom: usize) -> Option<Cell> {
+        let mut shown = None;
+        for row in y..(y + zoom).min(self.height) {
+            for col in x..(x + zoom).min(self.width) {
+                match self.cells[row * self.width + col] {
+                    Some(cell) if cell.kind.is_some() => return Some(cell),
+                    Some(cell) => shown = shown.or(Some(cell)),
+                    None => (),
+                }
+            }
+        }
+        shown
+    }
+
+    /// Smallest zoom, a power of two, showing the whole grid in an area of the terminal
+    pub fn fit_zoom(&self, cols: usize, rows: usize) -> usize {
+        let mut zoom = 1;
+        while self.width.div_ceil(zoom) > cols.max(1) || self.height.div_ceil(zoom) > rows.max(1) {
+            zoom *= 2;
+        }
+        zoom
+    }
+
+    /// The grid as plain text at a zoom
+    pub fn lines(&self, zoom: usize) -> Vec<String> {
+        (0..self.height)
+            .step_by(zoom)
+            .map(|y| {
+                (0..self.width)
+                    .step_by(zoom)
+                    .map(|x| self.block(x, y, zoom).map_or(' ', |cell| cell.glyph))
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+}
+
+/// Glyphs of an entity's footprint, row by row
+fn entity_glyphs(entity: &Placed) -> Vec<char> {
+    let (width, height) = (entity.width, entity.height);
+    let label: Vec<char> = render::short_name(entity.name).chars().collect();
+    let fill = |glyph: char| vec![glyph; width * height];
+
+    match entity.kind {
+        Kind::Belt => return fill(BELT_ARROWS[entity.direction.unwrap_or(0)]),
+        // inserters face their pickup side, the arrow follows the items
+        Kind::Inserter => {
+            return fill(INSERTER_ARROWS[(entity.direction.unwrap_or(0) / 2 + 2) % 4])
+        }
+        _ if entity.name.ends_with("electric-pole") || entity.name == "substation" => {
+            return fill('┼')
+        }
+        _ if width < 2 || height < 2 => {
+            let mut glyphs = fill('▪');
+            for (glyph, letter) in glyphs.iter_mut().zip(&label) {
+                *glyph = *letter;
+            }
+            return glyphs;
+        }
+        _ => (),
+    }
+
+    // a box, labelled on its middle row
+    let mut glyphs = fill(' ');
+    for row in 0..height {
+        for col in 0..width {
+            glyphs[row * width + col] = match (row, col) {
+                (0, 0) => '┌',
+                (0, c) if c == width - 1 => '┐',
+                (r, 0) if r == height - 1 => '└',
+                (r, c) if r == height - 1 && c == width - 1 => '┘',
+                (0, _) => '─',
+                (r, _) if r == height - 1 => '─',
+                (_, 0) => '│',
+                (_, c) if c == width - 1 => '│',
+                _ => ' ',
+            };
+        }
+    }
+    let (first, last) = match width > 2 {
+        true => (1, width - 1),
+        false => (0, width),
+    };
+    let shown = label.len().min(last - first);
+    let start = first + (last - first - shown) / 2;
+    let row = height / 2;
+    glyphs[row * width + start..row * width + start + shown].copy_from_slice(&label[..shown]);
+    glyphs
+}
+
+/// Part of a grid shown in the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct View {
+    /// Tile at the top left corner
+    pub x: usize,
+    pub y: usize,
+    /// Tiles per cell along each side
+    pub zoom: usize,
+}
+
+impl View {
+    /// Moves by a number of cells, staying on the grid
+    pub fn pan(&mut self, grid: &Grid, cols: i64, rows: i64) {
+        let step = |pos: usize, cells: i64, size: usize| -> usize {
+            (pos as i64 + cells * self.zoom as i64).clamp(0, size.saturating_sub(1) as i64) as usize
+        };
+        self.x = step(self.x, cols, grid.width);
+        self.y = step(self.y, rows, grid.height);
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = (self.zoom / 2).max(1);
+    }
+
+    pub fn zoom_out(&mut self, grid: &Grid) {
+        if self.zoom < grid.width.max(grid.height) {
+            self.zoom *= 2;
+        }
+    }
+
+    /// Queues the view into an area of the terminal
+    pub fn queue<W: Write>(
+        &self,
+        out: &mut W,
+        grid: &Grid,
+        (left, top, cols, rows): (u16, u16, u16, u16),
+    ) -> io::Result<()> {
+        for row in 0..rows {
+            out.queue(cursor::MoveTo(left, top + row))?;
+            let y = self.y + row as usize * self.zoom;
+            for col in 0..cols {
+                let x = self.x + col as usize * self.zoom;
+                let cell = match x < grid.width && y < grid.height {
+                    true => grid.block(x, y, self.zoom),
+                    false => None,
+                };
+                match cell {
+                    Some(cell) => {
+                        out.queue(style::PrintStyledContent(cell.glyph.with(cell.colour())))?
+                    }
+                    None => out.queue(style::Print(' '))?,
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Worker {
+    pub source: Source,
+}
+
+impl Worker {
+    pub fn from(show_args: &args::Show) -> Worker {
+        Worker {
+            source: Source::from(&show_args.source),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let show_error = |err_msg: String| -> ! {
+            progress::Tracker::new_quiet_sync(CommandType::Show)
+                .lock()
+                .unwrap()
+                .error_additional(err_msg);
+            exit(1);
+        };
+
+        let blueprint_obj = match self.source.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => show_error(err_msg),
+        };
+        let label = match BlueprintType::classify(&blueprint_obj) {
+            BlueprintType::Blueprint(_label) => _label,
+            _ => show_error(format!("{:?} is not a blueprint", self.source)),
+        };
+        let grid = Grid::from(&blueprint_obj[factorio_structs::FACTORIO_BP_KEY]);
+
+        // piped output gets the whole blueprint as text
+        if !io::stdout().is_terminal() {
+            for line in grid.lines(1) {
+                println!("{}", line);
+            }
+            return;
+        }
+
+        if let Err(err) = Worker::interactive(&grid, &label) {
+            show_error(format!("terminal error: {}", err));
+        }
+    }
+
+    /// Shows the grid until the user quits, panning with the arrow keys and zooming with +/-
+    fn interactive(grid: &Grid, label: &str) -> io::Result<()> {
+        let mut screen = Screen::enter()?;
+        let (cols, rows) = Screen::size();
+        let mut view = View {
+            x: 0,
+            y: 0,
+            zoom: grid.fit_zoom(cols as usize, rows.saturating_sub(1) as usize),
+        };
+
+        loop {
+            let (cols, rows) = Screen::size();
+            let rows = rows.saturating_sub(1);
+            let out = screen.out();
+            out.queue(terminal::Clear(terminal::ClearType::All))?;
+            view.queue(out, grid, (0, 0, cols, rows))?;
+            out.queue(cursor::MoveTo(0, rows))?;
+            out.queue(style::PrintStyledContent(
+                format!(
+                    "{}  {},{}  1:{}  arrows/hjkl pan  +/- zoom  0 fit  q quit",
+                    label, view.x, view.y, view.zoom
+                )
+                .reverse(),
+            ))?;
+            out.flush()?;
+
+            let (pan_cols, pan_rows) = ((cols / 4).max(1) as i64, (rows / 4).max(1) as i64);
+            if let Event::Key(key) = event::read()? {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Left | KeyCode::Char('h') => view.pan(grid, -pan_cols, 0),
+                    KeyCode::Right | KeyCode::Char('l') => view.pan(grid, pan_cols, 0),
+                    KeyCode::Up | KeyCode::Char('k') => view.pan(grid, 0, -pan_rows),
+                    KeyCode::Down | KeyCode::Char('j') => view.pan(grid, 0, pan_rows),
+                    KeyCode::Char('+') | KeyCode::Char('=') => view.zoom_in(),
+                    KeyCode::Char('-') => view.zoom_out(grid),
+                    KeyCode::Char('0') => {
+                        view = View {
+                            x: 0,
+                            y: 0,
+                            zoom: grid.fit_zoom(cols as usize, rows as usize),
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn grid() -> Grid {
+        Grid::from(&json!({"label": "preview", "version": 1, "entities": [
+            {"entity_number": 1, "name": "assembling-machine-2", "position": {"x": 1.5, "y": 1.5}},
+            {"entity_number": 2, "name": "transport-belt", "position": {"x": 3.5, "y": 0.5},
+             "direction": 4},
+            {"entity_number": 3, "name": "inserter", "position": {"x": 3.5, "y": 1.5},
+             "direction": 2},
+            {"entity_number": 4, "name": "small-electric-pole", "position": {"x": 3.5, "y": 2.5}},
+            {"entity_number": 5, "name": "decider-combinator", "position": {"x": 5, "y": 0.5},
+             "direction": 2}
+        ], "tiles": [{"name": "concrete", "position": {"x": 0, "y": 3}}]}))
+    }
+
+    #[test]
+    fn test_grid_glyphs() {
+        let grid = grid();
+        assert_eq!((grid.width, grid.height), (6, 4));
+        assert_eq!(grid.lines(1), ["┌─┐↓DC", "│A│◄", "└─┘┼", "·"]);
+    }
+
+    #[test]
+    fn test_zoom_and_pan() {
+        let grid = grid();
+        assert_eq!(grid.fit_zoom(80, 24), 1);
+        assert_eq!(grid.fit_zoom(3, 3), 2);
+        // each cell shows the first entity of its block
+        assert_eq!(grid.lines(2), ["┌┐D", "└┘"]);
+
+        let mut view = View {
+            x: 0,
+            y: 0,
+            zoom: 2,
+        };
+        view.pan(&grid, 1, 5);
+        assert_eq!(
+            view,
+            View {
+                x: 2,
+                y: 3,
+                zoom: 2
+            }
+        );
+        view.pan(&grid, -4, 0);
+        assert_eq!((view.x, view.y), (0, 3));
+    }
+}
@@ -0,0 +1,131 @@
+//! Resolves the source argument of a command into a blueprint JSON value.
+//!
+//! A source can be a blueprint string, a file containing a blueprint string,
+//! a single JSON file or directory tree created by import, or the clipboard.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use copypasta::{ClipboardContext, ClipboardProvider};
+use serde_json::Value;
+
+use crate::progress::{self, CommandType};
+use crate::{common, export};
+
+/// Source argument that reads from the clipboard
+pub const CLIPBOARD_SOURCE: &str = "clipboard";
+
+/// Where a blueprint is read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Blueprint string in the clipboard
+    Clipboard,
+    /// Book directory created by import
+    Tree(PathBuf),
+    /// Single JSON file created by import
+    JsonFile(PathBuf),
+    /// File containing a blueprint string
+    StringFile(PathBuf),
+    /// Blueprint string given directly
+    String(String),
+}
+
+impl Source {
+    /// Determines the source type from a command line argument.
+    /// Existing paths take precedence over blueprint strings.
+    pub fn from(source: &str) -> Source {
+        let source_path = Path::new(source);
+
+        if source == CLIPBOARD_SOURCE {
+            Source::Clipboard
+        } else if source_path.is_dir() {
+            Source::Tree(source_path.to_path_buf())
+        } else if source_path.is_file() {
+            match source_path.extension() {
+                Some(ext) if ext.eq_ignore_ascii_case("json") => {
+                    Source::JsonFile(source_path.to_path_buf())
+                }
+                _ => Source::StringFile(source_path.to_path_buf()),
+            }
+        } else {
+            Source::String(source.to_string())
+        }
+    }
+
+    /// Reads the source into a blueprint JSON value.
+    /// The slot index of the top-level item is removed, as it is not inside a book.
+    /// Returns an error message if an error occurs
+    pub fn read(&self) -> Result<Value, String> {
+        let mut blueprint_obj = match self {
+            Source::Clipboard => {
+                let bp_string = match ClipboardContext::new()
+                    .map_err(|e| e.to_string())
+                    .and_then(|mut clipboard| clipboard.get_contents().map_err(|e| e.to_string()))
+                {
+                    Ok(_contents) => _contents,
+                    Err(_) => return Err("clipboard empty".to_string()),
+                };
+                Source::parse_string(&bp_string)?
+            }
+            Source::Tree(book_dir) => {
+                let prog_tracker = progress::Tracker::new_quiet_sync(CommandType::Export);
+                export::Worker::read_book_recursive(&prog_tracker, book_dir)?
+            }
+            Source::JsonFile(json_file) => export::Worker::read_blueprint(json_file)?,
+            Source::StringFile(string_file) => match fs::read_to_string(string_file) {
+                Ok(bp_string) => Source::parse_string(&bp_string)?,
+                Err(_) => return Err(format!("{:?}: unable to read file", string_file)),
+            },
+            Source::String(bp_string) => Source::parse_string(bp_string)?,
+        };
+
+        if let Some(head) = blueprint_obj.as_object_mut() {
+            head.remove("index");
+        }
+
+        Ok(blueprint_obj)
+    }
+
+    /// Inflates and parses a blueprint string
+    fn parse_string(bp_string: &str) -> Result<Value, String> {
+        let inflated = common::factorio_inflate(bp_string)?;
+        match serde_json::from_str(&inflated) {
+            Ok(_obj) => Ok(_obj),
+            Err(_) => Err("json parse error. check if blueprint string is valid".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_source_from_argument() {
+        assert_eq!(Source::from("clipboard"), Source::Clipboard);
+        assert_eq!(Source::from("src"), Source::Tree(PathBuf::from("src")));
+        assert_eq!(
+            Source::from("Cargo.toml"),
+            Source::StringFile(PathBuf::from("Cargo.toml"))
+        );
+        assert_eq!(
+            Source::from("0eNqrVkrKTEpNLPLMyyotykxOVbICAEmRByI="),
+            Source::String("0eNqrVkrKTEpNLPLMyyotykxOVbICAEmRByI=".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_string_strips_index() {
+        let blueprint = json!({
+            "blueprint": {"item": "blueprint", "label": "source", "version": 1},
+            "index": 3
+        });
+        let bp_string =
+            common::factorio_deflate(&blueprint, common::CompressionLevel::Default).unwrap();
+
+        let read = Source::String(bp_string).read().unwrap();
+        assert_eq!(read["blueprint"]["label"], "source");
+        assert!(read.get("index").is_none());
+    }
+}
@@ -0,0 +1,388 @@
+//! Mirrors, rotates and moves the contents of blueprints.
+//!
+//! Positions are transformed around the blueprint's origin: first mirrored, then rotated
+//! clockwise, then offset. Directions follow the entities, with the adjustments the game makes
+//! when flipping a blueprint: splitter priorities swap sides, and rail signals and train stops
+//! turn around so they stay on the right-hand side of the track.
+
+use std::process::exit;
+
+use serde_json::Value;
+
+use crate::args::{self, MirrorAxis};
+use crate::common::{self, BlueprintType, CompressionLevel};
+use crate::destination::Destination;
+use crate::factorio_structs::GridSnap;
+use crate::progress::{self, CommandType};
+use crate::source::Source;
+
+/// Entities placed beside a rail, facing the direction of the trains they apply to
+const RAIL_SIDE_ENTITIES: [&str; 3] = ["rail-signal", "rail-chain-signal", "train-stop"];
+
+pub struct Worker {
+    pub source: Source,
+    pub transform: Transform,
+    pub destination: Destination,
+    pub compression: CompressionLevel,
+}
+
+/// Geometric transformation of a blueprint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Transform {
+    pub mirror: Option<MirrorAxis>,
+    /// Clockwise rotation in quarter turns
+    pub quarter_turns: u8,
+    /// Offset in tiles, applied last
+    pub offset: (i64, i64),
+}
+
+impl Transform {
+    /// Transforms a point
+    pub fn point(&self, x: f64, y: f64) -> (f64, f64) {
+        let (mut x, mut y) = match self.mirror {
+            Some(MirrorAxis::X) => (-x, y),
+            Some(MirrorAxis::Y) => (x, -y),
+            None => (x, y),
+        };
+        for _ in 0..self.quarter_turns {
+            (x, y) = (-y, x);
+        }
+        // adding zero turns -0.0 into 0.0
+        (
+            x + self.offset.0 as f64 + 0.0,
+            y + self.offset.1 as f64 + 0.0,
+        )
+    }
+
+    /// Transforms a direction, given the number of directions of the blueprint's game version
+    pub fn direction(&self, direction: u64, direction_count: u64) -> u64 {
+        let mirrored = match self.mirror {
+            Some(MirrorAxis::X) => direction_count - direction % direction_count,
+            Some(MirrorAxis::Y) => {
+                direction_count + direction_count / 2 - direction % direction_count
+            }
+            None => direction,
+        };
+        (mirrored + self.quarter_turns as u64 * direction_count / 4) % direction_count
+    }
+
+    /// Transforms the orientation of rolling stock, a fraction of a full clockwise turn from north
+    pub fn orientation(&self, orientation: f64) -> f64 {
+        let mirrored = match self.mirror {
+            Some(MirrorAxis::X) => 1.0 - orientation,
+            Some(MirrorAxis::Y) => 0.5 - orientation,
+            None => orientation,
+        };
+        (mirrored + self.quarter_turns as f64 * 0.25).rem_euclid(1.0)
+    }
+
+    /// Transforms every blueprint of a blueprint or book
+    pub fn apply(&self, unknown_bp: &mut Value) {
+        common::visit_blueprints_mut(unknown_bp, &mut |blueprint| self.apply_blueprint(blueprint));
+    }
+
+    /// Transforms the entities, tiles and grid settings of a blueprint object
+    fn apply_blueprint(&self, blueprint: &mut Value) {
+        let is_2_0 = common::is_version_2(blueprint);
+        let direction_count = common::direction_count(blueprint);
+
+        for entity in blueprint
+            .get_mut("entities")
+            .and_then(|entities| entities.as_array_mut())
+            .into_iter()
+            .flatten()
+        {
+            self.apply_entity(entity, direction_count, is_2_0);
+        }
+
+        // tiles are placed by their top left corner, so their centres are transformed
+        for tile in blueprint
+            .get_mut("tiles")
+            .and_then(|tiles| tiles.as_array_mut())
+            .into_iter()
+            .flatten()
+        {
+            if let Some((x, y)) = common::position(tile) {
+                let (x, y) = self.point(x + 0.5, y + 0.5);
+                set_position(tile, x - 0.5, y - 0.5);
+            }
+        }
+
+        self.apply_grid(blueprint);
+    }
+
+    fn apply_entity(&self, entity: &mut Value, direction_count: u64, is_2_0: bool) {
+        if let Some((x, y)) = common::position(entity) {
+            let (x, y) = self.point(x, y);
+            set_position(entity, x, y);
+        }
+
+        let name = entity
+            .get("name")
+            .and_then(|name| name.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let old_direction = entity
+            .get("direction")
+            .and_then(|d| d.as_u64())
+            .unwrap_or(0);
+        let mut direction = self.direction(old_direction, direction_count);
+
+        if self.mirror.is_some() {
+            if name == "curved-rail" && !is_2_0 {
+                // 1.1 curved rails are numbered by the diagonal end of the curve
+                let mirrored = match self.mirror {
+                    Some(MirrorAxis::X) => (9 - old_direction % 8) % 8,
+                    _ => (13 - old_direction % 8) % 8,
+                };
+                direction = (mirrored + self.quarter_turns as u64 * 2) % 8;
+            }
+            if RAIL_SIDE_ENTITIES.contains(&name.as_str()) {
+                direction = (direction + direction_count / 2) % direction_count;
+            }
+
+            for priority in ["input_priority", "output_priority"] {
+                let swapped = match entity.get(priority).and_then(|side| side.as_str()) {
+                    Some("left") => "right",
+                    Some("right") => "left",
+                    _ => continue,
+                };
+                entity[priority] = swapped.into();
+            }
+
+            // 2.0 crafting machines keep their fluid connections in place when flipped
+            if is_2_0 && entity.get("recipe").is_some() {
+                let mirror = entity
+                    .get("mirror")
+                    .and_then(|m| m.as_bool())
+                    .unwrap_or(false);
+                entity["mirror"] = (!mirror).into();
+            }
+        }
+
+        if direction != old_direction || entity.get("direction").is_some() {
+            entity["direction"] = direction.into();
+        }
+
+        if let Some(orientation) = entity.get("orientation").and_then(|o| o.as_f64()) {
+            entity["orientation"] = self.orientation(orientation).into();
+        }
+    }
+
+    /// Transforms the grid of a blueprint with snapping. Positions relative to an absolute grid
+    /// are the grid's corner in blueprint coordinates, so they are transformed like a point
+    fn apply_grid(&self, blueprint: &mut Value) {
+        let snap_to_grid: GridSnap = match blueprint.get("snap-to-grid").and_then(grid_snap) {
+            Some(_grid) => _grid,
+            None => return,
+        };
+        let rotated_grid = match self.quarter_turns % 2 {
+            0 => snap_to_grid.clone(),
+            _ => GridSnap {
+                x: snap_to_grid.y,
+                y: snap_to_grid.x,
+            },
+        };
+
+        if let Some(relative) = blueprint
+            .get("position-relative-to-grid")
+            .and_then(grid_snap)
+        {
+            let (x, y) = self.point(relative.x as f64, relative.y as f64);
+            let relative = GridSnap {
+                x: (x as i64).rem_euclid(rotated_grid.x.max(1) as i64) as u32,
+                y: (y as i64).rem_euclid(rotated_grid.y.max(1) as i64) as u32,
+            };
+            blueprint["position-relative-to-grid"] = serde_json::to_value(relative).unwrap();
+        }
+        blueprint["snap-to-grid"] = serde_json::to_value(rotated_grid).unwrap();
+    }
+}
+
+/// Grid size or position of a blueprint with snapping
+fn grid_snap(value: &Value) -> Option<GridSnap> {
+    serde_json::from_value(value.clone()).ok()
+}
+
+/// Sets the position of an entity or tile, writing whole numbers without a fraction
+fn set_position(object: &mut Value, x: f64, y: f64) {
+    let number = |coordinate: f64| -> Value {
+        match coordinate.fract() == 0.0 {
+            true => (coordinate as i64).into(),
+            false => coordinate.into(),
+        }
+    };
+    object["position"] = serde_json::json!({"x": number(x), "y": number(y)});
+}
+
+/// Parses an offset given as "dx,dy" in whole tiles
+pub fn parse_offset(offset: &str) -> Result<(i64, i64), String> {
+    let parsed = offset
+        .split_once(',')
+        .and_then(|(dx, dy)| Some((dx.trim().parse().ok()?, dy.trim().parse().ok()?)));
+    match parsed {
+        Some(_offset) => Ok(_offset),
+        None => Err(format!(
+            "invalid offset \"{}\", expected dx,dy in whole tiles",
+            offset
+        )),
+    }
+}
+
+impl Worker {
+    pub fn from(transform_args: &args::Transform) -> Worker {
+        let offset = match transform_args.offset.as_deref().map(parse_offset) {
+            Some(Ok(_offset)) => _offset,
+            Some(Err(err_msg)) => {
+                progress::Tracker::new_quiet_sync(CommandType::Transform)
+                    .lock()
+                    .unwrap()
+                    .error_additional(err_msg);
+                exit(1);
+            }
+            None => (0, 0),
+        };
+
+        Worker {
+            source: Source::from(&transform_args.source),
+            transform: Transform {
+                mirror: transform_args.mirror,
+                quarter_turns: (transform_args.rotate.unwrap_or(0) / 90) as u8,
+                offset,
+            },
+            destination: Destination::from(&transform_args.output),
+            compression: transform_args.output.compression.unwrap_or_default(),
+        }
+    }
+
+    /// Main calling method for struct
+    pub fn exec(&self) {
+        let transform_error = |err_msg: String| -> ! {
+            progress::Tracker::new_quiet_sync(CommandType::Transform)
+                .lock()
+                .unwrap()
+                .error_additional(err_msg);
+            exit(1);
+        };
+
+        if self.transform == Transform::default() {
+            transform_error("nothing to do, give --mirror, --rotate or --offset".to_string());
+        }
+
+        let mut blueprint_obj = match self.source.read() {
+            Ok(_obj) => _obj,
+            Err(err_msg) => transform_error(err_msg),
+        };
+        match BlueprintType::classify(&blueprint_obj) {
+            BlueprintType::Blueprint(_) | BlueprintType::Book(_) => (),
+            _ => transform_error(format!("{:?} is not a blueprint or book", self.source)),
+        }
+
+        self.transform.apply(&mut blueprint_obj);
+
+        if let Err(err_msg) = self.destination.write(blueprint_obj, self.compression) {
+            transform_error(err_msg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn transform(mirror: Option<MirrorAxis>, quarter_turns: u8) -> Transform {
+        Transform {
+            mirror,
+            quarter_turns,
+            offset: (0, 0),
+        }
+    }
+
+    #[test]
+    fn test_points_and_directions() {
+        let clockwise = transform(None, 1);
+        assert_eq!(clockwise.point(2.5, 1.0), (-1.0, 2.5));
+        assert_eq!(clockwise.direction(0, 8), 2);
+        assert_eq!(clockwise.direction(6, 8), 0);
+        assert_eq!(clockwise.direction(12, 16), 0);
+        assert_eq!(clockwise.orientation(0.75), 0.0);
+
+        let flip_x = transform(Some(MirrorAxis::X), 0);
+        assert_eq!(flip_x.point(2.5, 1.0), (-2.5, 1.0));
+        assert_eq!(flip_x.direction(2, 8), 6);
+        assert_eq!(flip_x.direction(0, 8), 0);
+        assert_eq!(flip_x.direction(1, 8), 7);
+
+        let flip_y = transform(Some(MirrorAxis::Y), 0);
+        assert_eq!(flip_y.direction(0, 8), 4);
+        assert_eq!(flip_y.direction(2, 8), 2);
+        assert_eq!(flip_y.direction(4, 16), 4);
+
+        let moved = Transform {
+            offset: (3, -2),
+            ..transform(Some(MirrorAxis::X), 2)
+        };
+        // mirrored, rotated half a turn, then moved
+        assert_eq!(moved.point(1.5, 0.5), (4.5, -2.5));
+    }
+
+    #[test]
+    fn test_mirror_quirks() {
+        let mut blueprint = json!({"blueprint": {"label": "quirks", "version": 281479275675648u64,
+            "entities": [
+                {"entity_number": 1, "name": "fast-splitter", "position": {"x": 0.5, "y": 0},
+                 "direction": 0, "input_priority": "left", "output_priority": "right"},
+                {"entity_number": 2, "name": "rail-signal", "position": {"x": 2.5, "y": 0.5},
+                 "direction": 2},
+                {"entity_number": 3, "name": "curved-rail", "position": {"x": 4, "y": 4},
+                 "direction": 0},
+                {"entity_number": 4, "name": "underground-belt", "position": {"x": 0.5, "y": 3.5},
+                 "direction": 2, "type": "input"},
+                {"entity_number": 5, "name": "wooden-chest", "position": {"x": 1.5, "y": 1.5}}
+            ],
+            "tiles": [{"name": "concrete", "position": {"x": 0, "y": 2}}]
+        }});
+
+        transform(Some(MirrorAxis::X), 0).apply(&mut blueprint);
+        let entities = &blueprint["blueprint"]["entities"];
+
+        assert_eq!(entities[0]["position"], json!({"x": -0.5, "y": 0}));
+        assert_eq!(entities[0]["input_priority"], "right");
+        assert_eq!(entities[0]["output_priority"], "left");
+        // the signal stays on the right-hand side of trains going east
+        assert_eq!(entities[1]["direction"], 2);
+        assert_eq!(entities[2]["direction"], 1);
+        assert_eq!(entities[3]["direction"], 6);
+        assert_eq!(entities[3]["type"], "input");
+        assert!(entities[4].get("direction").is_none());
+        assert_eq!(
+            blueprint["blueprint"]["tiles"][0]["position"],
+            json!({"x": -1, "y": 2})
+        );
+    }
+
+    #[test]
+    fn test_rotate_grid() {
+        let mut blueprint = json!({"blueprint": {"label": "grid", "version": 1,
+            "snap-to-grid": {"x": 4, "y": 2},
+            "absolute-snapping": true,
+            "position-relative-to-grid": {"x": 1, "y": 0},
+            "tiles": [{"name": "concrete", "position": {"x": 0, "y": 0}}]
+        }});
+
+        transform(None, 1).apply(&mut blueprint);
+        let inner = &blueprint["blueprint"];
+
+        assert_eq!(inner["snap-to-grid"], json!({"x": 2, "y": 4}));
+        assert_eq!(inner["position-relative-to-grid"], json!({"x": 0, "y": 1}));
+        assert_eq!(inner["tiles"][0]["position"], json!({"x": -1, "y": 0}));
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("3,-2"), Ok((3, -2)));
+        assert!(parse_offset("3").is_err());
+        assert!(parse_offset("1.5,2").is_err());
+    }
+}